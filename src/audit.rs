@@ -0,0 +1,308 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{Fail2banBackend, JailSummary};
+use crate::config::Config;
+
+pub const DEFAULT_AUDIT_LOG: &str = "/var/log/f2bs-audit.log";
+const SYSLOG_SOCKET: &str = "/dev/log";
+/// How many of the newest records the TUI panel loads.
+pub const PANEL_RECORDS: usize = 1000;
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub time: String,
+    pub user: String,
+    pub source: String,
+    pub action: String,
+    pub jail: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ips: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    pub result: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Where audit records go: a JSON lines file and, optionally, syslog (which
+/// journald also reads).
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    syslog: bool,
+    source: &'static str,
+}
+
+/// The user behind the action: the one who ran sudo, not root.
+fn invoking_user() -> String {
+    user_from(|name| std::env::var(name).ok())
+}
+
+/// The first of `SUDO_USER`, `USER` and `LOGNAME` that `var` finds set.
+fn user_from(var: impl Fn(&str) -> Option<String>) -> String {
+    ["SUDO_USER", "USER", "LOGNAME"]
+        .into_iter()
+        .filter_map(var)
+        .find(|user| !user.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+impl AuditLog {
+    /// `audit_log` unset means the default file, an empty string turns the
+    /// file off. `source` tells TUI and command line actions apart.
+    pub fn from_config(config: &Config, source: &'static str) -> Self {
+        let path = match config.audit_log.as_deref() {
+            None => Some(PathBuf::from(DEFAULT_AUDIT_LOG)),
+            Some("") => None,
+            Some(path) => Some(PathBuf::from(path)),
+        };
+        Self {
+            path,
+            syslog: config.audit_syslog,
+            source,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Makes sure the log file can be appended to, so a broken audit trail
+    /// is reported up front rather than discovered later.
+    pub fn check(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("cannot write audit log {}", path.display()))?;
+        Ok(())
+    }
+
+    fn write(&self, record: &AuditRecord) {
+        let Ok(line) = serde_json::to_string(record) else {
+            return;
+        };
+        if let Some(path) = &self.path
+            && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path)
+        {
+            let _ = writeln!(file, "{line}");
+        }
+        if self.syslog {
+            // authpriv facility; info for success, warning for failures.
+            let priority = if record.error.is_some() { 10 * 8 + 4 } else { 10 * 8 + 6 };
+            let message = format!("<{priority}>f2bs[{}]: {line}", std::process::id());
+            if let Ok(socket) = UnixDatagram::unbound() {
+                let _ = socket.send_to(message.as_bytes(), SYSLOG_SOCKET);
+            }
+        }
+    }
+
+    fn record<T>(&self, action: &str, jail: &str, ips: &[&str], args: &[&str], result: &Result<T>) {
+        self.write(&AuditRecord {
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            user: invoking_user(),
+            source: self.source.to_string(),
+            action: action.to_string(),
+            jail: jail.to_string(),
+            ips: ips.iter().map(|ip| ip.to_string()).collect(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            result: if result.is_ok() { "ok" } else { "error" }.to_string(),
+            error: result.as_ref().err().map(|err| format!("{err:#}")),
+        });
+    }
+}
+
+/// Reads the newest `limit` records, newest first. Lines that don't parse
+/// are skipped.
+pub fn read_records(path: &Path, limit: usize) -> Result<Vec<AuditRecord>> {
+    let text = fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    Ok(text
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect())
+}
+
+/// Passes every call through to `inner` and logs the ones that change the
+/// server: bans, unbans and `set` commands.
+pub struct AuditedBackend {
+    inner: Box<dyn Fail2banBackend>,
+    log: AuditLog,
+}
+
+impl AuditedBackend {
+    pub fn new(inner: Box<dyn Fail2banBackend>, log: AuditLog) -> Self {
+        Self { inner, log }
+    }
+}
+
+impl Fail2banBackend for AuditedBackend {
+    fn jails(&mut self) -> Result<Vec<String>> {
+        self.inner.jails()
+    }
+
+    fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+        self.inner.jail_status(jail)
+    }
+
+    fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        self.inner.get(jail, args)
+    }
+
+    fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        let result = self.inner.set(jail, args);
+        self.log.record("set", jail, &[], args, &result);
+        result
+    }
+
+    fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let result = self.inner.ban(jail, ips);
+        self.log.record("ban", jail, ips, &[], &result);
+        result
+    }
+
+    fn own_ban_times(&mut self) -> bool {
+        self.inner.own_ban_times()
+    }
+
+    fn ban_with_time(&mut self, jail: &str, ips: &[&str], bantime: i64) -> Result<()> {
+        let result = self.inner.ban_with_time(jail, ips, bantime);
+        self.log.record("ban", jail, ips, &["bantime", &bantime.to_string()], &result);
+        result
+    }
+
+    fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let result = self.inner.unban(jail, ips);
+        self.log.record("unban", jail, ips, &[], &result);
+        result
+    }
+
+    fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+        self.inner.banned_in(ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    /// An audit log in a fresh temporary file.
+    fn temp_log(name: &str) -> AuditLog {
+        let path = std::env::temp_dir().join(format!("f2bs-{}-{name}.log", std::process::id()));
+        fs::remove_file(&path).ok();
+        AuditLog {
+            path: Some(path),
+            syslog: false,
+            source: "test",
+        }
+    }
+
+    #[test]
+    fn records_are_one_json_object_per_line() {
+        let record = AuditRecord {
+            time: "2026-01-01T00:00:00Z".to_string(),
+            user: "alice".to_string(),
+            source: "cli".to_string(),
+            action: "unban".to_string(),
+            jail: "sshd".to_string(),
+            ips: vec!["192.0.2.1".to_string()],
+            args: Vec::new(),
+            result: "error".to_string(),
+            error: Some("IP 192.0.2.1 is not banned".to_string()),
+        };
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            line,
+            r#"{"time":"2026-01-01T00:00:00Z","user":"alice","source":"cli","action":"unban","jail":"sshd","ips":["192.0.2.1"],"result":"error","error":"IP 192.0.2.1 is not banned"}"#
+        );
+        assert_eq!(serde_json::from_str::<AuditRecord>(&line).unwrap(), record);
+    }
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    }
+
+    fn strs(list: &[String]) -> Vec<&str> {
+        list.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn the_sudo_user_comes_first() {
+        let all = [("SUDO_USER", "alice"), ("USER", "root"), ("LOGNAME", "root")];
+        assert_eq!(user_from(env(&all)), "alice");
+        assert_eq!(user_from(env(&[("SUDO_USER", ""), ("USER", "bob"), ("LOGNAME", "root")])), "bob");
+        assert_eq!(user_from(env(&[("LOGNAME", "carol")])), "carol");
+        assert_eq!(user_from(env(&[])), "unknown");
+    }
+
+    #[test]
+    fn logs_the_calls_that_change_the_server() {
+        let log = temp_log("audited");
+        let path = log.path().unwrap().to_path_buf();
+        let mut backend = AuditedBackend::new(Box::new(MemoryBackend::demo()), log);
+        backend.jails().unwrap();
+        backend.get("sshd", &["bantime"]).unwrap();
+        backend.ban("sshd", &["203.0.113.1", "203.0.113.2"]).unwrap();
+        backend.ban_with_time("sshd", &["203.0.113.3"], 7200).unwrap();
+        backend.unban("sshd", &["198.18.0.1"]).unwrap_err();
+        backend.set("sshd", &["bantime", "60"]).unwrap();
+
+        let records = read_records(&path, 10).unwrap();
+        fs::remove_file(&path).ok();
+        let summary: Vec<(&str, Vec<&str>, Vec<&str>, &str)> = records
+            .iter()
+            .rev()
+            .map(|r| (r.action.as_str(), strs(&r.ips), strs(&r.args), r.result.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("ban", vec!["203.0.113.1", "203.0.113.2"], vec![], "ok"),
+                ("ban", vec!["203.0.113.3"], vec!["bantime", "7200"], "ok"),
+                ("unban", vec!["198.18.0.1"], vec![], "error"),
+                ("set", vec![], vec!["bantime", "60"], "ok"),
+            ]
+        );
+        assert!(records.iter().all(|r| r.jail == "sshd" && r.source == "test"));
+        assert_eq!(records[1].error.as_deref(), Some("IP 198.18.0.1 is not banned"));
+        assert_eq!(records[0].error, None);
+    }
+
+    #[test]
+    fn reading_skips_lines_that_do_not_parse() {
+        let log = temp_log("malformed");
+        let path = log.path().unwrap().to_path_buf();
+        log.record("ban", "sshd", &["192.0.2.1"], &[], &Ok(()));
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{{\"time\": \"cut off"))
+            .unwrap();
+        log.record("unban", "sshd", &["192.0.2.1"], &[], &Ok(()));
+
+        let records = read_records(&path, 10).unwrap();
+        let newest = read_records(&path, 1).unwrap();
+        fs::remove_file(&path).ok();
+        let actions: Vec<&str> = records.iter().map(|r| r.action.as_str()).collect();
+        assert_eq!(actions, ["unban", "ban"]);
+        assert_eq!(newest.len(), 1);
+        assert_eq!(newest[0].action, "unban");
+        assert!(read_records(&path, 10).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::audit::{AuditLog, AuditedBackend};
+use crate::socket::{SocketBackend, DEFAULT_SOCKET};
+use crate::{is_ban_target, parse_banned_ips, parse_jail_list, parse_status_counts};
+
+#[derive(Debug, Clone, Default)]
+pub struct JailSummary {
+    pub currently_banned: Option<u32>,
+    pub total_banned: Option<u32>,
+    pub ips: Vec<String>,
+}
+
+/// Everything f2bs needs from a fail2ban server. `get` returns the value the
+/// way `fail2ban-client get <jail> <args>` prints it, so the same parsers work
+/// for every implementation.
+pub trait Fail2banBackend: Send {
+    fn jails(&mut self) -> Result<Vec<String>>;
+    fn jail_status(&mut self, jail: &str) -> Result<JailSummary>;
+    fn get(&mut self, jail: &str, args: &[&str]) -> Result<String>;
+    /// Changes a jail setting, like `fail2ban-client set <jail> <args>`.
+    fn set(&mut self, jail: &str, args: &[&str]) -> Result<String>;
+    fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()>;
+    /// Whether the server can give a ban a time of its own with
+    /// [`ban_with_time`](Self::ban_with_time). `banip` takes no time in the
+    /// fail2ban releases f2bs knows, so only the demo server says yes.
+    fn own_ban_times(&mut self) -> bool {
+        false
+    }
+    /// Bans `ips` in `jail` for `bantime` seconds (`-1` for good) without
+    /// touching the jail's bantime.
+    fn ban_with_time(&mut self, jail: &str, ips: &[&str], bantime: i64) -> Result<()> {
+        let _ = (ips, bantime);
+        Err(anyhow!("the server for {jail} can't give a ban a time of its own"))
+    }
+    fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()>;
+    /// Jails currently banning `ip`, via fail2ban's `banned <ip>` command.
+    fn banned_in(&mut self, ip: &str) -> Result<Vec<String>>;
+}
+
+/// The backend asked for with `--backend` or `F2BS_BACKEND`; empty for auto.
+pub fn backend_kind(kind: Option<&str>) -> String {
+    kind.map(String::from)
+        .or_else(|| std::env::var("F2BS_BACKEND").ok())
+        .unwrap_or_default()
+}
+
+/// Opens the backend and wraps it so changes are written to `audit`. The
+/// demo backend is not audited since it never touches a real server.
+pub fn open_backend(
+    kind: Option<&str>,
+    socket: Option<&str>,
+    audit: AuditLog,
+) -> Result<Box<dyn Fail2banBackend>> {
+    let kind = backend_kind(kind);
+    let socket = socket
+        .map(String::from)
+        .or_else(|| std::env::var("F2BS_SOCKET").ok())
+        .unwrap_or_else(|| DEFAULT_SOCKET.to_string());
+    let backend: Box<dyn Fail2banBackend> = match kind.as_str() {
+        "demo" => return Ok(Box::new(MemoryBackend::demo())),
+        "client" => Box::new(ClientBackend),
+        "socket" => Box::new(SocketBackend::connect(socket)?),
+        "" | "auto" => match SocketBackend::connect(socket) {
+            Ok(backend) => Box::new(backend),
+            Err(_) => Box::new(ClientBackend),
+        },
+        other => return Err(anyhow!("unknown backend '{other}' (expected auto, socket, client or demo)")),
+    };
+    Ok(Box::new(AuditedBackend::new(backend, audit)))
+}
+
+pub fn run_fail2ban(args: &[&str]) -> Result<String> {
+    let output = Command::new("fail2ban-client")
+        .args(args)
+        .output()
+        .with_context(|| "failed to execute fail2ban-client")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let msg = if !stderr.is_empty() { stderr } else { stdout };
+        return Err(anyhow!(msg));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientBackend;
+
+fn parse_banned_jails(output: &str) -> Vec<String> {
+    output
+        .split(['\'', '"'])
+        .skip(1)
+        .step_by(2)
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+impl Fail2banBackend for ClientBackend {
+    fn jails(&mut self) -> Result<Vec<String>> {
+        let status = run_fail2ban(&["status"])?;
+        Ok(parse_jail_list(&status))
+    }
+
+    fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+        let output = run_fail2ban(&["status", jail])?;
+        let (currently_banned, total_banned) = parse_status_counts(&output);
+        Ok(JailSummary {
+            currently_banned,
+            total_banned,
+            ips: parse_banned_ips(&output),
+        })
+    }
+
+    fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        let mut full = vec!["get", jail];
+        full.extend_from_slice(args);
+        run_fail2ban(&full)
+    }
+
+    fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        let mut full = vec!["set", jail];
+        full.extend_from_slice(args);
+        run_fail2ban(&full)
+    }
+
+    fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let mut full = vec!["set", jail, "banip"];
+        full.extend_from_slice(ips);
+        run_fail2ban(&full).map(|_| ())
+    }
+
+    fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let mut full = vec!["set", jail, "unbanip"];
+        full.extend_from_slice(ips);
+        run_fail2ban(&full).map(|_| ())
+    }
+
+    fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+        run_fail2ban(&["banned", ip]).map(|output| parse_banned_jails(&output))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryBan {
+    pub ip: String,
+    pub start: i64,
+    /// The ban's own time; `None` follows the jail's current bantime.
+    pub bantime: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryJail {
+    pub name: String,
+    pub bantime: i64,
+    pub findtime: u64,
+    pub maxretry: u32,
+    pub total_banned: u32,
+    pub bans: Vec<MemoryBan>,
+    pub ignoreip: Vec<String>,
+}
+
+impl MemoryJail {
+    pub fn new(name: &str, bantime: i64) -> Self {
+        Self {
+            name: name.to_string(),
+            bantime,
+            findtime: 600,
+            maxretry: 5,
+            total_banned: 0,
+            bans: Vec::new(),
+            ignoreip: vec!["127.0.0.1/8".to_string(), "::1".to_string()],
+        }
+    }
+}
+
+/// In-memory stand-in for a fail2ban server. Bans never expire on their own;
+/// the reported end time is simply `start + bantime`. Like fail2ban, a ban
+/// follows the jail's current bantime until banning the IP again makes it
+/// end later, which gives the ban a time of its own. With `own_ban_times`
+/// it also takes a time per ban, like a server that supports it would.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBackend {
+    pub jails: Vec<MemoryJail>,
+    pub own_ban_times: bool,
+}
+
+impl MemoryBackend {
+    pub fn demo() -> Self {
+        let now = Utc::now().timestamp();
+        let jails = vec![
+            demo_jail(
+                "sshd",
+                3600,
+                &[
+                    ("192.0.2.14", 120),
+                    ("192.0.2.9", 900),
+                    ("192.0.2.100", 2400),
+                    ("198.51.100.23", 3000),
+                    ("2001:db8::17", 1800),
+                ],
+                now,
+            ),
+            demo_jail(
+                "nginx-http-auth",
+                600,
+                &[("203.0.113.7", 60), ("198.51.100.23", 300)],
+                now,
+            ),
+            demo_jail("recidive", 604_800, &[("203.0.113.200", 86_400)], now),
+            demo_jail("postfix", 600, &[], now),
+        ];
+        Self {
+            jails,
+            own_ban_times: true,
+        }
+    }
+
+    fn jail_mut(&mut self, jail: &str) -> Result<&mut MemoryJail> {
+        self.jails
+            .iter_mut()
+            .find(|j| j.name == jail)
+            .ok_or_else(|| anyhow!("Sorry but the jail '{jail}' does not exist"))
+    }
+}
+
+fn demo_jail(name: &str, bantime: i64, bans: &[(&str, i64)], now: i64) -> MemoryJail {
+    let mut jail = MemoryJail::new(name, bantime);
+    jail.total_banned = bans.len() as u32 * 3;
+    jail.bans = bans
+        .iter()
+        .map(|(ip, age)| MemoryBan {
+            ip: ip.to_string(),
+            start: now - age,
+            bantime: None,
+        })
+        .collect();
+    jail
+}
+
+/// The ignore list the way `fail2ban-client get <jail> ignoreip` prints it.
+fn format_ignore_list(ignoreip: &[String]) -> String {
+    let Some((last, rest)) = ignoreip.split_last() else {
+        return "No IP address/network is ignored".to_string();
+    };
+    let mut out = "These IP addresses/networks are ignored:".to_string();
+    for entry in rest {
+        out.push_str(&format!("\n|- {entry}"));
+    }
+    out.push_str(&format!("\n`- {last}"));
+    out
+}
+
+fn format_stamp(epoch: i64) -> String {
+    DateTime::<Utc>::from_timestamp(epoch, 0)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+impl Fail2banBackend for MemoryBackend {
+    fn jails(&mut self) -> Result<Vec<String>> {
+        Ok(self.jails.iter().map(|j| j.name.clone()).collect())
+    }
+
+    fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+        let jail = self.jail_mut(jail)?;
+        Ok(JailSummary {
+            currently_banned: Some(jail.bans.len() as u32),
+            total_banned: Some(jail.total_banned),
+            ips: jail.bans.iter().map(|b| b.ip.clone()).collect(),
+        })
+    }
+
+    fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        let jail = self.jail_mut(jail)?;
+        match args {
+            ["bantime"] => Ok(jail.bantime.to_string()),
+            ["findtime"] => Ok(jail.findtime.to_string()),
+            ["maxretry"] => Ok(jail.maxretry.to_string()),
+            ["ignoreip"] => Ok(format_ignore_list(&jail.ignoreip)),
+            ["banip"] => Ok(jail
+                .bans
+                .iter()
+                .map(|b| b.ip.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")),
+            ["banip", "--with-time"] => Ok(jail
+                .bans
+                .iter()
+                .map(|b| {
+                    let bantime = b.bantime.unwrap_or(jail.bantime);
+                    let end = if bantime < 0 {
+                        "9999-12-31 23:59:59".to_string()
+                    } else {
+                        format_stamp(b.start + bantime)
+                    };
+                    format!("{} \t{} + {} = {}", b.ip, format_stamp(b.start), bantime, end)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+            _ => Err(anyhow!("Invalid command: get {} {}", jail.name, args.join(" "))),
+        }
+    }
+
+    fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        let jail = self.jail_mut(jail)?;
+        match args {
+            ["bantime", value] => {
+                jail.bantime = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid bantime '{value}'"))?;
+                Ok(jail.bantime.to_string())
+            }
+            ["addignoreip", entry] => {
+                if !jail.ignoreip.iter().any(|e| e == entry) {
+                    jail.ignoreip.push(entry.to_string());
+                }
+                Ok(format_ignore_list(&jail.ignoreip))
+            }
+            ["delignoreip", entry] => {
+                jail.ignoreip.retain(|e| e != entry);
+                Ok(format_ignore_list(&jail.ignoreip))
+            }
+            _ => Err(anyhow!("Invalid command: set {} {}", jail.name, args.join(" "))),
+        }
+    }
+
+    fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let jail = self.jail_mut(jail)?;
+        let now = Utc::now().timestamp();
+        for ip in ips {
+            if !is_ban_target(ip) {
+                return Err(anyhow!("'{ip}' is not a valid IP address or CIDR block"));
+            }
+            let bantime = jail.bantime;
+            let Some(ban) = jail.bans.iter_mut().find(|b| b.ip == *ip) else {
+                jail.bans.push(MemoryBan {
+                    ip: ip.to_string(),
+                    start: now,
+                    bantime: None,
+                });
+                jail.total_banned += 1;
+                continue;
+            };
+            // fail2ban prolongs a ban that the new one would outlast. Its
+            // clock is finer than ours, so a ban made now ends later than an
+            // equal one made earlier in the same second.
+            let end = |start: i64, bantime: i64| (bantime >= 0).then(|| start + bantime);
+            let later = match (end(now, bantime), end(ban.start, ban.bantime.unwrap_or(bantime))) {
+                (None, old) => old.is_some(),
+                (Some(new), Some(old)) => new >= old,
+                (Some(_), None) => false,
+            };
+            if later {
+                ban.bantime = Some(if bantime < 0 { -1 } else { bantime + now - ban.start });
+            }
+        }
+        Ok(())
+    }
+
+    fn own_ban_times(&mut self) -> bool {
+        self.own_ban_times
+    }
+
+    fn ban_with_time(&mut self, jail: &str, ips: &[&str], bantime: i64) -> Result<()> {
+        if !self.own_ban_times {
+            return Err(anyhow!("Invalid command: set {jail} banip with a time"));
+        }
+        let jail = self.jail_mut(jail)?;
+        let now = Utc::now().timestamp();
+        for ip in ips {
+            if !is_ban_target(ip) {
+                return Err(anyhow!("'{ip}' is not a valid IP address or CIDR block"));
+            }
+            let ban = MemoryBan {
+                ip: ip.to_string(),
+                start: now,
+                bantime: Some(bantime.max(-1)),
+            };
+            match jail.bans.iter_mut().find(|b| b.ip == *ip) {
+                Some(old) => *old = ban,
+                None => {
+                    jail.bans.push(ban);
+                    jail.total_banned += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let jail = self.jail_mut(jail)?;
+        for ip in ips {
+            let Some(pos) = jail.bans.iter().position(|b| b.ip == *ip) else {
+                return Err(anyhow!("IP {ip} is not banned"));
+            };
+            jail.bans.remove(pos);
+        }
+        Ok(())
+    }
+
+    fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+        Ok(self
+            .jails
+            .iter()
+            .filter(|j| j.bans.iter().any(|b| b.ip == ip))
+            .map(|j| j.name.clone())
+            .collect())
+    }
+}
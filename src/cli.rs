@@ -0,0 +1,586 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::backend::Fail2banBackend;
+use crate::export::{export_to_file, write_export, ExportFormat};
+use crate::{
+    ban_list, fetch_status, format_remaining, ignore_match, is_ban_target, locate_ip,
+    parse_ignore_list, plan_ban_list, remaining_seconds, unban_all_in_jail, IpEntry, JailStatus,
+};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+
+pub const USAGE: &str = "\
+Usage: f2bs [OPTIONS] [COMMAND]
+
+Without a command, f2bs starts the interactive TUI.
+
+Commands:
+  jails                      List jails with ban counts
+  banned [JAIL]              List banned IPs, optionally for one jail
+  ban <JAIL> <IP>            Ban an IP or CIDR block in a jail
+  ban --from-file <FILE> <JAIL>
+                             Ban every IP and CIDR block listed in FILE (- for stdin)
+  unban <IP> [--jail JAIL]   Unban an IP from one jail, or from every jail holding it
+  unban --all <JAIL>         Unban every IP in a jail
+  whereis <IP>               Show every jail holding an IP and the time left
+  export [JAIL]              Write the full ban state (default: JSON to stdout)
+  help                       Show this help
+
+Options:
+  --format <FORMAT>          text, tsv, json, ndjson or csv (default: text, json for export);
+                             not valid with ban or unban
+  -o, --output <FILE>        Write export to FILE; format follows the extension
+  --backend <KIND>           auto, socket, client or demo (default: auto)
+  --socket <PATH>            fail2ban server socket path
+  -h, --help                 Show this help
+  -V, --version              Show version
+
+Exit codes: 0 success, 1 fail2ban error, 2 usage error, 3 jail or IP not found";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Tsv,
+    Export(ExportFormat),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Tui,
+    Jails,
+    Banned { jail: Option<String> },
+    Ban { jail: String, ip: String },
+    BanList { jail: String, file: PathBuf },
+    Unban { ip: String, jail: Option<String> },
+    UnbanAll { jail: String },
+    Whereis { ip: String },
+    Export { jail: Option<String>, output: Option<PathBuf> },
+    Help,
+    Version,
+}
+
+impl Command {
+    /// Whether the command changes the server, and so ends up in the audit log.
+    pub fn modifies(&self) -> bool {
+        matches!(
+            self,
+            Command::Ban { .. } | Command::BanList { .. } | Command::Unban { .. } | Command::UnbanAll { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cli {
+    pub command: Command,
+    pub format: Option<OutputFormat>,
+    pub backend: Option<String>,
+    pub socket: Option<String>,
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Cli> {
+    let mut format = None;
+    let mut output = None;
+    let mut backend = None;
+    let mut socket = None;
+    let mut jail_flag = None;
+    let mut all = false;
+    let mut from_file = None;
+    let mut positional: Vec<String> = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| anyhow!("{name} requires a value"))
+        };
+        match flag.as_str() {
+            "-h" | "--help" => positional.insert(0, "help".to_string()),
+            "-V" | "--version" => positional.insert(0, "version".to_string()),
+            "--format" => {
+                let name = value("--format")?;
+                format = Some(match name.as_str() {
+                    "text" => OutputFormat::Text,
+                    "tsv" => OutputFormat::Tsv,
+                    other => OutputFormat::Export(
+                        ExportFormat::parse(other)
+                            .ok_or_else(|| anyhow!("unknown format '{other}'"))?,
+                    ),
+                })
+            }
+            "--backend" => backend = Some(value("--backend")?),
+            "--socket" => socket = Some(value("--socket")?),
+            "--jail" | "-j" => jail_flag = Some(value("--jail")?),
+            "--all" => all = true,
+            "--from-file" => from_file = Some(PathBuf::from(value("--from-file")?)),
+            "--output" | "-o" => output = Some(PathBuf::from(value("--output")?)),
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(anyhow!("unknown option '{flag}'"));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let name = positional.next();
+    let rest: Vec<String> = positional.collect();
+    let command = match (name.as_deref(), rest.as_slice()) {
+        (None, []) => Command::Tui,
+        (Some("help"), _) => Command::Help,
+        (Some("version"), _) => Command::Version,
+        (Some("jails"), []) => Command::Jails,
+        (Some("banned"), []) => Command::Banned { jail: jail_flag },
+        (Some("banned"), [jail]) => Command::Banned {
+            jail: Some(jail.clone()),
+        },
+        (Some("ban"), [jail]) => match from_file.take() {
+            Some(file) => Command::BanList {
+                jail: jail.clone(),
+                file,
+            },
+            None => return Err(anyhow!("wrong number of arguments for 'ban'")),
+        },
+        (Some("ban"), [jail, ip]) => Command::Ban {
+            jail: jail.clone(),
+            ip: ip.clone(),
+        },
+        (Some("unban"), [jail]) if all => Command::UnbanAll { jail: jail.clone() },
+        (Some("unban"), []) if all => match jail_flag {
+            Some(jail) => Command::UnbanAll { jail },
+            None => return Err(anyhow!("unban --all requires a jail")),
+        },
+        (Some("unban"), [ip]) => Command::Unban {
+            ip: ip.clone(),
+            jail: jail_flag,
+        },
+        (Some("whereis"), [ip]) => Command::Whereis { ip: ip.clone() },
+        (Some("export"), []) => Command::Export {
+            jail: jail_flag,
+            output: output.take(),
+        },
+        (Some("export"), [jail]) => Command::Export {
+            jail: Some(jail.clone()),
+            output: output.take(),
+        },
+        (Some(cmd @ ("jails" | "banned" | "ban" | "unban" | "whereis" | "export")), _) => {
+            return Err(anyhow!("wrong number of arguments for '{cmd}'"));
+        }
+        (Some(other), _) => return Err(anyhow!("unknown command '{other}'")),
+        (None, _) => unreachable!(),
+    };
+
+    if output.is_some() {
+        return Err(anyhow!("--output is only valid with export"));
+    }
+    if from_file.is_some() {
+        return Err(anyhow!("--from-file is only valid with ban <JAIL>"));
+    }
+    if let (Command::Jails, Some(OutputFormat::Export(format))) = (&command, format)
+        && format != ExportFormat::Json
+    {
+        return Err(anyhow!("jails supports text, tsv and json output"));
+    }
+    if let (Command::Export { .. }, Some(OutputFormat::Text | OutputFormat::Tsv)) = (&command, format) {
+        return Err(anyhow!("export supports json, ndjson and csv output"));
+    }
+    if command.modifies() && format.is_some() {
+        return Err(anyhow!("--format is not valid with ban or unban"));
+    }
+
+    Ok(Cli {
+        command,
+        format,
+        backend,
+        socket,
+    })
+}
+
+pub fn run(cli: &Cli, backend: &mut dyn Fail2banBackend) -> Result<i32> {
+    match &cli.command {
+        Command::Tui | Command::Help | Command::Version => Ok(EXIT_OK),
+        Command::Jails => {
+            let jails = fetch_status(backend)?;
+            match cli.format.unwrap_or(OutputFormat::Text) {
+                OutputFormat::Export(format) => print_export(&jails, format)?,
+                format => print_jails(&jails, format),
+            }
+            Ok(EXIT_OK)
+        }
+        Command::Banned { jail } => {
+            let Some(jails) = fetch_jails(backend, jail.as_deref())? else {
+                return Ok(EXIT_NOT_FOUND);
+            };
+            match cli.format.unwrap_or(OutputFormat::Text) {
+                OutputFormat::Export(format) => print_export(&jails, format)?,
+                format => print_banned(&jails, format),
+            }
+            Ok(EXIT_OK)
+        }
+        Command::Export { jail, output } => {
+            let Some(jails) = fetch_jails(backend, jail.as_deref())? else {
+                return Ok(EXIT_NOT_FOUND);
+            };
+            let explicit = match cli.format {
+                Some(OutputFormat::Export(format)) => Some(format),
+                _ => None,
+            };
+            match output {
+                Some(path) => {
+                    let format = explicit.unwrap_or_else(|| ExportFormat::from_path(path));
+                    let rows: Vec<_> = jails.iter().map(|j| (j, j.ips.iter().collect())).collect();
+                    let count = export_to_file(path, &rows, format, true)?;
+                    eprintln!("Exported {count} IPs to {}", path.display());
+                }
+                None => print_export(&jails, explicit.unwrap_or(ExportFormat::Json))?,
+            }
+            Ok(EXIT_OK)
+        }
+        Command::Ban { jail, ip } => {
+            if !is_ban_target(ip) {
+                eprintln!("f2bs: invalid IP address or CIDR block '{ip}'");
+                return Ok(EXIT_USAGE);
+            }
+            if !backend.jails()?.iter().any(|name| name == jail) {
+                eprintln!("f2bs: jail '{jail}' not found");
+                return Ok(EXIT_NOT_FOUND);
+            }
+            warn_ignored(backend, jail, &[ip.as_str()]);
+            backend.ban(jail, &[ip])?;
+            println!("Banned {ip} in {jail}");
+            Ok(EXIT_OK)
+        }
+        Command::BanList { jail, file } => {
+            let text = if file.as_os_str() == "-" {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                fs::read_to_string(file).map_err(|err| anyhow!("cannot read {}: {err}", file.display()))?
+            };
+            let Some(jails) = fetch_jails(backend, Some(jail))? else {
+                return Ok(EXIT_NOT_FOUND);
+            };
+            let plan = plan_ban_list(&text, &jails[0].ips);
+            if plan.new.is_empty() && plan.already.is_empty() {
+                eprintln!("f2bs: no IP addresses or CIDR blocks in {}", file.display());
+                return Ok(EXIT_USAGE);
+            }
+            let new: Vec<&str> = plan.new.iter().map(String::as_str).collect();
+            warn_ignored(backend, jail, &new);
+            let report = ban_list(backend, jail, &plan);
+            for ip in &plan.new {
+                match report.failed.iter().find(|(_, failed, _)| failed == ip) {
+                    Some((_, _, err)) => eprintln!("f2bs: could not ban {ip}: {err}"),
+                    None => println!("Banned {ip} in {jail}"),
+                }
+            }
+            for ip in &plan.already {
+                println!("{ip} is already banned in {jail}");
+            }
+            for warning in &report.warnings {
+                eprintln!("f2bs: {warning}");
+            }
+            println!("{}", report.summary("Banned", &format!("IPs in {jail}")));
+            Ok(if report.failed.is_empty() { EXIT_OK } else { EXIT_FAILURE })
+        }
+        Command::Unban { ip, jail } => {
+            if !is_ban_target(ip) {
+                eprintln!("f2bs: invalid IP address or CIDR block '{ip}'");
+                return Ok(EXIT_USAGE);
+            }
+            let targets: Vec<String> = match jail {
+                Some(jail) => {
+                    if !backend.jails()?.iter().any(|name| name == jail) {
+                        eprintln!("f2bs: jail '{jail}' not found");
+                        return Ok(EXIT_NOT_FOUND);
+                    }
+                    if !backend.jail_status(jail)?.ips.iter().any(|banned| banned == ip) {
+                        eprintln!("f2bs: {ip} is not banned in {jail}");
+                        return Ok(EXIT_NOT_FOUND);
+                    }
+                    vec![jail.clone()]
+                }
+                None => fetch_status(backend)?
+                    .into_iter()
+                    .filter(|j| j.ips.iter().any(|e| &e.ip == ip))
+                    .map(|j| j.name)
+                    .collect(),
+            };
+            if targets.is_empty() {
+                eprintln!("f2bs: {ip} is not banned in any jail");
+                return Ok(EXIT_NOT_FOUND);
+            }
+            let mut code = EXIT_OK;
+            for jail in targets {
+                match backend.unban(&jail, &[ip]) {
+                    Ok(()) => println!("Unbanned {ip} from {jail}"),
+                    Err(err) => {
+                        eprintln!("f2bs: unban of {ip} from {jail} failed: {err}");
+                        code = EXIT_FAILURE;
+                    }
+                }
+            }
+            Ok(code)
+        }
+        Command::Whereis { ip } => {
+            if ip.parse::<IpAddr>().is_err() {
+                eprintln!("f2bs: invalid IP address '{ip}'");
+                return Ok(EXIT_USAGE);
+            }
+            let jails = fetch_status(backend)?;
+            let matches = locate_ip(backend, &jails, ip);
+            if matches.is_empty() {
+                eprintln!("f2bs: {ip} is not banned in any jail");
+                return Ok(EXIT_NOT_FOUND);
+            }
+            let format = cli.format.unwrap_or(OutputFormat::Text);
+            if let OutputFormat::Export(format) = format {
+                let rows: Vec<_> = matches
+                    .iter()
+                    .filter_map(|m| jails.iter().find(|j| j.name == m.jail))
+                    .map(|j| (j, j.ips.iter().filter(|e| &e.ip == ip).collect()))
+                    .collect();
+                print_rows(&rows, format)?;
+                return Ok(EXIT_OK);
+            }
+            for m in matches {
+                match format {
+                    OutputFormat::Text => {
+                        let remaining = format_remaining(m.end_epoch, m.time_raw.as_deref());
+                        let remaining = remaining.trim_start_matches('(').trim_end_matches(')');
+                        println!("{}  {remaining}", m.jail);
+                    }
+                    OutputFormat::Tsv | OutputFormat::Export(_) => println!(
+                        "{}\t{}\t{}",
+                        m.jail,
+                        m.end_epoch.map(|v| v.to_string()).unwrap_or_default(),
+                        remaining_seconds(m.end_epoch)
+                            .map(|v| v.to_string())
+                            .unwrap_or_default(),
+                    ),
+                }
+            }
+            Ok(EXIT_OK)
+        }
+        Command::UnbanAll { jail } => {
+            let jails = fetch_status(backend)?;
+            if !jails.iter().any(|j| &j.name == jail) {
+                eprintln!("f2bs: jail '{jail}' not found");
+                return Ok(EXIT_NOT_FOUND);
+            }
+            let report = unban_all_in_jail(backend, &jails, jail)?;
+            for (_, ip, err) in &report.failed {
+                eprintln!("f2bs: could not unban {ip}: {err}");
+            }
+            println!("{}", report.summary("Unbanned", &format!("IPs from {jail}")));
+            Ok(if report.failed.is_empty() { EXIT_OK } else { EXIT_FAILURE })
+        }
+    }
+}
+
+/// Says which of `ips` the jail's ignore list covers. The ban still goes
+/// ahead: an explicit ban is the operator's call.
+fn warn_ignored(backend: &mut dyn Fail2banBackend, jail: &str, ips: &[&str]) {
+    let Ok(output) = backend.get(jail, &["ignoreip"]) else {
+        return;
+    };
+    let ignore = parse_ignore_list(&output);
+    for ip in ips {
+        if let Some(entry) = ignore_match(&ignore, ip) {
+            eprintln!("f2bs: warning: {ip} is on the ignore list of {jail} ({entry})");
+        }
+    }
+}
+
+fn fetch_jails(
+    backend: &mut dyn Fail2banBackend,
+    jail: Option<&str>,
+) -> Result<Option<Vec<JailStatus>>> {
+    let mut jails = fetch_status(backend)?;
+    if let Some(name) = jail {
+        jails.retain(|j| j.name == name);
+        if jails.is_empty() {
+            eprintln!("f2bs: jail '{name}' not found");
+            return Ok(None);
+        }
+    }
+    Ok(Some(jails))
+}
+
+fn print_export(jails: &[JailStatus], format: ExportFormat) -> Result<()> {
+    let rows: Vec<_> = jails.iter().map(|j| (j, j.ips.iter().collect())).collect();
+    print_rows(&rows, format)
+}
+
+fn print_rows(rows: &[(&JailStatus, Vec<&IpEntry>)], format: ExportFormat) -> Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    write_export(&mut out, rows, format)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn print_jails(jails: &[JailStatus], format: OutputFormat) {
+    match format {
+        OutputFormat::Tsv | OutputFormat::Export(_) => {
+            for jail in jails {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    jail.name,
+                    jail.ips.len(),
+                    jail.total_banned.map(|v| v.to_string()).unwrap_or_default(),
+                    jail.bantime.seconds.map(|v| v.to_string()).unwrap_or_default(),
+                );
+            }
+        }
+        OutputFormat::Text => {
+            let width = jails.iter().map(|j| j.name.len()).max().unwrap_or(0).max(4);
+            println!("{:<width$}  {:>6}  {:>6}  BANTIME", "JAIL", "BANNED", "TOTAL");
+            for jail in jails {
+                println!(
+                    "{:<width$}  {:>6}  {:>6}  {}",
+                    jail.name,
+                    jail.ips.len(),
+                    jail.total_banned.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string()),
+                    jail.bantime.raw,
+                );
+            }
+        }
+    }
+}
+
+fn print_banned(jails: &[JailStatus], format: OutputFormat) {
+    let rows = jails
+        .iter()
+        .flat_map(|jail| jail.ips.iter().map(move |entry| (jail.name.as_str(), entry)));
+    match format {
+        OutputFormat::Tsv | OutputFormat::Export(_) => {
+            for (jail, entry) in rows {
+                println!(
+                    "{jail}\t{}\t{}\t{}",
+                    entry.ip,
+                    entry.end_epoch.map(|v| v.to_string()).unwrap_or_default(),
+                    remaining_seconds(entry.end_epoch)
+                        .map(|v| v.to_string())
+                        .unwrap_or_default(),
+                );
+            }
+        }
+        OutputFormat::Text => {
+            let rows: Vec<_> = rows.collect();
+            let jail_width = rows.iter().map(|(j, _)| j.len()).max().unwrap_or(0).max(4);
+            let ip_width = rows.iter().map(|(_, e)| e.ip.len()).max().unwrap_or(0).max(2);
+            println!("{:<jail_width$}  {:<ip_width$}  LEFT", "JAIL", "IP");
+            for (jail, entry) in rows {
+                let remaining = format_remaining(entry.end_epoch, entry.time_raw.as_deref());
+                let remaining = remaining.trim_start_matches('(').trim_end_matches(')');
+                println!("{jail:<jail_width$}  {:<ip_width$}  {remaining}", entry.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    fn parse(args: &str) -> Result<Cli> {
+        parse_args(args.split_whitespace().map(String::from))
+    }
+
+    fn command(args: &str) -> Command {
+        parse(args).unwrap().command
+    }
+
+    fn usage_error(args: &str) -> String {
+        format!("{:#}", parse(args).unwrap_err())
+    }
+
+    fn exit_code(args: &str) -> i32 {
+        run(&parse(args).unwrap(), &mut MemoryBackend::demo()).unwrap()
+    }
+
+    #[test]
+    fn parses_commands_and_flags() {
+        assert_eq!(command(""), Command::Tui);
+        assert_eq!(command("--help jails"), Command::Help);
+        assert_eq!(command("-V"), Command::Version);
+        assert_eq!(command("banned -j sshd"), Command::Banned { jail: Some("sshd".to_string()) });
+        assert_eq!(
+            command("ban sshd 192.0.2.0/24"),
+            Command::Ban { jail: "sshd".to_string(), ip: "192.0.2.0/24".to_string() }
+        );
+        assert_eq!(
+            command("ban --from-file=- sshd"),
+            Command::BanList { jail: "sshd".to_string(), file: PathBuf::from("-") }
+        );
+        assert_eq!(
+            command("unban 192.0.2.1 --jail sshd"),
+            Command::Unban { ip: "192.0.2.1".to_string(), jail: Some("sshd".to_string()) }
+        );
+        assert_eq!(command("unban --all sshd"), Command::UnbanAll { jail: "sshd".to_string() });
+        assert_eq!(command("unban --all --jail sshd"), Command::UnbanAll { jail: "sshd".to_string() });
+        assert_eq!(
+            command("export -o bans.csv"),
+            Command::Export { jail: None, output: Some(PathBuf::from("bans.csv")) }
+        );
+
+        let cli = parse("jails --format json --backend demo --socket /tmp/f2b.sock").unwrap();
+        assert_eq!(cli.format, Some(OutputFormat::Export(ExportFormat::Json)));
+        assert_eq!(cli.backend.as_deref(), Some("demo"));
+        assert_eq!(cli.socket.as_deref(), Some("/tmp/f2b.sock"));
+    }
+
+    #[test]
+    fn reports_usage_errors() {
+        assert!(usage_error("frobnicate").contains("unknown command"));
+        assert!(usage_error("--frobnicate").contains("unknown option"));
+        assert!(usage_error("--format").contains("requires a value"));
+        assert!(usage_error("--format xml").contains("unknown format"));
+        assert!(usage_error("ban sshd").contains("wrong number of arguments"));
+        assert!(usage_error("whereis").contains("wrong number of arguments"));
+        assert!(usage_error("unban --all").contains("requires a jail"));
+        assert!(usage_error("banned -o out.json").contains("only valid with export"));
+        assert!(usage_error("ban sshd 192.0.2.1 --from-file ips.txt").contains("--from-file"));
+        assert!(usage_error("jails --format csv").contains("jails supports"));
+        assert!(usage_error("export --format tsv").contains("export supports"));
+        assert!(usage_error("ban sshd 192.0.2.1 --format json").contains("not valid with ban"));
+        assert!(usage_error("ban --from-file - sshd --format csv").contains("not valid with ban"));
+        assert!(usage_error("unban 192.0.2.1 --format tsv").contains("not valid with ban or unban"));
+        assert!(usage_error("unban --all sshd --format text").contains("not valid with ban or unban"));
+    }
+
+    #[test]
+    fn exit_codes_follow_the_usage_text() {
+        assert_eq!(exit_code("jails"), EXIT_OK);
+        assert_eq!(exit_code("banned nosuchjail"), EXIT_NOT_FOUND);
+        assert_eq!(exit_code("ban sshd 203.0.113.x"), EXIT_USAGE);
+        assert_eq!(exit_code("ban nosuchjail 203.0.113.50"), EXIT_NOT_FOUND);
+        assert_eq!(exit_code("ban sshd 203.0.113.0/24"), EXIT_OK);
+        assert_eq!(exit_code("whereis 192.0.2.x"), EXIT_USAGE);
+        assert_eq!(exit_code("whereis 203.0.113.99"), EXIT_NOT_FOUND);
+        assert_eq!(exit_code("whereis 198.51.100.23"), EXIT_OK);
+        assert_eq!(exit_code("unban 192.0.2.x"), EXIT_USAGE);
+        assert_eq!(exit_code("unban 203.0.113.99"), EXIT_NOT_FOUND);
+        assert_eq!(exit_code("unban 192.0.2.9 --jail nosuchjail"), EXIT_NOT_FOUND);
+        assert_eq!(exit_code("unban 192.0.2.9 --jail postfix"), EXIT_NOT_FOUND);
+        assert_eq!(exit_code("unban 192.0.2.9 --jail sshd"), EXIT_OK);
+        assert_eq!(exit_code("unban 198.51.100.23"), EXIT_OK);
+        assert_eq!(exit_code("unban --all nosuchjail"), EXIT_NOT_FOUND);
+        assert_eq!(exit_code("unban --all sshd"), EXIT_OK);
+    }
+}
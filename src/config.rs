@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::keymap::KeyList;
+
+pub const SYSTEM_CONFIG: &str = "/etc/f2bs.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedFilter {
+    pub name: String,
+    pub query: String,
+}
+
+/// Preferences restored at startup and written back on quit. Missing keys
+/// keep their defaults, so a hand-written file only needs the keys it sets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub sort: String,
+    pub sort_desc: bool,
+    pub autorefresh: bool,
+    pub refresh_interval: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jail: Option<String>,
+    #[serde(rename = "filter", skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<SavedFilter>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeyList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colors: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub themes: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<String>,
+    pub audit_syslog: bool,
+    /// Prefix lengths the subnet view groups IPv4 and IPv6 addresses by.
+    pub group_prefix_v4: u8,
+    pub group_prefix_v6: u8,
+    /// On servers that can't give a ban a time of its own, briefly set the
+    /// jail's bantime to ban for a duration. Off by default, since bans the
+    /// jail makes meanwhile get that time too.
+    pub bantime_override: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sort: "ip".to_string(),
+            sort_desc: false,
+            autorefresh: false,
+            refresh_interval: 5,
+            jail: None,
+            filters: Vec::new(),
+            keys: BTreeMap::new(),
+            theme: None,
+            colors: None,
+            themes: BTreeMap::new(),
+            audit_log: None,
+            audit_syslog: false,
+            group_prefix_v4: 24,
+            group_prefix_v6: 64,
+            bantime_override: false,
+        }
+    }
+}
+
+/// `$F2BS_CONFIG`, else `$XDG_CONFIG_HOME/f2bs/config.toml`, else
+/// `~/.config/f2bs/config.toml`.
+pub fn user_path() -> Option<PathBuf> {
+    user_path_from(|name| std::env::var_os(name))
+}
+
+/// [`user_path`] with the environment read through `var`.
+fn user_path_from(var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let var = |name| var(name).filter(|p| !p.is_empty());
+    if let Some(path) = var("F2BS_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("f2bs").join("config.toml"))
+}
+
+impl Config {
+    /// Reads the user config, falling back to the system-wide one. A file
+    /// that can't be parsed is reported and replaced by the defaults.
+    pub fn load() -> (Self, Option<String>) {
+        Self::load_first(user_path().into_iter().chain([PathBuf::from(SYSTEM_CONFIG)]))
+    }
+
+    /// Reads the first of `candidates` that exists.
+    fn load_first(candidates: impl IntoIterator<Item = PathBuf>) -> (Self, Option<String>) {
+        for path in candidates {
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            return match toml::from_str(&text) {
+                Ok(config) => (config, None),
+                Err(err) => (
+                    Self::default(),
+                    Some(format!("Ignoring {}: {}", path.display(), err.message())),
+                ),
+            };
+        }
+        (Self::default(), None)
+    }
+
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = user_path().ok_or_else(|| anyhow!("no config directory (HOME is not set)"))?;
+        self.save_to(&path)?;
+        Ok(path)
+    }
+
+    /// Writes the config to `path`, unless a file there doesn't parse: that
+    /// one is left for the user to fix.
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Ok(text) = fs::read_to_string(path)
+            && toml::from_str::<Config>(&text).is_err()
+        {
+            return Err(anyhow!("{} has errors, not overwriting it", path.display()));
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        }
+        let text = toml::to_string(self)?;
+        fs::write(path, text).with_context(|| format!("cannot write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh temporary directory for one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("f2bs-{}-{name}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn path_with(vars: &[(&str, &str)]) -> Option<PathBuf> {
+        user_path_from(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| OsString::from(value))
+        })
+    }
+
+    #[test]
+    fn finds_the_user_config() {
+        let all = [("F2BS_CONFIG", "/tmp/f2bs.toml"), ("XDG_CONFIG_HOME", "/xdg"), ("HOME", "/home/a")];
+        assert_eq!(path_with(&all), Some(PathBuf::from("/tmp/f2bs.toml")));
+        assert_eq!(
+            path_with(&[("F2BS_CONFIG", ""), ("XDG_CONFIG_HOME", "/xdg"), ("HOME", "/home/a")]),
+            Some(PathBuf::from("/xdg/f2bs/config.toml"))
+        );
+        assert_eq!(
+            path_with(&[("XDG_CONFIG_HOME", ""), ("HOME", "/home/a")]),
+            Some(PathBuf::from("/home/a/.config/f2bs/config.toml"))
+        );
+        assert_eq!(path_with(&[("HOME", "")]), None);
+    }
+
+    #[test]
+    fn loads_the_first_file_that_exists() {
+        let dir = temp_dir("config-load");
+        let user = dir.join("user.toml");
+        let system = dir.join("system.toml");
+        fs::write(&system, "sort = \"left\"\n").unwrap();
+
+        let (config, warning) = Config::load_first([user.clone(), system.clone()]);
+        assert_eq!((config.sort.as_str(), warning), ("left", None));
+
+        // The user file wins whole; keys it leaves out keep their defaults.
+        fs::write(&user, "autorefresh = true\n[[filter]]\nname = \"ssh\"\nquery = \"jail:sshd\"\n").unwrap();
+        let (config, _) = Config::load_first([user.clone(), system.clone()]);
+        assert!(config.autorefresh);
+        assert_eq!(config.sort, "ip");
+        assert_eq!(config.refresh_interval, 5);
+        assert_eq!(config.filters, [SavedFilter { name: "ssh".into(), query: "jail:sshd".into() }]);
+
+        fs::write(&user, "sort = [").unwrap();
+        let (config, warning) = Config::load_first([user.clone(), system.clone()]);
+        assert_eq!(config, Config::default());
+        assert!(warning.unwrap().contains("user.toml"));
+
+        assert_eq!(Config::load_first([dir.join("missing.toml")]), (Config::default(), None));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn saves_what_it_loads() {
+        let dir = temp_dir("config-save");
+        let path = dir.join("nested").join("config.toml");
+        let config = Config {
+            sort: "expires".to_string(),
+            sort_desc: true,
+            jail: Some("sshd".to_string()),
+            bantime_override: true,
+            ..Config::default()
+        };
+        config.save_to(&path).unwrap();
+        assert_eq!(Config::load_first([path.clone()]), (config, None));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn does_not_overwrite_a_file_with_errors() {
+        let dir = temp_dir("config-broken");
+        let path = dir.join("config.toml");
+        let text = "# my settings\nsort = \"left\"\nautorefresh = maybe\n";
+        fs::write(&path, text).unwrap();
+        let err = Config::default().save_to(&path).unwrap_err();
+        assert!(format!("{err}").contains("not overwriting"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
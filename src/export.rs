@@ -0,0 +1,337 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{remaining_seconds, IpEntry, JailStatus, TimeValue};
+
+/// Bumped whenever a field is renamed or removed; new optional fields do not
+/// change the version. CSV is read by position, so there any new column
+/// bumps it too: the columns of a version are frozen.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The NDJSON fields, in the same order. Every row repeats the version.
+pub const CSV_HEADER: &str =
+    "schema_version,jail,ip,start_epoch,end_epoch,expires_at,remaining_seconds,time_raw";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| Self::parse(&ext.to_ascii_lowercase()))
+            .unwrap_or(Self::Json)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Snapshot<'a> {
+    schema_version: u32,
+    generated_at: String,
+    jails: Vec<JailRecord<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct TimeRecord<'a> {
+    raw: &'a str,
+    seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct JailRecord<'a> {
+    name: &'a str,
+    bantime: TimeRecord<'a>,
+    findtime: TimeRecord<'a>,
+    maxretry: Option<u32>,
+    currently_banned: Option<u32>,
+    total_banned: Option<u32>,
+    ips: Vec<IpRecord<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct IpRecord<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema_version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    jail: Option<&'a str>,
+    ip: &'a str,
+    start_epoch: Option<i64>,
+    end_epoch: Option<i64>,
+    expires_at: Option<String>,
+    remaining_seconds: Option<u64>,
+    time_raw: Option<&'a str>,
+}
+
+fn iso(epoch: i64) -> Option<String> {
+    DateTime::<Utc>::from_timestamp(epoch, 0).map(|dt| dt.to_rfc3339())
+}
+
+fn time_record(value: &TimeValue) -> TimeRecord<'_> {
+    TimeRecord {
+        raw: &value.raw,
+        seconds: value.seconds,
+    }
+}
+
+fn ip_record<'a>(jail: Option<&'a str>, entry: &'a IpEntry) -> IpRecord<'a> {
+    IpRecord {
+        schema_version: jail.map(|_| SCHEMA_VERSION),
+        jail,
+        ip: &entry.ip,
+        start_epoch: entry.start_epoch,
+        end_epoch: entry.end_epoch,
+        expires_at: entry.end_epoch.and_then(iso),
+        remaining_seconds: remaining_seconds(entry.end_epoch),
+        time_raw: entry.time_raw.as_deref(),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes `jails` in `format`. Each jail carries the IPs to export, which lets
+/// callers pass a filtered view instead of the full jail.
+pub fn write_export<W: Write>(
+    out: &mut W,
+    jails: &[(&JailStatus, Vec<&IpEntry>)],
+    format: ExportFormat,
+) -> Result<()> {
+    match format {
+        ExportFormat::Json => {
+            let snapshot = Snapshot {
+                schema_version: SCHEMA_VERSION,
+                generated_at: Utc::now().to_rfc3339(),
+                jails: jails
+                    .iter()
+                    .map(|(jail, ips)| JailRecord {
+                        name: &jail.name,
+                        bantime: time_record(&jail.bantime),
+                        findtime: time_record(&jail.findtime),
+                        maxretry: jail.maxretry,
+                        currently_banned: jail.currently_banned,
+                        total_banned: jail.total_banned,
+                        ips: ips.iter().map(|entry| ip_record(None, entry)).collect(),
+                    })
+                    .collect(),
+            };
+            serde_json::to_writer_pretty(&mut *out, &snapshot)?;
+            writeln!(out)?;
+        }
+        ExportFormat::Ndjson => {
+            for (jail, ips) in jails {
+                for entry in ips {
+                    serde_json::to_writer(&mut *out, &ip_record(Some(&jail.name), entry))?;
+                    writeln!(out)?;
+                }
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "{CSV_HEADER}")?;
+            for (jail, ips) in jails {
+                for entry in ips {
+                    let record = ip_record(Some(&jail.name), entry);
+                    writeln!(
+                        out,
+                        "{SCHEMA_VERSION},{},{},{},{},{},{},{}",
+                        csv_field(&jail.name),
+                        csv_field(record.ip),
+                        record.start_epoch.map(|v| v.to_string()).unwrap_or_default(),
+                        record.end_epoch.map(|v| v.to_string()).unwrap_or_default(),
+                        record.expires_at.unwrap_or_default(),
+                        record.remaining_seconds.map(|v| v.to_string()).unwrap_or_default(),
+                        csv_field(record.time_raw.unwrap_or_default()),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the export to `path`. A file already there is only replaced with
+/// `overwrite`.
+pub fn export_to_file(
+    path: &Path,
+    jails: &[(&JailStatus, Vec<&IpEntry>)],
+    format: ExportFormat,
+    overwrite: bool,
+) -> Result<usize> {
+    if path.as_os_str().is_empty() {
+        return Err(anyhow!("no file name given"));
+    }
+    let file = match OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .create_new(!overwrite)
+        .open(path)
+    {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(anyhow!("{} already exists", path.display()));
+        }
+        result => result.with_context(|| format!("cannot create {}", path.display()))?,
+    };
+    let mut out = BufWriter::new(file);
+    write_export(&mut out, jails, format)?;
+    out.flush()?;
+    Ok(jails.iter().map(|(_, ips)| ips.len()).sum())
+}
+
+pub fn default_export_name() -> String {
+    format!("f2bs-{}.json", Utc::now().format("%Y%m%d-%H%M%S"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(raw: &str, seconds: u64) -> TimeValue {
+        TimeValue {
+            raw: raw.to_string(),
+            seconds: Some(seconds),
+        }
+    }
+
+    /// One expired ban, so that the remaining time is 0, and one with no
+    /// times at all.
+    fn jail() -> JailStatus {
+        let entry = |ip: &str, start: Option<i64>, end: Option<i64>, raw: Option<&str>| IpEntry {
+            ip: ip.to_string(),
+            addr: ip.parse().ok(),
+            start_epoch: start,
+            end_epoch: end,
+            time_raw: raw.map(String::from),
+        };
+        JailStatus {
+            name: "sshd".to_string(),
+            ips: vec![
+                entry(
+                    "192.0.2.1",
+                    Some(1_767_225_600),
+                    Some(1_767_229_200),
+                    Some("2026-01-01 00:00:00 + 3600 = 2026-01-01 01:00:00"),
+                ),
+                entry("2001:db8::1", None, None, None),
+            ],
+            bantime: time("3600", 3600),
+            findtime: time("600", 600),
+            maxretry: Some(5),
+            currently_banned: Some(2),
+            total_banned: Some(7),
+        }
+    }
+
+    fn export(format: ExportFormat) -> String {
+        let jail = jail();
+        let rows = [(&jail, jail.ips.iter().collect())];
+        let mut out = Vec::new();
+        write_export(&mut out, &rows, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_layout_is_pinned() {
+        assert_eq!(
+            export(ExportFormat::Csv),
+            "schema_version,jail,ip,start_epoch,end_epoch,expires_at,remaining_seconds,time_raw\n\
+             1,sshd,192.0.2.1,1767225600,1767229200,2026-01-01T01:00:00+00:00,0,\
+             2026-01-01 00:00:00 + 3600 = 2026-01-01 01:00:00\n\
+             1,sshd,2001:db8::1,,,,,\n"
+        );
+    }
+
+    #[test]
+    fn ndjson_layout_is_pinned() {
+        assert_eq!(
+            export(ExportFormat::Ndjson),
+            "{\"schema_version\":1,\"jail\":\"sshd\",\"ip\":\"192.0.2.1\",\"start_epoch\":1767225600,\
+             \"end_epoch\":1767229200,\"expires_at\":\"2026-01-01T01:00:00+00:00\",\"remaining_seconds\":0,\
+             \"time_raw\":\"2026-01-01 00:00:00 + 3600 = 2026-01-01 01:00:00\"}\n\
+             {\"schema_version\":1,\"jail\":\"sshd\",\"ip\":\"2001:db8::1\",\"start_epoch\":null,\
+             \"end_epoch\":null,\"expires_at\":null,\"remaining_seconds\":null,\"time_raw\":null}\n"
+        );
+    }
+
+    #[test]
+    fn json_layout_is_pinned() {
+        let text = export(ExportFormat::Json);
+        let mut value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert!(value["generated_at"].as_str().is_some_and(|at| at.contains('T')));
+        value["generated_at"] = serde_json::Value::Null;
+        let expected = serde_json::json!({
+            "schema_version": 1,
+            "generated_at": null,
+            "jails": [{
+                "name": "sshd",
+                "bantime": {"raw": "3600", "seconds": 3600},
+                "findtime": {"raw": "600", "seconds": 600},
+                "maxretry": 5,
+                "currently_banned": 2,
+                "total_banned": 7,
+                "ips": [
+                    {
+                        "ip": "192.0.2.1",
+                        "start_epoch": 1_767_225_600,
+                        "end_epoch": 1_767_229_200,
+                        "expires_at": "2026-01-01T01:00:00+00:00",
+                        "remaining_seconds": 0,
+                        "time_raw": "2026-01-01 00:00:00 + 3600 = 2026-01-01 01:00:00"
+                    },
+                    {
+                        "ip": "2001:db8::1",
+                        "start_epoch": null,
+                        "end_epoch": null,
+                        "expires_at": null,
+                        "remaining_seconds": null,
+                        "time_raw": null
+                    }
+                ]
+            }]
+        });
+        assert_eq!(value, expected);
+        // Field order is part of the layout too.
+        let keys: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix('"')?.split('"').next())
+            .take(4)
+            .collect();
+        assert_eq!(keys, ["schema_version", "generated_at", "jails", "name"]);
+    }
+
+    #[test]
+    fn only_replaces_a_file_when_asked() {
+        let path = std::env::temp_dir().join(format!("f2bs-{}-export.csv", std::process::id()));
+        std::fs::write(&path, "keep me").unwrap();
+        let jail = jail();
+        let rows = [(&jail, jail.ips.iter().collect())];
+        let err = export_to_file(&path, &rows, ExportFormat::Csv, false).unwrap_err();
+        assert!(err.to_string().ends_with("already exists"), "{err:#}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        assert_eq!(export_to_file(&path, &rows, ExportFormat::Csv, true).unwrap(), 2);
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with(CSV_HEADER));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use crate::backend::Fail2banBackend;
+use crate::{ban_for, BulkReport};
+
+/// An IP that was unbanned this session, with what is needed to ban it again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoBan {
+    pub jail: String,
+    pub ip: String,
+    /// Ban time to restore: seconds left, `-1` for a permanent ban, or
+    /// `None` to use the jail's bantime.
+    pub bantime: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub label: String,
+    pub bans: Vec<UndoBan>,
+}
+
+/// Unbans made this session, newest last.
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    pub fn record(&mut self, label: String, bans: Vec<UndoBan>) {
+        if !bans.is_empty() {
+            self.entries.push(JournalEntry { label, bans });
+        }
+    }
+
+    pub fn last(&self) -> Option<&JournalEntry> {
+        self.entries.last()
+    }
+
+    pub fn pop(&mut self) -> Option<JournalEntry> {
+        self.entries.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// The time `ban` is banned again for: its remaining time rounded up to the
+/// minute, so IPs from one jail can share a batch.
+pub fn restored_bantime(ban: &UndoBan) -> Option<i64> {
+    ban.bantime.map(|secs| if secs > 0 { (secs + 59) / 60 * 60 } else { secs })
+}
+
+/// Bans `bans` again, each for its [`restored_bantime`]. `override_bantime`
+/// is passed on to [`ban_for`], which warns about bans that get the jail's
+/// bantime instead.
+pub fn restore(backend: &mut dyn Fail2banBackend, bans: &[UndoBan], override_bantime: bool) -> BulkReport {
+    let mut groups: BTreeMap<(&str, Option<i64>), Vec<&str>> = BTreeMap::new();
+    for ban in bans {
+        groups.entry((&ban.jail, restored_bantime(ban))).or_default().push(&ban.ip);
+    }
+
+    let mut report = BulkReport::default();
+    for ((jail, bantime), ips) in groups {
+        ban_for(backend, &mut report, jail, &ips, bantime, override_bantime);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::{parse_ban_seconds, parse_banip_with_time};
+
+    fn undo(ip: &str, bantime: Option<i64>) -> UndoBan {
+        UndoBan {
+            jail: "postfix".to_string(),
+            ip: ip.to_string(),
+            bantime,
+        }
+    }
+
+    fn ban_seconds(backend: &mut MemoryBackend, ip: &str) -> Option<i64> {
+        let output = backend.get("postfix", &["banip", "--with-time"]).unwrap();
+        let entry = parse_banip_with_time(&output, None).into_iter().find(|e| e.ip == ip)?;
+        parse_ban_seconds(entry.time_raw.as_deref()?)
+    }
+
+    #[test]
+    fn rounds_times_up_to_the_minute() {
+        assert_eq!(restored_bantime(&undo("192.0.2.1", Some(61))), Some(120));
+        assert_eq!(restored_bantime(&undo("192.0.2.1", Some(120))), Some(120));
+        assert_eq!(restored_bantime(&undo("192.0.2.1", Some(1))), Some(60));
+        assert_eq!(restored_bantime(&undo("192.0.2.1", Some(-1))), Some(-1));
+        assert_eq!(restored_bantime(&undo("192.0.2.1", None)), None);
+    }
+
+    #[test]
+    fn restores_the_time_each_ban_had_left() {
+        let mut backend = MemoryBackend::demo();
+        let bans = [
+            undo("192.0.2.1", Some(7200)),
+            undo("192.0.2.2", Some(61)),
+            undo("192.0.2.3", Some(100)),
+            undo("192.0.2.4", Some(-1)),
+            undo("192.0.2.5", None),
+        ];
+        let report = restore(&mut backend, &bans, false);
+        assert_eq!(report.done, 5);
+        assert!(report.failed.is_empty() && report.warnings.is_empty(), "{report:?}");
+        assert_eq!(ban_seconds(&mut backend, "192.0.2.1"), Some(7200));
+        assert_eq!(ban_seconds(&mut backend, "192.0.2.2"), Some(120));
+        assert_eq!(ban_seconds(&mut backend, "192.0.2.3"), Some(120));
+        assert_eq!(ban_seconds(&mut backend, "192.0.2.4"), Some(-1));
+        assert_eq!(ban_seconds(&mut backend, "192.0.2.5"), Some(600));
+    }
+
+    #[test]
+    fn warns_when_the_time_left_cannot_be_restored() {
+        let mut backend = MemoryBackend {
+            own_ban_times: false,
+            ..MemoryBackend::demo()
+        };
+        let report = restore(&mut backend, &[undo("192.0.2.1", Some(7200))], false);
+        assert_eq!(report.done, 1);
+        assert_eq!(report.warnings.len(), 1, "{report:?}");
+        assert_eq!(ban_seconds(&mut backend, "192.0.2.1"), Some(600));
+    }
+}
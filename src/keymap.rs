@@ -0,0 +1,745 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    Main,
+    Confirm,
+    IgnoreList,
+    MoveIp,
+    BanSubnet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Refresh,
+    Filter,
+    ClearFilter,
+    SavedFilters,
+    SortNext,
+    SortReverse,
+    Ban,
+    BanList,
+    IgnoreList,
+    SwitchPanel,
+    Up,
+    Down,
+    Activate,
+    Unban,
+    UnbanEverywhere,
+    BanTime,
+    MoveToJail,
+    UnbanAndIgnore,
+    Undo,
+    ToggleMark,
+    MarkRange,
+    MarkAll,
+    ClearMarks,
+    Subnets,
+    AllJails,
+    Whereis,
+    UnbanAll,
+    Export,
+    ToggleAutorefresh,
+    Help,
+    AuditLog,
+    Palette,
+    Confirm,
+    Cancel,
+    IgnoreAdd,
+    IgnoreRemove,
+    IgnoreReload,
+    MoveCopy,
+    MoveKeepTime,
+    SubnetUnban,
+}
+
+impl Action {
+    pub const ALL: [Action; 41] = [
+        Action::Quit,
+        Action::Refresh,
+        Action::Filter,
+        Action::ClearFilter,
+        Action::SavedFilters,
+        Action::SortNext,
+        Action::SortReverse,
+        Action::Ban,
+        Action::BanList,
+        Action::IgnoreList,
+        Action::SwitchPanel,
+        Action::Up,
+        Action::Down,
+        Action::Activate,
+        Action::Unban,
+        Action::UnbanEverywhere,
+        Action::BanTime,
+        Action::MoveToJail,
+        Action::UnbanAndIgnore,
+        Action::Undo,
+        Action::ToggleMark,
+        Action::MarkRange,
+        Action::MarkAll,
+        Action::ClearMarks,
+        Action::Subnets,
+        Action::AllJails,
+        Action::Whereis,
+        Action::UnbanAll,
+        Action::Export,
+        Action::ToggleAutorefresh,
+        Action::Help,
+        Action::AuditLog,
+        Action::Palette,
+        Action::Confirm,
+        Action::Cancel,
+        Action::IgnoreAdd,
+        Action::IgnoreRemove,
+        Action::IgnoreReload,
+        Action::MoveCopy,
+        Action::MoveKeepTime,
+        Action::SubnetUnban,
+    ];
+
+    /// Headings of the `?` screen, in the order they are shown.
+    pub const SECTIONS: [&'static str; 5] = ["General", "Jails", "Banned IPs", "Filter", "Dialogs"];
+
+    /// The name used for the action in the `[keys]` config table.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Refresh => "refresh",
+            Action::Filter => "filter",
+            Action::ClearFilter => "clear_filter",
+            Action::SavedFilters => "saved_filters",
+            Action::SortNext => "sort_next",
+            Action::SortReverse => "sort_reverse",
+            Action::Ban => "ban",
+            Action::BanList => "ban_list",
+            Action::IgnoreList => "ignore_list",
+            Action::SwitchPanel => "switch_panel",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Activate => "activate",
+            Action::Unban => "unban",
+            Action::UnbanEverywhere => "unban_everywhere",
+            Action::BanTime => "ban_time",
+            Action::MoveToJail => "move_to_jail",
+            Action::UnbanAndIgnore => "unban_and_ignore",
+            Action::Undo => "undo",
+            Action::ToggleMark => "mark",
+            Action::MarkRange => "mark_range",
+            Action::MarkAll => "mark_all",
+            Action::ClearMarks => "clear_marks",
+            Action::Subnets => "subnets",
+            Action::AllJails => "all_jails",
+            Action::Whereis => "whereis",
+            Action::UnbanAll => "unban_all",
+            Action::Export => "export",
+            Action::ToggleAutorefresh => "toggle_autorefresh",
+            Action::Help => "help",
+            Action::AuditLog => "audit_log",
+            Action::Palette => "command_palette",
+            Action::Confirm => "confirm",
+            Action::Cancel => "cancel",
+            Action::IgnoreAdd => "ignore_add",
+            Action::IgnoreRemove => "ignore_remove",
+            Action::IgnoreReload => "ignore_reload",
+            Action::MoveCopy => "move_copy",
+            Action::MoveKeepTime => "move_keep_time",
+            Action::SubnetUnban => "subnet_unban",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Refresh => "refresh",
+            Action::Filter => "filter",
+            Action::ClearFilter => "clear filter",
+            Action::SavedFilters => "saved filters",
+            Action::SortNext => "sort by next column",
+            Action::SortReverse => "reverse sort",
+            Action::Ban => "ban an IP in the selected jail (marked IPs: pick a jail)",
+            Action::BanList => "ban a pasted list of IPs and CIDRs in the selected jail",
+            Action::IgnoreList => "show and edit the selected jail's ignore list (ignoreip)",
+            Action::SwitchPanel => "switch panel",
+            Action::Up => "move up",
+            Action::Down => "move down",
+            Action::Activate => "open jail / unban selected or marked IPs",
+            Action::Unban => "unban selected or marked IPs",
+            Action::UnbanEverywhere => "unban selected IP from every jail",
+            Action::BanTime => "extend, shorten or make permanent the selected ban",
+            Action::MoveToJail => "move or copy the selected IP to another jail",
+            Action::UnbanAndIgnore => "unban the selected IP and add it to the jail's ignore list",
+            Action::Undo => "undo the last unban",
+            Action::ToggleMark => "mark / unmark selected IP",
+            Action::MarkRange => "mark from the last marked IP to here",
+            Action::MarkAll => "mark every IP matching the filter",
+            Action::ClearMarks => "clear marks",
+            Action::Subnets => "toggle the subnet view (IPs grouped by CIDR block)",
+            Action::AllJails => "toggle all-jails view",
+            Action::Whereis => "look up an IP across jails",
+            Action::UnbanAll => "unban all in selected jail",
+            Action::Export => "export",
+            Action::ToggleAutorefresh => "toggle auto-refresh",
+            Action::Help => "help",
+            Action::AuditLog => "browse the audit log",
+            Action::Palette => "command palette",
+            Action::Confirm => "confirm",
+            Action::Cancel => "cancel",
+            Action::IgnoreAdd => "ignore list: add an entry",
+            Action::IgnoreRemove => "ignore list: remove the selected entry",
+            Action::IgnoreReload => "ignore list: reload",
+            Action::MoveCopy => "move dialog: move / copy",
+            Action::MoveKeepTime => "move dialog: keep the time left / use the jail's bantime",
+            Action::SubnetUnban => "subnet ban: unban / keep the single IPs the block covers",
+        }
+    }
+
+    /// Short label for the footer, `None` for actions left out of it.
+    pub fn footer_label(self) -> Option<&'static str> {
+        match self {
+            Action::Quit => Some("quit"),
+            Action::Refresh => Some("refresh"),
+            Action::Filter => Some("filter"),
+            Action::ClearFilter => Some("clear"),
+            Action::SavedFilters => Some("filters"),
+            Action::SortNext => Some("sort"),
+            Action::SortReverse => Some("reverse"),
+            Action::Ban => Some("ban"),
+            Action::SwitchPanel => Some("switch panel"),
+            Action::Activate => Some("unban"),
+            Action::UnbanEverywhere => Some("unban everywhere"),
+            Action::Undo => Some("undo"),
+            Action::ToggleMark => Some("mark"),
+            Action::AllJails => Some("all jails"),
+            Action::Whereis => Some("whereis"),
+            Action::UnbanAll => Some("unban all"),
+            Action::Export => Some("export"),
+            Action::ToggleAutorefresh => Some("auto"),
+            Action::Help => Some("help"),
+            Action::Palette => Some("commands"),
+            Action::Up
+            | Action::Down
+            | Action::AuditLog
+            | Action::BanList
+            | Action::IgnoreList
+            | Action::UnbanAndIgnore
+            | Action::Unban
+            | Action::BanTime
+            | Action::MoveToJail
+            | Action::MarkRange
+            | Action::MarkAll
+            | Action::ClearMarks
+            | Action::Subnets
+            | Action::Confirm
+            | Action::Cancel
+            | Action::IgnoreAdd
+            | Action::IgnoreRemove
+            | Action::IgnoreReload
+            | Action::MoveCopy
+            | Action::MoveKeepTime
+            | Action::SubnetUnban => None,
+        }
+    }
+
+    pub fn context(self) -> KeyContext {
+        match self {
+            Action::Confirm | Action::Cancel => KeyContext::Confirm,
+            Action::IgnoreAdd | Action::IgnoreRemove | Action::IgnoreReload => KeyContext::IgnoreList,
+            Action::MoveCopy | Action::MoveKeepTime => KeyContext::MoveIp,
+            Action::SubnetUnban => KeyContext::BanSubnet,
+            _ => KeyContext::Main,
+        }
+    }
+
+    /// The `?` screen section the action is listed under.
+    pub fn section(self) -> &'static str {
+        match self {
+            Action::Quit
+            | Action::Refresh
+            | Action::SwitchPanel
+            | Action::Up
+            | Action::Down
+            | Action::Whereis
+            | Action::Export
+            | Action::ToggleAutorefresh
+            | Action::Help
+            | Action::AuditLog
+            | Action::Palette => "General",
+            Action::AllJails
+            | Action::Ban
+            | Action::BanList
+            | Action::IgnoreList
+            | Action::UnbanAll => "Jails",
+            Action::Activate
+            | Action::Unban
+            | Action::UnbanEverywhere
+            | Action::BanTime
+            | Action::MoveToJail
+            | Action::UnbanAndIgnore
+            | Action::Undo
+            | Action::ToggleMark
+            | Action::MarkRange
+            | Action::MarkAll
+            | Action::ClearMarks
+            | Action::Subnets
+            | Action::SortNext
+            | Action::SortReverse => "Banned IPs",
+            Action::Filter | Action::ClearFilter | Action::SavedFilters => "Filter",
+            Action::Confirm
+            | Action::Cancel
+            | Action::IgnoreAdd
+            | Action::IgnoreRemove
+            | Action::IgnoreReload
+            | Action::MoveCopy
+            | Action::MoveKeepTime
+            | Action::SubnetUnban => "Dialogs",
+        }
+    }
+
+    /// Whether the `:` palette offers the action. Movement and the dialog
+    /// keys only make sense as keys.
+    pub fn in_palette(self) -> bool {
+        !matches!(self, Action::Up | Action::Down | Action::Palette) && self.context() == KeyContext::Main
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "Q"],
+            Action::Refresh => &["r", "R"],
+            Action::Filter => &["/"],
+            Action::ClearFilter => &["x", "X"],
+            Action::SavedFilters => &["f", "F"],
+            Action::SortNext => &["s"],
+            Action::SortReverse => &["S"],
+            Action::Ban => &["b"],
+            Action::BanList => &["B"],
+            Action::IgnoreList => &["i"],
+            Action::SwitchPanel => &["tab"],
+            Action::Up => &["up", "k"],
+            Action::Down => &["down", "j"],
+            Action::Activate => &["enter"],
+            Action::Unban => &["u"],
+            Action::UnbanEverywhere => &["U"],
+            Action::BanTime => &["d", "D"],
+            Action::MoveToJail => &["m", "M"],
+            Action::UnbanAndIgnore => &["I"],
+            Action::Undo => &["z", "Z"],
+            Action::ToggleMark => &["space"],
+            Action::MarkRange => &["v", "V"],
+            Action::MarkAll => &["*"],
+            Action::ClearMarks => &["esc"],
+            Action::Subnets => &["c", "C"],
+            Action::AllJails => &["g", "G"],
+            Action::Whereis => &["w", "W"],
+            Action::UnbanAll => &["A"],
+            Action::Export => &["e", "E"],
+            Action::ToggleAutorefresh => &["t", "T"],
+            Action::Help => &["?"],
+            Action::AuditLog => &["L"],
+            Action::Palette => &[":"],
+            Action::Confirm => &["y", "Y", "enter"],
+            Action::Cancel => &["n", "N", "esc"],
+            Action::IgnoreAdd => &["a", "A"],
+            Action::IgnoreRemove => &["d", "D", "delete"],
+            Action::IgnoreReload => &["r", "R"],
+            Action::MoveCopy => &["c", "C"],
+            Action::MoveKeepTime => &["t", "T"],
+            Action::SubnetUnban => &["u", "U"],
+        }
+    }
+}
+
+/// One key press. Shift is folded into the character for printable keys, so
+/// `A` and `shift-a` are the same chord.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn from_event(key: &KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            other => other,
+        };
+        Self { code, modifiers }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        while let Some((prefix, tail)) = rest.split_once(['-', '+']).filter(|(_, tail)| !tail.is_empty()) {
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{prefix}' in '{text}'")),
+            };
+            rest = tail;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "space" => KeyCode::Char(' '),
+                "backspace" | "bs" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{rest}' in '{text}'")),
+                },
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Enter => write!(f, "enter"),
+            KeyCode::Esc => write!(f, "esc"),
+            KeyCode::Tab => write!(f, "tab"),
+            KeyCode::BackTab => write!(f, "shift-tab"),
+            KeyCode::Backspace => write!(f, "backspace"),
+            KeyCode::Delete => write!(f, "delete"),
+            KeyCode::Insert => write!(f, "insert"),
+            KeyCode::Up => write!(f, "up"),
+            KeyCode::Down => write!(f, "down"),
+            KeyCode::Left => write!(f, "left"),
+            KeyCode::Right => write!(f, "right"),
+            KeyCode::Home => write!(f, "home"),
+            KeyCode::End => write!(f, "end"),
+            KeyCode::PageUp => write!(f, "pageup"),
+            KeyCode::PageDown => write!(f, "pagedown"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+/// A key sequence such as `g g` or `ctrl-x u`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySeq(Vec<KeyChord>);
+
+impl KeySeq {
+    fn parse(text: &str) -> Result<Self, String> {
+        let chords = text
+            .split_whitespace()
+            .map(KeyChord::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if chords.is_empty() {
+            return Err("empty key binding".to_string());
+        }
+        Ok(Self(chords))
+    }
+}
+
+impl fmt::Display for KeySeq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, chord) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{chord}")?;
+        }
+        Ok(())
+    }
+}
+
+/// `[keys]` entries accept a single binding or a list of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn items(&self) -> Vec<&str> {
+        match self {
+            KeyList::One(key) => vec![key.as_str()],
+            KeyList::Many(keys) => keys.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+pub enum Resolved {
+    Action(Action),
+    Pending,
+    Unbound,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(KeySeq, Action)>,
+    pending: Vec<KeyChord>,
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults with the `[keys]` overrides
+    /// applied. An overridden action loses its default keys, and a key taken
+    /// by an override is removed from other actions in the same context.
+    /// Problems are returned as messages and the offending entry is skipped.
+    pub fn from_config(overrides: &BTreeMap<String, KeyList>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut custom: Vec<(KeySeq, Action)> = Vec::new();
+        for (name, keys) in overrides {
+            let Some(action) = Action::from_name(name) else {
+                warnings.push(format!("unknown action '{name}' in [keys]"));
+                continue;
+            };
+            for key in keys.items() {
+                match KeySeq::parse(key) {
+                    Ok(seq) => custom.push((seq, action)),
+                    Err(err) => warnings.push(err),
+                }
+            }
+        }
+
+        let mut bindings: Vec<(KeySeq, Action)> = Action::ALL
+            .into_iter()
+            .filter(|action| !custom.iter().any(|(_, a)| a == action))
+            .flat_map(|action| {
+                action
+                    .default_keys()
+                    .iter()
+                    .filter_map(move |key| KeySeq::parse(key).ok().map(|seq| (seq, action)))
+            })
+            .filter(|(seq, action)| {
+                !custom
+                    .iter()
+                    .any(|(s, a)| s == seq && a.context() == action.context())
+            })
+            .collect();
+        bindings.extend(custom);
+
+        for (seq, action) in &bindings {
+            if seq.0.len() < 2 {
+                continue;
+            }
+            if let Some((short, other)) = bindings.iter().find(|(s, a)| {
+                a.context() == action.context() && s.0.len() < seq.0.len() && seq.0.starts_with(&s.0)
+            }) {
+                warnings.push(format!(
+                    "'{seq}' ({}) can't be reached because '{short}' is bound to {}",
+                    action.name(),
+                    other.name()
+                ));
+            }
+        }
+
+        (
+            Self {
+                bindings,
+                pending: Vec::new(),
+            },
+            warnings,
+        )
+    }
+
+    /// Feeds one key press. Keys that start a longer binding are held until
+    /// the sequence completes or stops matching.
+    pub fn resolve(&mut self, context: KeyContext, key: &KeyEvent) -> Resolved {
+        self.pending.push(KeyChord::from_event(key));
+        let mut partial = false;
+        for (seq, action) in &self.bindings {
+            if action.context() != context {
+                continue;
+            }
+            if seq.0 == self.pending {
+                self.pending.clear();
+                return Resolved::Action(*action);
+            }
+            partial |= seq.0.starts_with(&self.pending);
+        }
+        if partial {
+            return Resolved::Pending;
+        }
+        let retry = self.pending.len() > 1;
+        self.pending.clear();
+        if retry {
+            return self.resolve(context, key);
+        }
+        Resolved::Unbound
+    }
+
+    pub fn pending(&self) -> Option<String> {
+        (!self.pending.is_empty()).then(|| KeySeq(self.pending.clone()).to_string())
+    }
+
+    /// Whether `key` alone is bound to `action`, whatever its context. Lists
+    /// in dialogs use this to follow the bindings of `up`, `down` and `quit`.
+    pub fn matches(&self, action: Action, key: &KeyEvent) -> bool {
+        let chord = KeyChord::from_event(key);
+        self.bindings
+            .iter()
+            .any(|(seq, a)| *a == action && seq.0 == [chord])
+    }
+
+    /// The first key bound to `action`, for the hints in dialogs.
+    pub fn key_hint(&self, action: Action) -> String {
+        self.keys_for(action)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| "(unbound)".to_string())
+    }
+
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(seq, _)| seq.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured(overrides: &[(&str, &str)]) -> (Keymap, Vec<String>) {
+        let overrides = overrides
+            .iter()
+            .map(|(name, keys)| (name.to_string(), KeyList::One(keys.to_string())))
+            .collect();
+        Keymap::from_config(&overrides)
+    }
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn char(c: char) -> KeyEvent {
+        press(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn resolved(keymap: &mut Keymap, context: KeyContext, key: KeyEvent) -> Option<Action> {
+        match keymap.resolve(context, &key) {
+            Resolved::Action(action) => Some(action),
+            Resolved::Pending | Resolved::Unbound => None,
+        }
+    }
+
+    #[test]
+    fn parses_chords() {
+        let chord = |text| KeyChord::parse(text).unwrap();
+        assert_eq!(chord("ctrl-q"), KeyChord::new(KeyCode::Char('q'), KeyModifiers::CONTROL));
+        assert_eq!(chord("C+q"), chord("ctrl-q"));
+        assert_eq!(chord("shift-a"), chord("A"));
+        assert_eq!(chord("shift-tab"), chord("backtab"));
+        assert_eq!(chord("Alt-Enter"), KeyChord::new(KeyCode::Enter, KeyModifiers::ALT));
+        assert_eq!(chord("-"), KeyChord::new(KeyCode::Char('-'), KeyModifiers::NONE));
+        assert_eq!(chord("ctrl--"), KeyChord::new(KeyCode::Char('-'), KeyModifiers::CONTROL));
+        assert_eq!(chord("space"), KeyChord::new(KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(chord("f12"), KeyChord::new(KeyCode::F(12), KeyModifiers::NONE));
+        assert!(KeyChord::parse("f13").is_err());
+        assert!(KeyChord::parse("hyper-x").is_err());
+        assert!(KeyChord::parse("nosuchkey").is_err());
+        assert_eq!(chord("ctrl-alt-x").to_string(), "ctrl-alt-x");
+        assert_eq!(KeySeq::parse("g  g").unwrap().to_string(), "g g");
+        assert!(KeySeq::parse(" ").is_err());
+    }
+
+    #[test]
+    fn shift_is_folded_into_letters() {
+        let (mut keymap, _) = configured(&[]);
+        let shifted = press(KeyCode::Char('a'), KeyModifiers::SHIFT);
+        assert_eq!(resolved(&mut keymap, KeyContext::Main, shifted), Some(Action::UnbanAll));
+    }
+
+    #[test]
+    fn sequences_wait_for_their_last_key() {
+        let (mut keymap, warnings) = configured(&[("all_jails", "g a"), ("whereis", "g w")]);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert!(matches!(keymap.resolve(KeyContext::Main, &char('g')), Resolved::Pending));
+        assert_eq!(keymap.pending().as_deref(), Some("g"));
+        assert_eq!(resolved(&mut keymap, KeyContext::Main, char('w')), Some(Action::Whereis));
+        assert_eq!(keymap.pending(), None);
+
+        // A key that breaks the sequence is tried on its own.
+        assert!(matches!(keymap.resolve(KeyContext::Main, &char('g')), Resolved::Pending));
+        assert_eq!(resolved(&mut keymap, KeyContext::Main, char('q')), Some(Action::Quit));
+    }
+
+    #[test]
+    fn overrides_replace_defaults_within_a_context() {
+        let (mut keymap, warnings) = configured(&[("quit", "ctrl-q"), ("refresh", "q")]);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(keymap.keys_for(Action::Quit), vec!["ctrl-q"]);
+        assert_eq!(resolved(&mut keymap, KeyContext::Main, char('q')), Some(Action::Refresh));
+        let ctrl_q = press(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        assert_eq!(resolved(&mut keymap, KeyContext::Main, ctrl_q), Some(Action::Quit));
+
+        // Dialog keys live in their own context, so `d` still removes from
+        // the ignore list when it is bound elsewhere.
+        let (mut keymap, _) = configured(&[("unban", "d")]);
+        assert_eq!(resolved(&mut keymap, KeyContext::Main, char('d')), Some(Action::Unban));
+        assert_eq!(resolved(&mut keymap, KeyContext::IgnoreList, char('d')), Some(Action::IgnoreRemove));
+        assert_eq!(resolved(&mut keymap, KeyContext::MoveIp, char('d')), None);
+        assert!(keymap.matches(Action::Down, &char('j')));
+        assert!(!keymap.matches(Action::Down, &char('k')));
+    }
+
+    #[test]
+    fn bad_overrides_are_warned_about_and_skipped() {
+        let (keymap, warnings) = configured(&[("frobnicate", "x"), ("refresh", "hyper-r")]);
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("frobnicate"));
+        assert!(warnings[1].contains("hyper"));
+        // The action keeps its defaults when its only override is skipped.
+        assert_eq!(keymap.keys_for(Action::Refresh), vec!["r", "R"]);
+        assert_eq!(keymap.key_hint(Action::Refresh), "r");
+
+        let (_, warnings) = configured(&[("whereis", "g w")]);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].contains("can't be reached"), "{warnings:?}");
+    }
+}
@@ -1,24 +1,57 @@
-use std::collections::HashSet;
+mod audit;
+mod backend;
+mod cli;
+mod config;
+mod export;
+mod journal;
+mod keymap;
+mod net;
+mod palette;
+mod pickle;
+mod query;
+mod refresh;
+mod socket;
+mod theme;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io;
 use std::net::IpAddr;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use crossterm::event::{
-    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event,
+    KeyCode, KeyEvent, KeyModifiers,
     MouseEvent, MouseEventKind,
 };
 use crossterm::execute;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::CrosstermBackend;
-use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::layout::{Alignment, Constraint, Direction, Flex, Layout, Rect};
+use ratatui::style::Style;
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::widgets::{
+    Block, Borders, Cell, Clear, HighlightSpacing, List, ListItem, ListState, Paragraph, Row, Table,
+    TableState, Wrap,
+};
 use ratatui::Terminal;
 
+use audit::{AuditLog, AuditRecord};
+use backend::{backend_kind, open_backend, Fail2banBackend};
+use cli::{Command as CliCommand, EXIT_FAILURE, EXIT_USAGE, USAGE};
+use config::{Config, SavedFilter};
+use export::{default_export_name, export_to_file, ExportFormat};
+use journal::{Journal, UndoBan};
+use keymap::{Action, KeyContext, KeyList, Keymap, Resolved};
+use net::{compare_addrs, Cidr};
+use palette::matching_actions;
+use query::{Candidate, Query};
+use refresh::{lock_backend, Refresher, SharedBackend};
+use theme::Theme;
+
 #[derive(Debug, Clone)]
 struct TimeValue {
     raw: String,
@@ -28,6 +61,21 @@ struct TimeValue {
 #[derive(Debug, Clone)]
 struct IpEntry {
     ip: String,
+    addr: Option<IpAddr>,
+    start_epoch: Option<i64>,
+    end_epoch: Option<i64>,
+    time_raw: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IpRow<'a> {
+    jail: &'a str,
+    entry: &'a IpEntry,
+}
+
+#[derive(Debug, Clone)]
+struct IpMatch {
+    jail: String,
     end_epoch: Option<i64>,
     time_raw: Option<String>,
 }
@@ -43,60 +91,259 @@ struct JailStatus {
     total_banned: Option<u32>,
 }
 
+#[derive(Debug, Clone)]
+struct ReleasedIp {
+    jail: String,
+    ip: String,
+    at: i64,
+}
+
+const FRESH_CYCLES: u8 = 3;
+/// fail2ban reports permanent bans as ending in year 9999.
+const PERMANENT_END_EPOCH: i64 = 253_370_764_800;
+const RELEASED_KEEP_SECS: i64 = 30 * 60;
+const RELEASED_MAX: usize = 50;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SortMode {
+enum Column {
     Ip,
-    TimeLeft,
+    Jail,
+    Left,
+    Expires,
+    Started,
+    Raw,
+}
+
+impl Column {
+    const ALL: [Column; 6] = [
+        Column::Ip,
+        Column::Jail,
+        Column::Left,
+        Column::Expires,
+        Column::Started,
+        Column::Raw,
+    ];
+
+    fn key(self) -> &'static str {
+        match self {
+            Column::Ip => "ip",
+            Column::Jail => "jail",
+            Column::Left => "left",
+            Column::Expires => "expires",
+            Column::Started => "started",
+            Column::Raw => "raw",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Column::ALL.into_iter().find(|c| c.key().eq_ignore_ascii_case(key))
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Column::Ip => "IP",
+            Column::Jail => "Jail",
+            Column::Left => "Left",
+            Column::Expires => "Expires",
+            Column::Started => "Banned at",
+            Column::Raw => "fail2ban time",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 enum Modal {
     UnbanIp { jail: String, ip: String },
     UnbanAll { jail: String, step: u8 },
-    BanIp { jail: String, input: String, error: Option<String> },
+    UnbanEverywhere { ip: String, jails: Vec<String> },
+    BanIp { jail: String, input: String, duration: String, on_duration: bool, error: Option<String> },
+    BanTime { jail: String, ip: String, end_epoch: Option<i64>, input: String, error: Option<String> },
+    /// `overwrite` is set once enter found the file already there.
+    Export { input: String, scope: ExportScope, error: Option<String>, overwrite: bool },
+    Lookup { input: String, matches: Option<Vec<IpMatch>>, error: Option<String> },
+    Filters { selected: usize, name: Option<String> },
+    Help { scroll: u16 },
+    Palette { input: String, selected: usize },
+    UnbanMarked { targets: Vec<(String, String)> },
+    Undo { label: String, count: usize },
+    Audit { records: Vec<AuditRecord>, scroll: u16, error: Option<String> },
+    BanMarked { ips: Vec<String>, selected: usize },
+    BanList { jail: String, text: String, review: bool },
+    BanSubnet { cidr: Cidr, selected: usize, unban: bool },
+    IgnoreList {
+        jail: String,
+        entries: Vec<String>,
+        selected: usize,
+        input: Option<String>,
+        removing: bool,
+        error: Option<String>,
+    },
+    UnbanIgnore { jail: String, ip: String },
+    MoveIp {
+        jail: String,
+        ip: String,
+        targets: Vec<String>,
+        selected: usize,
+        copy: bool,
+        keep_time: bool,
+    },
+    Report { title: String, lines: Vec<String> },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportScope {
+    All,
+    Filtered,
+    Marked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 enum Focus {
+    #[default]
     Jails,
     Ips,
 }
 
-impl Default for Focus {
-    fn default() -> Self {
-        Self::Jails
-    }
-}
-
-#[derive(Debug)]
 struct UiState {
+    backend: SharedBackend,
+    refresher: Refresher,
     jails: Vec<JailStatus>,
     jail_state: ListState,
-    ip_state: ListState,
+    ip_state: TableState,
     focus: Focus,
     status: String,
     modal: Option<Modal>,
     search_query: String,
     search_mode: bool,
-    sort_mode: SortMode,
+    filter: Query,
+    filter_error: Option<String>,
+    view_all: bool,
+    sort_column: Column,
+    sort_desc: bool,
     autorefresh: bool,
     refresh_interval: Duration,
     last_refresh: Instant,
     jail_rect: Option<Rect>,
     ip_rect: Option<Rect>,
+    ip_columns: Vec<(Column, Rect)>,
     modal_yes_rect: Option<Rect>,
     modal_no_rect: Option<Rect>,
+    has_snapshot: bool,
+    fresh: HashMap<(String, String), u8>,
+    marked: BTreeSet<(String, String)>,
+    mark_anchor: Option<(String, String)>,
+    journal: Journal,
+    released: Vec<ReleasedIp>,
+    last_diff: Option<(usize, usize)>,
+    saved_filters: Vec<SavedFilter>,
+    start_jail: Option<String>,
+    keymap: Keymap,
+    key_overrides: BTreeMap<String, KeyList>,
+    theme: Theme,
+    theme_name: Option<String>,
+    colors: Option<String>,
+    themes: BTreeMap<String, BTreeMap<String, String>>,
+    audit_log: Option<String>,
+    audit_syslog: bool,
+    audit_path: Option<PathBuf>,
+    subnets: bool,
+    group_prefix: (u8, u8),
+    bantime_override: bool,
+    own_ban_times: bool,
+    ignore_lists: HashMap<String, Vec<String>>,
 }
 
 impl UiState {
-    fn new() -> Self {
-        let mut state = Self::default();
-        state.focus = Focus::Jails;
-        state.sort_mode = SortMode::Ip;
-        state.autorefresh = false;
-        state.refresh_interval = Duration::from_secs(5);
-        state.last_refresh = Instant::now();
-        state
+    fn new(mut backend: Box<dyn Fail2banBackend>, config: Config) -> Self {
+        let own_ban_times = backend.own_ban_times();
+        let backend: SharedBackend = Arc::new(Mutex::new(backend));
+        let (keymap, mut warnings) = Keymap::from_config(&config.keys);
+        let (theme, theme_warnings) =
+            Theme::load(config.theme.as_deref(), config.colors.as_deref(), &config.themes);
+        warnings.extend(theme_warnings);
+        let audit_path = AuditLog::from_config(&config, "tui").path().map(PathBuf::from);
+        Self {
+            refresher: Refresher::spawn(Arc::clone(&backend)),
+            backend,
+            jails: Vec::new(),
+            jail_state: ListState::default(),
+            ip_state: TableState::default(),
+            focus: Focus::default(),
+            status: warnings.join("; "),
+            modal: None,
+            search_query: String::new(),
+            search_mode: false,
+            filter: Query::All,
+            filter_error: None,
+            view_all: false,
+            sort_column: Column::from_key(&config.sort).unwrap_or(Column::Ip),
+            sort_desc: config.sort_desc,
+            autorefresh: config.autorefresh,
+            refresh_interval: Duration::from_secs(config.refresh_interval.max(1)),
+            last_refresh: Instant::now(),
+            jail_rect: None,
+            ip_rect: None,
+            ip_columns: Vec::new(),
+            modal_yes_rect: None,
+            modal_no_rect: None,
+            has_snapshot: false,
+            fresh: HashMap::new(),
+            marked: BTreeSet::new(),
+            mark_anchor: None,
+            journal: Journal::default(),
+            released: Vec::new(),
+            last_diff: None,
+            saved_filters: config.filters,
+            start_jail: config.jail,
+            keymap,
+            key_overrides: config.keys,
+            theme,
+            theme_name: config.theme,
+            colors: config.colors,
+            themes: config.themes,
+            audit_path,
+            audit_log: config.audit_log,
+            audit_syslog: config.audit_syslog,
+            subnets: false,
+            group_prefix: (config.group_prefix_v4, config.group_prefix_v6),
+            bantime_override: config.bantime_override,
+            own_ban_times,
+            ignore_lists: HashMap::new(),
+        }
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            sort: self.sort_column.key().to_string(),
+            sort_desc: self.sort_desc,
+            autorefresh: self.autorefresh,
+            refresh_interval: self.refresh_interval.as_secs(),
+            jail: self
+                .selected_jail()
+                .map(|j| j.name.clone())
+                .or_else(|| self.start_jail.clone()),
+            filters: self.saved_filters.clone(),
+            keys: self.key_overrides.clone(),
+            theme: self.theme_name.clone(),
+            colors: self.colors.clone(),
+            themes: self.themes.clone(),
+            audit_log: self.audit_log.clone(),
+            audit_syslog: self.audit_syslog,
+            group_prefix_v4: self.group_prefix.0,
+            group_prefix_v6: self.group_prefix.1,
+            bantime_override: self.bantime_override,
+        }
+    }
+
+    fn set_filter(&mut self, query: &str) {
+        self.search_query = query.to_string();
+        match Query::parse(query) {
+            Ok(parsed) => {
+                self.filter = parsed;
+                self.filter_error = None;
+            }
+            Err(err) => self.filter_error = Some(err),
+        }
     }
 
     fn selected_jail_index(&self) -> usize {
@@ -111,35 +358,268 @@ impl UiState {
         self.jails.get(self.selected_jail_index())
     }
 
-    fn selected_ip<'a>(&self, view: &'a [&'a IpEntry]) -> Option<&'a IpEntry> {
+    fn selected_ip<'a>(&self, view: &[IpRow<'a>]) -> Option<IpRow<'a>> {
         view.get(self.selected_ip_index()).copied()
     }
 
+    /// The selected IP row; `None` in the subnet view, where rows are blocks.
+    fn selected_row(&self) -> Option<(String, String)> {
+        if self.subnets {
+            return None;
+        }
+        let view = current_ip_view(self, &self.jails);
+        self.selected_ip(&view)
+            .map(|row| (row.jail.to_string(), row.entry.ip.clone()))
+    }
+
+    /// Reads `jail`'s ignore list from the server and keeps it for the
+    /// warnings of the ban dialogs.
+    fn load_ignore_list(&mut self, jail: &str) -> Result<Vec<String>> {
+        let output = self.backend().get(jail, &["ignoreip"])?;
+        let list = parse_ignore_list(&output);
+        self.ignore_lists.insert(jail.to_string(), list.clone());
+        Ok(list)
+    }
+
+    /// [`UiState::load_ignore_list`] for each jail a picker offers.
+    fn load_ignore_lists(&mut self, jails: &[String]) {
+        for jail in jails {
+            self.load_ignore_list(jail).ok();
+        }
+    }
+
+    /// The entry of `jail`'s last read ignore list that covers `ip`.
+    fn ignored(&self, jail: &str, ip: &str) -> Option<&str> {
+        ignore_match(self.ignore_lists.get(jail)?, ip)
+    }
+
+    /// Rows in the IP panel: IPs, or CIDR blocks in the subnet view.
+    fn ip_rows(&self) -> usize {
+        let view = current_ip_view(self, &self.jails);
+        if self.subnets {
+            subnet_groups(self, &view).len()
+        } else {
+            view.len()
+        }
+    }
+
+    /// The block selected in the subnet view and the IPs of it that are shown.
+    fn selected_group(&self) -> Option<(Cidr, Vec<(String, String)>)> {
+        let view = current_ip_view(self, &self.jails);
+        let group = subnet_groups(self, &view).into_iter().nth(self.selected_ip_index())?;
+        let keys = group
+            .rows
+            .iter()
+            .map(|row| (row.jail.to_string(), row.entry.ip.clone()))
+            .collect();
+        Some((group.cidr, keys))
+    }
+
+    fn jails_holding(&self, ip: &str) -> Vec<String> {
+        self.jails
+            .iter()
+            .filter(|j| j.ips.iter().any(|e| e.ip == ip))
+            .map(|j| j.name.clone())
+            .collect()
+    }
+
+    /// What undo needs to ban `ip` in `jail` again for the time it has left.
+    fn undo_ban(&self, jail: &str, ip: &str) -> UndoBan {
+        let status = self.jails.iter().find(|j| j.name == jail);
+        let end_epoch = status
+            .and_then(|j| j.ips.iter().find(|e| e.ip == ip))
+            .and_then(|e| e.end_epoch);
+        let permanent = status.is_some_and(|j| j.bantime.raw.trim() == "-1")
+            || end_epoch.is_some_and(|end| end >= PERMANENT_END_EPOCH);
+        let bantime = if permanent {
+            Some(-1)
+        } else {
+            remaining_seconds(end_epoch)
+                .filter(|secs| *secs > 0)
+                .map(|secs| secs as i64)
+        };
+        UndoBan {
+            jail: jail.to_string(),
+            ip: ip.to_string(),
+            bantime,
+        }
+    }
+
+    /// Why bans of `bantime` in `jail` would get the jail's bantime instead,
+    /// going by the bantime in the last snapshot.
+    fn ban_time_problem(&self, jail: &str, bantime: Option<i64>) -> Option<String> {
+        let current = self
+            .jails
+            .iter()
+            .find(|j| j.name == jail)
+            .and_then(|j| j.bantime.raw.trim().parse::<i64>().ok());
+        let why = ban_time_way(self.own_ban_times, current, bantime?, self.bantime_override).err()?;
+        Some(format!("{jail}: {why}"))
+    }
+
     fn set_status<S: Into<String>>(&mut self, msg: S) {
         self.status = msg.into();
     }
 
+    fn backend(&self) -> MutexGuard<'_, Box<dyn Fail2banBackend>> {
+        lock_backend(&self.backend)
+    }
+
     fn refresh(&mut self) {
-        match fetch_status() {
+        self.refresher.request();
+    }
+
+    fn refreshing(&self) -> bool {
+        self.refresher.is_pending()
+    }
+
+    fn poll_refresh(&mut self) {
+        let Some(result) = self.refresher.poll() else {
+            return;
+        };
+        self.last_refresh = Instant::now();
+        match result {
             Ok(jails) => {
-                self.jails = jails;
+                let first = !self.has_snapshot;
+                self.apply_snapshot(jails);
                 if self.jails.is_empty() {
-                    self.jail_state.select(None);
-                    self.ip_state.select(None);
                     self.set_status("No jails reported by fail2ban-client");
-                } else {
-                    self.jail_state.select(Some(0));
-                    self.ip_state.select(Some(0));
+                } else if !first || self.status.is_empty() {
                     self.set_status("Refreshed");
                 }
-                self.last_refresh = Instant::now();
             }
             Err(err) => {
-                self.set_status(format!("Refresh failed: {err}"));
+                if self.jails.is_empty() {
+                    self.set_status(format!("Refresh failed: {err}"));
+                } else {
+                    self.set_status(format!("Refresh failed, showing last snapshot: {err}"));
+                }
             }
         }
     }
 
+    fn apply_snapshot(&mut self, jails: Vec<JailStatus>) {
+        let old_jails: Vec<String> = self.jails.iter().map(|j| j.name.clone()).collect();
+        let old_ips: Vec<(String, String)> = current_ip_view(self, &self.jails)
+            .iter()
+            .map(|row| (row.jail.to_string(), row.entry.ip.clone()))
+            .collect();
+        let old_jail = self.selected_jail().map(|j| j.name.clone());
+        let jail_index = self.jail_state.selected();
+        let ip_index = self.ip_state.selected();
+        let jail_offset = self.jail_state.offset();
+        let ip_offset = self.ip_state.offset();
+
+        self.track_changes(&jails);
+        self.jails = jails;
+        if self.jails.is_empty() {
+            self.jail_state.select(None);
+            self.ip_state.select(None);
+            return;
+        }
+
+        let new_jail = match jail_index {
+            Some(index) if !old_jails.is_empty() => nearest_surviving(&old_jails, index, |name| {
+                self.jails.iter().position(|j| &j.name == name)
+            })
+            .unwrap_or_else(|| index.min(self.jails.len() - 1)),
+            _ => self
+                .start_jail
+                .take()
+                .and_then(|name| self.jails.iter().position(|j| j.name == name))
+                .unwrap_or(0),
+        };
+        self.jail_state.select(Some(new_jail));
+        *self.jail_state.offset_mut() = jail_offset;
+
+        let same_view =
+            self.view_all || old_jail.as_deref() == Some(self.jails[new_jail].name.as_str());
+        let new_ip = match ip_index {
+            Some(index) if same_view && !old_ips.is_empty() => {
+                let view = current_ip_view(self, &self.jails);
+                nearest_surviving(&old_ips, index, |(jail, ip)| {
+                    view.iter()
+                        .position(|row| row.jail == jail && &row.entry.ip == ip)
+                })
+                .unwrap_or_else(|| index.min(view.len().saturating_sub(1)))
+            }
+            _ => 0,
+        };
+        self.ip_state.select(Some(new_ip));
+        if same_view {
+            *self.ip_state.offset_mut() = ip_offset;
+        }
+    }
+
+    fn track_changes(&mut self, jails: &[JailStatus]) {
+        let keys = |jails: &[JailStatus]| -> HashSet<(String, String)> {
+            jails
+                .iter()
+                .flat_map(|j| j.ips.iter().map(move |e| (j.name.clone(), e.ip.clone())))
+                .collect()
+        };
+        let before = keys(&self.jails);
+        let after = keys(jails);
+
+        self.fresh.retain(|key, cycles| {
+            *cycles = cycles.saturating_sub(1);
+            *cycles > 0 && after.contains(key)
+        });
+        self.marked.retain(|key| after.contains(key));
+        let now = Utc::now().timestamp();
+        self.released.retain(|r| {
+            now - r.at < RELEASED_KEEP_SECS && !after.contains(&(r.jail.clone(), r.ip.clone()))
+        });
+
+        if self.has_snapshot {
+            let added: Vec<_> = after.difference(&before).cloned().collect();
+            let removed: Vec<_> = before.difference(&after).cloned().collect();
+            self.last_diff = Some((added.len(), removed.len()));
+            for key in added {
+                self.fresh.insert(key, FRESH_CYCLES);
+            }
+            for (jail, ip) in removed {
+                self.released.insert(0, ReleasedIp { jail, ip, at: now });
+            }
+            self.released.truncate(RELEASED_MAX);
+        }
+        self.has_snapshot = true;
+    }
+
+    fn is_fresh(&self, jail: &str, ip: &str) -> bool {
+        self.fresh.contains_key(&(jail.to_string(), ip.to_string()))
+    }
+
+    fn sortable_columns(&self) -> Vec<Column> {
+        Column::ALL
+            .into_iter()
+            .filter(|c| *c != Column::Jail || self.view_all)
+            .collect()
+    }
+
+    /// Sorts by `column`, flipping the direction when it is already the sort
+    /// column, and keeps the selected row selected.
+    fn sort_by(&mut self, column: Column) {
+        let selected = self.selected_row();
+        if self.sort_column == column {
+            self.sort_desc = !self.sort_desc;
+        } else {
+            self.sort_column = column;
+            self.sort_desc = false;
+        }
+        let index = selected.and_then(|(jail, ip)| {
+            current_ip_view(self, &self.jails)
+                .iter()
+                .position(|row| row.jail == jail && row.entry.ip == ip)
+        });
+        self.ip_state.select(Some(index.unwrap_or(0)));
+        self.set_status(format!(
+            "Sorted by {} ({})",
+            column.title(),
+            if self.sort_desc { "descending" } else { "ascending" }
+        ));
+    }
+
     fn move_jail(&mut self, delta: i32) {
         if self.jails.is_empty() {
             return;
@@ -152,55 +632,91 @@ impl UiState {
     }
 
     fn move_ip(&mut self, delta: i32) {
-        let Some(jail) = self.selected_jail() else {
-            return;
-        };
-        let view = current_ip_view(self, jail);
-        if view.is_empty() {
+        let len = self.ip_rows() as i32;
+        if len == 0 {
             return;
         }
-        let len = view.len() as i32;
         let current = self.ip_state.selected().unwrap_or(0) as i32;
         let next = (current + delta).clamp(0, len.saturating_sub(1));
         self.ip_state.select(Some(next as usize));
     }
-}
 
-impl Default for UiState {
-    fn default() -> Self {
-        Self {
-            jails: Vec::new(),
-            jail_state: ListState::default(),
-            ip_state: ListState::default(),
-            focus: Focus::default(),
-            status: String::new(),
-            modal: None,
-            search_query: String::new(),
-            search_mode: false,
-            sort_mode: SortMode::Ip,
-            autorefresh: false,
-            refresh_interval: Duration::from_secs(5),
-            last_refresh: Instant::now(),
-            jail_rect: None,
-            ip_rect: None,
-            modal_yes_rect: None,
-            modal_no_rect: None,
+    fn toggle_mark(&mut self) {
+        if self.subnets {
+            self.toggle_group_marks();
+            return;
         }
+        let Some(key) = self.selected_row() else {
+            return;
+        };
+        if !self.marked.remove(&key) {
+            self.marked.insert(key.clone());
+        }
+        self.mark_anchor = Some(key);
+        self.move_ip(1);
+    }
+
+    /// Marks the IPs of the selected block, or unmarks them when they are all
+    /// marked already.
+    fn toggle_group_marks(&mut self) {
+        let Some((cidr, keys)) = self.selected_group() else {
+            return;
+        };
+        if keys.iter().all(|key| self.marked.contains(key)) {
+            for key in &keys {
+                self.marked.remove(key);
+            }
+            self.set_status(format!("Unmarked the IPs in {cidr}"));
+        } else {
+            self.set_status(format!("Marked {} IPs in {cidr}", keys.len()));
+            self.marked.extend(keys);
+        }
+        self.move_ip(1);
+    }
+
+    /// Marks every row between the last toggled row and the selected one.
+    fn mark_range(&mut self) {
+        if self.subnets {
+            self.toggle_group_marks();
+            return;
+        }
+        let view = current_ip_view(self, &self.jails);
+        let current = self.selected_ip_index();
+        let anchor = self
+            .mark_anchor
+            .as_ref()
+            .and_then(|(jail, ip)| view.iter().position(|row| row.jail == jail && &row.entry.ip == ip))
+            .unwrap_or(current);
+        let keys: Vec<(String, String)> = view
+            .iter()
+            .skip(anchor.min(current))
+            .take(anchor.abs_diff(current) + 1)
+            .map(|row| (row.jail.to_string(), row.entry.ip.clone()))
+            .collect();
+        let count = keys.len();
+        self.mark_anchor = keys.get(current.saturating_sub(anchor.min(current))).cloned();
+        self.marked.extend(keys);
+        self.set_status(format!("Marked {count} IPs ({} in total)", self.marked.len()));
     }
-}
 
-fn run_fail2ban(args: &[&str]) -> Result<String> {
-    let output = Command::new("fail2ban-client")
-        .args(args)
-        .output()
-        .with_context(|| "failed to execute fail2ban-client")?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        let msg = if !stderr.is_empty() { stderr } else { stdout };
-        return Err(anyhow!(msg));
+    /// Marks every row the filter lets through, or unmarks them when they
+    /// are all marked already.
+    fn mark_all_visible(&mut self) {
+        let keys: Vec<(String, String)> = current_ip_view(self, &self.jails)
+            .iter()
+            .map(|row| (row.jail.to_string(), row.entry.ip.clone()))
+            .collect();
+        if !keys.is_empty() && keys.iter().all(|key| self.marked.contains(key)) {
+            for key in &keys {
+                self.marked.remove(key);
+            }
+            self.set_status(format!("Unmarked {} IPs", keys.len()));
+        } else {
+            let count = keys.len();
+            self.marked.extend(keys);
+            self.set_status(format!("Marked {count} IPs ({} in total)", self.marked.len()));
+        }
     }
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
 fn parse_jail_list(output: &str) -> Vec<String> {
@@ -221,6 +737,11 @@ fn parse_jail_list(output: &str) -> Vec<String> {
     Vec::new()
 }
 
+/// Whether fail2ban can hold `token` as a ban: an IP or a CIDR block.
+fn is_ban_target(token: &str) -> bool {
+    token.parse::<IpAddr>().is_ok() || Cidr::parse(token).is_some()
+}
+
 fn extract_ips(text: &str) -> Vec<String> {
     let mut seen = HashSet::new();
     let mut ips = Vec::new();
@@ -228,13 +749,75 @@ fn extract_ips(text: &str) -> Vec<String> {
         if token.is_empty() {
             continue;
         }
-        if token.parse::<IpAddr>().is_ok() && seen.insert(token.to_string()) {
+        if is_ban_target(token) && seen.insert(token.to_string()) {
             ips.push(token.to_string());
         }
     }
     ips
 }
 
+/// A list of IPs and CIDR blocks to ban in one jail, as found in pasted
+/// text or a file.
+#[derive(Debug, Default)]
+struct BanPlan {
+    new: Vec<String>,
+    already: Vec<String>,
+    invalid: Vec<String>,
+}
+
+/// Splits `text` like [`extract_ips`] does, skipping `#` comments, dropping
+/// repeats and keeping apart what `banned` already holds and the words that
+/// are neither an IP nor a CIDR block.
+fn plan_ban_list(text: &str, banned: &[IpEntry]) -> BanPlan {
+    let text: Vec<&str> = text.lines().map(|line| line.split('#').next().unwrap_or_default()).collect();
+    let text = text.join("\n").replace(',', " ");
+    let mut seen = HashSet::new();
+    let mut list = BanPlan::default();
+    for token in text.split_whitespace() {
+        if !seen.insert(token) {
+            continue;
+        }
+        if !is_ban_target(token) {
+            list.invalid.push(token.to_string());
+        } else if banned.iter().any(|entry| entry.ip == token) {
+            list.already.push(token.to_string());
+        } else {
+            list.new.push(token.to_string());
+        }
+    }
+    list
+}
+
+/// Entries of `get <jail> ignoreip`: `fail2ban-client` draws them as a tree
+/// under a heading, the socket sends one per line.
+fn parse_ignore_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(|line| line.trim().trim_start_matches("|-").trim_start_matches("`-").trim())
+        .filter(|line| !line.is_empty() && !line.ends_with(':') && !line.starts_with("No IP"))
+        .map(String::from)
+        .collect()
+}
+
+/// The ignore list entry that covers `ip`: the same address or a CIDR block
+/// holding it. For a CIDR block `ip`, entries inside the block count too.
+/// Host names are not resolved.
+fn ignore_match<'a>(ignore: &'a [String], ip: &str) -> Option<&'a str> {
+    let addr = ip.parse::<IpAddr>().ok();
+    let block = Cidr::parse(ip);
+    ignore.iter().map(String::as_str).find(|entry| {
+        *entry == ip
+            || addr.is_some_and(|addr| {
+                entry.parse::<IpAddr>().ok() == Some(addr)
+                    || Cidr::parse(entry).is_some_and(|cidr| cidr.contains(&addr))
+            })
+            || block.is_some_and(|block| {
+                entry.parse::<IpAddr>().is_ok_and(|addr| block.contains(&addr))
+                    || Cidr::parse(entry).is_some_and(|cidr| cidr.overlaps(&block))
+            })
+    })
+}
+
 fn parse_banned_ips(output: &str) -> Vec<String> {
     if let Some(idx) = output.find("Banned IP list:") {
         let tail = &output[idx + "Banned IP list:".len()..];
@@ -320,6 +903,28 @@ fn parse_time_to_epoch(time_str: &str, bantime_secs: Option<u64>) -> Option<i64>
     None
 }
 
+/// fail2ban prints `start + bantime = end`; fall back to `end - bantime` when
+/// the start stamp is missing.
+fn parse_start_epoch(time_str: &str, end_epoch: Option<i64>, bantime_secs: Option<u64>) -> Option<i64> {
+    if let Some((start, _)) = time_str.split_once(" + ")
+        && let Some((date, time, tz)) = extract_last_datetime(start)
+    {
+        let stamp = format!("{date} {time} {}", tz.unwrap_or_else(|| "+0000".to_string()));
+        if let Ok(dt) = DateTime::parse_from_str(&stamp, "%Y-%m-%d %H:%M:%S %z") {
+            return Some(dt.timestamp());
+        }
+    }
+    Some(end_epoch? - i64::try_from(bantime_secs?).ok()?)
+}
+
+/// The `bantime` of `start + bantime = end`: the ban's own time, or the
+/// jail's when it has none.
+fn parse_ban_seconds(time_str: &str) -> Option<i64> {
+    let (_, rest) = time_str.split_once(" + ")?;
+    let (seconds, _) = rest.split_once(" = ")?;
+    seconds.trim().parse().ok()
+}
+
 fn extract_last_datetime(input: &str) -> Option<(String, String, Option<String>)> {
     let parts: Vec<&str> = input.split_whitespace().collect();
     let mut last: Option<(String, String, Option<String>)> = None;
@@ -389,12 +994,14 @@ fn parse_banip_with_time(output: &str, bantime_secs: Option<u64>) -> Vec<IpEntry
     let mut time_tokens: Vec<&str> = Vec::new();
 
     for token in output.split_whitespace() {
-        if token.parse::<IpAddr>().is_ok() {
+        if is_ban_target(token) {
             if let Some(ip) = current_ip.take() {
                 let time_str = time_tokens.join(" ");
                 let end_epoch = parse_time_to_epoch(&time_str, bantime_secs);
                 entries.push(IpEntry {
+                    addr: ip.parse().ok(),
                     ip,
+                    start_epoch: parse_start_epoch(&time_str, end_epoch, bantime_secs),
                     end_epoch,
                     time_raw: if time_str.is_empty() { None } else { Some(time_str) },
                 });
@@ -410,7 +1017,9 @@ fn parse_banip_with_time(output: &str, bantime_secs: Option<u64>) -> Vec<IpEntry
         let time_str = time_tokens.join(" ");
         let end_epoch = parse_time_to_epoch(&time_str, bantime_secs);
         entries.push(IpEntry {
+            addr: ip.parse().ok(),
             ip,
+            start_epoch: parse_start_epoch(&time_str, end_epoch, bantime_secs),
             end_epoch,
             time_raw: if time_str.is_empty() { None } else { Some(time_str) },
         });
@@ -419,43 +1028,62 @@ fn parse_banip_with_time(output: &str, bantime_secs: Option<u64>) -> Vec<IpEntry
     entries
 }
 
-fn ips_from_status(output: &str) -> Vec<IpEntry> {
-    parse_banned_ips(output)
-        .into_iter()
+fn ips_from_status(ips: Vec<String>) -> Vec<IpEntry> {
+    ips.into_iter()
         .map(|ip| IpEntry {
+            addr: ip.parse().ok(),
             ip,
+            start_epoch: None,
             end_epoch: None,
             time_raw: None,
         })
         .collect()
 }
 
-fn fetch_status() -> Result<Vec<JailStatus>> {
-    let status = run_fail2ban(&["status"])?;
-    let jails = parse_jail_list(&status);
+fn nearest_surviving<T>(
+    old: &[T],
+    index: usize,
+    find: impl Fn(&T) -> Option<usize>,
+) -> Option<usize> {
+    let index = index.min(old.len().checked_sub(1)?);
+    for distance in 0..old.len() {
+        let after = old.get(index + distance);
+        let before = index.checked_sub(distance).and_then(|i| old.get(i));
+        if let Some(found) = after.and_then(&find).or_else(|| before.and_then(&find)) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn fetch_status(backend: &mut dyn Fail2banBackend) -> Result<Vec<JailStatus>> {
+    let jails = backend.jails()?;
     let mut results = Vec::new();
     for jail in jails {
-        let jail_status = run_fail2ban(&["status", &jail])?;
-        let (currently_banned, total_banned) = parse_status_counts(&jail_status);
-        let bantime = run_fail2ban(&["get", &jail, "bantime"])
+        let summary = backend.jail_status(&jail)?;
+        let bantime = backend
+            .get(&jail, &["bantime"])
             .map(|v| parse_time_value(&v))
             .unwrap_or(TimeValue {
                 raw: "n/a".to_string(),
                 seconds: None,
             });
-        let findtime = run_fail2ban(&["get", &jail, "findtime"])
+        let findtime = backend
+            .get(&jail, &["findtime"])
             .map(|v| parse_time_value(&v))
             .unwrap_or(TimeValue {
                 raw: "n/a".to_string(),
                 seconds: None,
             });
-        let maxretry = run_fail2ban(&["get", &jail, "maxretry"])
+        let maxretry = backend
+            .get(&jail, &["maxretry"])
             .ok()
             .and_then(|v| parse_maxretry(&v));
 
-        let ips = run_fail2ban(&["get", &jail, "banip", "--with-time"])
+        let ips = backend
+            .get(&jail, &["banip", "--with-time"])
             .map(|output| parse_banip_with_time(&output, bantime.seconds))
-            .unwrap_or_else(|_| ips_from_status(&jail_status));
+            .unwrap_or_else(|_| ips_from_status(summary.ips));
 
         results.push(JailStatus {
             name: jail,
@@ -463,8 +1091,8 @@ fn fetch_status() -> Result<Vec<JailStatus>> {
             bantime,
             findtime,
             maxretry,
-            currently_banned,
-            total_banned,
+            currently_banned: summary.currently_banned,
+            total_banned: summary.total_banned,
         });
     }
     results.sort_by(|a, b| b.ips.len().cmp(&a.ips.len()).then_with(|| a.name.cmp(&b.name)));
@@ -473,13 +1101,14 @@ fn fetch_status() -> Result<Vec<JailStatus>> {
 
 fn draw_ui(frame: &mut ratatui::Frame, state: &mut UiState) {
     let size = frame.area();
+    let (footer, footer_height) = render_footer(state, size.width);
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3),
             Constraint::Min(8),
             Constraint::Length(4),
-            Constraint::Length(3),
+            Constraint::Length(footer_height),
         ])
         .split(size);
 
@@ -492,14 +1121,12 @@ fn draw_ui(frame: &mut ratatui::Frame, state: &mut UiState) {
         .split(layout[1]);
 
     state.jail_rect = Some(body_chunks[0]);
-    state.ip_rect = Some(body_chunks[1]);
     render_jails(frame, body_chunks[0], state);
     render_ips(frame, body_chunks[1], state);
 
     let details = render_details(state);
     frame.render_widget(details, layout[2]);
 
-    let footer = render_footer(state, layout[3].width);
     frame.render_widget(footer, layout[3]);
 
     if let Some(modal) = state.modal.clone() {
@@ -511,13 +1138,15 @@ fn draw_ui(frame: &mut ratatui::Frame, state: &mut UiState) {
 }
 
 fn render_header(state: &UiState) -> Paragraph<'_> {
-    let accent = Style::default().fg(Color::Rgb(255, 184, 108)).add_modifier(Modifier::BOLD);
-    let calm = Style::default().fg(Color::Rgb(120, 200, 210));
+    let accent = state.theme.accent;
+    let calm = state.theme.info;
     let text = vec![
         Line::from(vec![
             Span::styled("Fail2Ban Sentinel", accent),
             Span::raw("  "),
             Span::styled("live jail scanner & remover", calm),
+            Span::raw("  "),
+            Span::styled(if state.refreshing() { "refreshing…" } else { "" }, accent),
         ]),
         Line::from(vec![
             Span::styled(
@@ -526,7 +1155,23 @@ fn render_header(state: &UiState) -> Paragraph<'_> {
                     state.jails.len(),
                     total_banned(state)
                 ),
-                Style::default().fg(Color::Rgb(190, 190, 190)),
+                state.theme.text,
+            ),
+            Span::raw(if state.last_diff.is_some() { "  |  " } else { "" }),
+            Span::styled(
+                state
+                    .last_diff
+                    .map(|(added, _)| format!("+{added}"))
+                    .unwrap_or_default(),
+                state.theme.fresh,
+            ),
+            Span::raw(if state.last_diff.is_some() { " / " } else { "" }),
+            Span::styled(
+                state
+                    .last_diff
+                    .map(|(_, removed)| format!("-{removed} since last refresh"))
+                    .unwrap_or_default(),
+                state.theme.info,
             ),
         ]),
     ];
@@ -536,7 +1181,7 @@ fn render_header(state: &UiState) -> Paragraph<'_> {
         .block(
             Block::default()
                 .borders(Borders::BOTTOM)
-                .border_style(Style::default().fg(Color::Rgb(80, 80, 80))),
+                .border_style(state.theme.border),
         )
 }
 
@@ -558,149 +1203,342 @@ fn render_jails(frame: &mut ratatui::Frame, area: Rect, state: &mut UiState) {
     let block = Block::default()
         .title("Jails")
         .borders(Borders::ALL)
-        .border_style(border_style(state.focus == Focus::Jails));
+        .border_style(border_style(&state.theme, state.focus == Focus::Jails));
 
     let list = List::new(items)
         .block(block)
-        .highlight_style(
-            Style::default()
-                .bg(Color::Rgb(255, 184, 108))
-                .fg(Color::Rgb(20, 20, 20))
-                .add_modifier(Modifier::BOLD),
-        )
+        .highlight_style(state.theme.jail_selected)
         .highlight_symbol("> ");
 
     frame.render_stateful_widget(list, area, &mut state.jail_state);
 }
 
 fn render_ips(frame: &mut ratatui::Frame, area: Rect, state: &mut UiState) {
-    let mut items: Vec<ListItem> = Vec::new();
-    if let Some(jail_index) = state.jail_state.selected() {
-        let view_len = {
-            let jail = state.jails.get(jail_index);
-            jail.map(|j| current_ip_view(state, j).len()).unwrap_or(0)
-        };
-        if view_len == 0 {
-            state.ip_state.select(None);
-        } else if state.ip_state.selected().unwrap_or(0) >= view_len {
-            state.ip_state.select(Some(view_len.saturating_sub(1)));
-        }
-        let view = {
-            let jail = state.jails.get(jail_index);
-            jail.map(|j| current_ip_view(state, j)).unwrap_or_default()
-        };
-        if view.is_empty() {
-            items.push(ListItem::new("No banned IPs"));
-        } else {
-            let inner_width = area.width.saturating_sub(4) as usize;
-            for entry in view {
-                let remaining = format_remaining(entry.end_epoch, entry.time_raw.as_deref());
-                let label = format_ip_line(&entry.ip, &remaining, inner_width);
-                items.push(ListItem::new(label));
-            }
-        }
+    let released: Vec<&ReleasedIp> = match state.selected_jail() {
+        _ if state.view_all => state.released.iter().collect(),
+        Some(jail) => state.released.iter().filter(|r| r.jail == jail.name).collect(),
+        None => Vec::new(),
+    };
+    let (list_area, released_area) = if released.is_empty() || area.height < 12 {
+        (area, None)
     } else {
-        items.push(ListItem::new("Select a jail"));
+        let height = released.len().min(5) as u16 + 2;
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(5), Constraint::Length(height)])
+            .split(area);
+        (chunks[0], Some(chunks[1]))
+    };
+
+    if let Some(released_area) = released_area {
+        let now = Utc::now().timestamp();
+        let inner_width = released_area.width.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = released
+            .iter()
+            .map(|r| {
+                let mut ago = format!("{} ago", format_duration_compact((now - r.at).max(0) as u64));
+                if state.view_all {
+                    ago = format!("{}  {ago:>8}", r.jail);
+                }
+                ListItem::new(format_ip_line(&r.ip, &ago, inner_width))
+            })
+            .collect();
+        let block = Block::default()
+            .title("Recently released")
+            .borders(Borders::ALL)
+            .border_style(state.theme.border);
+        frame.render_widget(
+            List::new(items)
+                .block(block)
+                .style(state.theme.muted),
+            released_area,
+        );
     }
 
-    let title = if let Some(jail) = state.selected_jail() {
+    let mut title = if state.view_all {
+        "Banned IPs - all jails".to_string()
+    } else if let Some(jail) = state.selected_jail() {
         format!("Banned IPs - {}", jail.name)
     } else {
         "Banned IPs".to_string()
     };
+    if state.subnets {
+        title = format!("{title} - by /{} and /{}", state.group_prefix.0, state.group_prefix.1);
+    }
 
     let block = Block::default()
         .title(title)
         .borders(Borders::ALL)
-        .border_style(border_style(state.focus == Focus::Ips));
+        .border_style(border_style(&state.theme, state.focus == Focus::Ips));
 
-    let list = List::new(items)
-        .block(block)
-        .highlight_style(
-            Style::default()
-                .bg(Color::Rgb(88, 196, 220))
-                .fg(Color::Rgb(10, 10, 10))
-                .add_modifier(Modifier::BOLD),
-        )
-        .highlight_symbol("> ");
+    state.ip_rect = Some(list_area);
+    state.ip_columns.clear();
 
-    frame.render_stateful_widget(list, area, &mut state.ip_state);
-}
-
-fn render_footer(state: &UiState, width: u16) -> Paragraph<'_> {
-    let help = [
-        ("q", " quit  "),
-        ("r", " refresh  "),
-        ("/", " filter  "),
-        ("x", " clear  "),
-        ("s", " sort  "),
-        ("b", " ban  "),
-        ("tab", " switch panel  "),
-        ("enter", " unban  "),
-        ("A", " unban all  "),
-        ("t", " auto"),
-    ];
-    let mut spans: Vec<Span<'_>> = Vec::new();
-    for (key, label) in help {
-        spans.push(Span::styled(
-            key,
-            Style::default()
-                .fg(Color::Rgb(255, 184, 108))
-                .add_modifier(Modifier::BOLD),
-        ));
-        spans.push(Span::raw(label));
+    if !state.view_all && state.jail_state.selected().is_none() {
+        frame.render_widget(Paragraph::new("Select a jail").block(block), list_area);
+        return;
     }
-    let help_line_base = Line::from(spans);
-    let version = format!("v{}", env!("CARGO_PKG_VERSION"));
-    let inner_width = width.saturating_sub(2) as usize;
-    let help_len = help_line_base.width();
-    let mut help_line = help_line_base.clone();
-    let mut version_in_help = false;
-    if inner_width > help_len + version.len() + 1 {
-        let spaces = inner_width - help_len - version.len();
-        help_line = help_line_base
-            .spans
-            .into_iter()
-            .chain(std::iter::once(Span::raw(" ".repeat(spaces))))
-            .chain(std::iter::once(Span::styled(
-                version.clone(),
-                Style::default().fg(Color::Rgb(160, 160, 160)),
-            )))
-            .collect();
-        version_in_help = true;
+    let view_len = state.ip_rows();
+    if view_len == 0 {
+        state.ip_state.select(None);
+        frame.render_widget(Paragraph::new("No banned IPs").block(block), list_area);
+        return;
+    } else if state.ip_state.selected().unwrap_or(0) >= view_len {
+        state.ip_state.select(Some(view_len.saturating_sub(1)));
     }
 
-    let mut status_line = state.status.clone();
-    if !state.search_query.is_empty() || state.search_mode {
-        let filter = if state.search_mode {
-            format!("Filter: {}_", state.search_query)
-        } else {
-            format!("Filter: {}", state.search_query)
-        };
-        status_line = format!("{status_line}  |  {filter}");
+    if state.subnets {
+        render_subnets(frame, list_area, block, state);
+        return;
+    }
+
+    let view = current_ip_view(state, &state.jails);
+    let inner = block.inner(list_area);
+    let columns = ip_columns(state, &view, inner.width.saturating_sub(HIGHLIGHT.len() as u16));
+    let widths: Vec<Constraint> = columns.iter().map(|(_, width)| *width).collect();
+
+    let header = Row::new(columns.iter().map(|(column, _)| {
+        let mut title = column.title().to_string();
+        if *column == state.sort_column {
+            title.push_str(if state.sort_desc { " ▼" } else { " ▲" });
+        }
+        Cell::from(title)
+    }))
+    .style(state.theme.accent);
+
+    let rows: Vec<Row> = view
+        .iter()
+        .map(|row| {
+            let fresh = state.is_fresh(row.jail, &row.entry.ip);
+            let marked = (!state.marked.is_empty())
+                .then(|| state.marked.contains(&(row.jail.to_string(), row.entry.ip.clone())));
+            let cells = columns.iter().map(|(column, _)| ip_cell(*column, row, fresh, marked));
+            let row_widget = Row::new(cells);
+            if marked == Some(true) {
+                row_widget.style(state.theme.marked)
+            } else if fresh {
+                row_widget.style(state.theme.fresh)
+            } else {
+                row_widget
+            }
+        })
+        .collect();
+
+    let table = Table::new(rows, widths.clone())
+        .header(header)
+        .block(block)
+        .column_spacing(COLUMN_SPACING)
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_style(state.theme.ip_selected)
+        .highlight_symbol(HIGHLIGHT);
+
+    let [_, columns_area] = Layout::horizontal([
+        Constraint::Length(HIGHLIGHT.len() as u16),
+        Constraint::Fill(0),
+    ])
+    .areas(Rect { height: 1, ..inner });
+    let rects = Layout::horizontal(widths)
+        .flex(Flex::Start)
+        .spacing(COLUMN_SPACING)
+        .split(columns_area);
+    state.ip_columns = columns.iter().map(|(column, _)| *column).zip(rects.iter().copied()).collect();
+
+    frame.render_stateful_widget(table, list_area, &mut state.ip_state);
+}
+
+/// The subnet view: one row per CIDR block with its IP count and jails.
+fn render_subnets(frame: &mut ratatui::Frame, area: Rect, block: Block, state: &mut UiState) {
+    let view = current_ip_view(state, &state.jails);
+    let groups = subnet_groups(state, &view);
+    let width = groups.iter().map(|group| group.cidr.to_string().len()).max().unwrap_or(0).max(6);
+    let header = Row::new(["Subnet", "IPs", "Jails"]).style(state.theme.accent);
+    let rows: Vec<Row> = groups
+        .iter()
+        .map(|group| {
+            let jails: BTreeSet<&str> = group.rows.iter().map(|row| row.jail).collect();
+            let jails: Vec<&str> = jails.into_iter().collect();
+            let row = Row::new([
+                group.cidr.to_string(),
+                group.rows.len().to_string(),
+                jails.join(", "),
+            ]);
+            let all_marked = group
+                .rows
+                .iter()
+                .all(|row| state.marked.contains(&(row.jail.to_string(), row.entry.ip.clone())));
+            if all_marked { row.style(state.theme.marked) } else { row }
+        })
+        .collect();
+    let widths = [Constraint::Length(width as u16), Constraint::Length(5), Constraint::Min(5)];
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .column_spacing(COLUMN_SPACING)
+        .highlight_spacing(HighlightSpacing::Always)
+        .highlight_style(state.theme.ip_selected)
+        .highlight_symbol(HIGHLIGHT);
+    frame.render_stateful_widget(table, area, &mut state.ip_state);
+}
+
+const HIGHLIGHT: &str = "> ";
+const COLUMN_SPACING: u16 = 2;
+const STAMP_WIDTH: u16 = 16;
+
+/// Picks the IP table columns that fit in `width`, dropping the least useful
+/// ones from the right. The IP column is sized to the longest address so IPv6
+/// entries are never cut.
+fn ip_columns(state: &UiState, view: &[IpRow], width: u16) -> Vec<(Column, Constraint)> {
+    let mark_width = if state.marked.is_empty() { 0 } else { 2 };
+    let ip_width = view.iter().map(|row| row.entry.ip.len()).max().unwrap_or(0).max(2) as u16 + mark_width;
+    let jail_width = view.iter().map(|row| row.jail.len()).max().unwrap_or(0).max(4) as u16;
+    let mut columns = vec![(Column::Ip, ip_width)];
+    if state.view_all {
+        columns.push((Column::Jail, jail_width));
     }
-    let sort_label = match state.sort_mode {
-        SortMode::Ip => "IP",
-        SortMode::TimeLeft => "Time Left",
+    columns.extend([
+        (Column::Left, 13),
+        (Column::Expires, STAMP_WIDTH),
+        (Column::Started, STAMP_WIDTH),
+        (Column::Raw, 13),
+    ]);
+    let needed = |columns: &[(Column, u16)]| {
+        columns.iter().map(|(_, w)| *w).sum::<u16>()
+            + COLUMN_SPACING * columns.len().saturating_sub(1) as u16
     };
+    while columns.len() > 2 && needed(&columns) > width {
+        columns.pop();
+    }
+    let last = columns.len() - 1;
+    columns
+        .into_iter()
+        .enumerate()
+        .map(|(i, (column, w))| {
+            let constraint = if i == last && column == Column::Raw {
+                Constraint::Min(w)
+            } else {
+                Constraint::Length(w)
+            };
+            (column, constraint)
+        })
+        .collect()
+}
+
+/// `marked` is `None` while nothing is marked, so the IP column only makes
+/// room for the mark when it is needed.
+fn ip_cell<'a>(column: Column, row: &IpRow<'a>, fresh: bool, marked: Option<bool>) -> Cell<'a> {
+    let entry = row.entry;
+    match column {
+        Column::Ip => match marked {
+            Some(true) => Cell::from(format!("* {}", entry.ip)),
+            Some(false) => Cell::from(format!("  {}", entry.ip)),
+            None => Cell::from(entry.ip.as_str()),
+        },
+        Column::Jail => Cell::from(row.jail),
+        Column::Left => {
+            let remaining = format_remaining(entry.end_epoch, entry.time_raw.as_deref());
+            let remaining = remaining.trim_start_matches('(').trim_end_matches(')');
+            Cell::from(if fresh {
+                format!("new {remaining}")
+            } else {
+                remaining.to_string()
+            })
+        }
+        Column::Expires => Cell::from(format_stamp_local(entry.end_epoch)),
+        Column::Started => Cell::from(format_stamp_local(entry.start_epoch)),
+        Column::Raw => Cell::from(entry.time_raw.as_deref().unwrap_or("--")),
+    }
+}
+
+fn format_stamp_local(epoch: Option<i64>) -> String {
+    epoch
+        .and_then(|epoch| DateTime::<Utc>::from_timestamp(epoch, 0))
+        .map(|dt| dt.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "--".to_string())
+}
+
+/// Returns the footer and the height it needs, so the help line can wrap on
+/// narrow terminals without pushing the status line out of view.
+fn render_footer(state: &UiState, width: u16) -> (Paragraph<'static>, u16) {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    for action in Action::ALL {
+        let (Some(label), Some(key)) = (action.footer_label(), state.keymap.keys_for(action).first().cloned())
+        else {
+            continue;
+        };
+        spans.push(Span::styled(
+            key,
+            state.theme.accent,
+        ));
+        spans.push(Span::raw(format!(" {label}  ")));
+    }
+    let help_line_base = Line::from(spans);
+    let version = format!("v{}", env!("CARGO_PKG_VERSION"));
+    let inner_width = width.saturating_sub(2) as usize;
+    let help_len = help_line_base.width();
+    let mut help_line = help_line_base.clone();
+    let mut version_in_help = false;
+    if inner_width > help_len + version.len() + 1 {
+        let spaces = inner_width - help_len - version.len();
+        help_line = help_line_base
+            .spans
+            .into_iter()
+            .chain(std::iter::once(Span::raw(" ".repeat(spaces))))
+            .chain(std::iter::once(Span::styled(
+                version.clone(),
+                state.theme.muted,
+            )))
+            .collect();
+        version_in_help = true;
+    }
+
+    let mut status_line = state.status.clone();
+    if !state.search_query.is_empty() || state.search_mode {
+        let filter = if state.search_mode {
+            format!("Filter: {}_", state.search_query)
+        } else {
+            format!("Filter: {}", state.search_query)
+        };
+        status_line = format!("{status_line}  |  {filter}");
+    }
+    let filter_error = state
+        .filter_error
+        .as_ref()
+        .map(|err| format!("  ({err})"))
+        .unwrap_or_default();
+    let sort_label = format!(
+        "{} {}",
+        state.sort_column.title(),
+        if state.sort_desc { "desc" } else { "asc" }
+    );
     let auto_label = if state.autorefresh { "Auto: on" } else { "Auto: off" };
-    status_line = format!("{status_line}  |  Sort: {sort_label}  |  {auto_label}");
+    let mut tail = format!("  |  Sort: {sort_label}  |  {auto_label}");
+    if !state.marked.is_empty() {
+        tail = format!("  |  Marked: {}{tail}", state.marked.len());
+    }
     if !version_in_help {
-        status_line = format!("{status_line}  |  {version}");
+        tail = format!("{tail}  |  {version}");
     }
 
-    let status = Line::from(Span::styled(
-        status_line,
-        Style::default().fg(Color::Rgb(180, 180, 180)),
-    ));
+    let wrapped = |len: usize| len.div_ceil(inner_width.max(1)).max(1) as u16;
+    let height = 1
+        + wrapped(help_line.width())
+        + wrapped(status_line.len() + filter_error.len() + tail.len());
 
-    Paragraph::new(Text::from(vec![help_line, status]))
+    let muted = state.theme.muted;
+    let status = Line::from(vec![
+        Span::styled(status_line, muted),
+        Span::styled(filter_error, state.theme.error),
+        Span::styled(tail, muted),
+    ]);
+
+    let footer = Paragraph::new(Text::from(vec![help_line, status]))
         .block(
             Block::default()
                 .borders(Borders::TOP)
-                .border_style(Style::default().fg(Color::Rgb(80, 80, 80))),
+                .border_style(state.theme.border),
         )
-        .wrap(Wrap { trim: true })
+        .wrap(Wrap { trim: true });
+    (footer, height)
 }
 
 fn render_details(state: &UiState) -> Paragraph<'_> {
@@ -747,54 +1585,132 @@ fn render_details(state: &UiState) -> Paragraph<'_> {
             Block::default()
                 .title(title)
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Rgb(80, 80, 80))),
+                .border_style(state.theme.border),
         )
         .wrap(Wrap { trim: true })
 }
 
-fn render_modal(frame: &mut ratatui::Frame, area: Rect, modal: Modal, state: &mut UiState) {
-    let modal_area = centered_rect(60, 30, area);
-    frame.render_widget(Clear, modal_area);
-
-    let block = Block::default()
-        .borders(Borders::ALL)
-        .title("Action")
-        .border_style(Style::default().fg(Color::Rgb(255, 184, 108)));
-
-    frame.render_widget(block, modal_area);
-
-    let inner = Rect {
-        x: modal_area.x + 1,
-        y: modal_area.y + 1,
-        width: modal_area.width.saturating_sub(2),
-        height: modal_area.height.saturating_sub(2),
-    };
+const PALETTE_ROWS: usize = 10;
+/// Rows shown at once in the jail pickers of the ban dialogs and in the
+/// ignore list.
+const PICKER_ROWS: usize = 8;
 
-    let content_area = Rect {
-        x: inner.x,
-        y: inner.y,
-        width: inner.width,
-        height: inner.height.saturating_sub(2),
-    };
+/// A jail picker scrolled, like the palette, so that `selected` stays in view.
+fn picker_lines(names: &[&str], selected: usize, theme: &Theme) -> Vec<Line<'static>> {
+    window_lines(names, selected, true, theme)
+}
 
-    let button_row = Rect {
-        x: inner.x,
-        y: inner.y + inner.height.saturating_sub(1),
-        width: inner.width,
-        height: 1,
-    };
+/// `names` scrolled so that `selected` stays in view, with the rows above
+/// and below counted. The selection is marked only when `highlight` is set.
+fn window_lines(names: &[&str], selected: usize, highlight: bool, theme: &Theme) -> Vec<Line<'static>> {
+    let first = selected.saturating_sub(PICKER_ROWS - 1);
+    let below = names.len().saturating_sub(first + PICKER_ROWS);
+    let mut lines = Vec::new();
+    if first > 0 {
+        lines.push(Line::from(Span::styled(format!("{first} more above"), theme.muted)));
+    }
+    for (i, name) in names.iter().enumerate().skip(first).take(PICKER_ROWS) {
+        lines.push(if i == selected && highlight {
+            Line::from(Span::styled(format!("> {name}"), theme.ip_selected))
+        } else {
+            Line::from(Span::raw(name.to_string()))
+        });
+    }
+    if below > 0 {
+        lines.push(Line::from(Span::styled(format!("{below} more below"), theme.muted)));
+    }
+    lines
+}
 
+fn render_modal(frame: &mut ratatui::Frame, area: Rect, modal: Modal, state: &mut UiState) {
     let lines = match modal {
         Modal::UnbanIp { ref jail, ref ip } => vec![
             Line::from(Span::styled(
                 "Confirm Unban",
-                Style::default().fg(Color::Rgb(255, 184, 108)).add_modifier(Modifier::BOLD),
+                state.theme.accent,
             )),
             Line::from(Span::raw("")),
             Line::from(Span::raw(format!("Unban {ip} from {jail}?"))),
             Line::from(Span::raw("")),
             Line::from(Span::raw("Press y/n or click a button")),
         ],
+        Modal::UnbanIgnore { ref jail, ref ip } => vec![
+            Line::from(Span::styled(
+                "Confirm Unban and Ignore",
+                state.theme.accent,
+            )),
+            Line::from(Span::raw("")),
+            Line::from(Span::raw(format!("Unban {ip} from {jail} and add it to the jail's ignore list?"))),
+            Line::from(Span::raw(format!("{jail} won't ban it again until fail2ban restarts."))),
+            Line::from(Span::raw("")),
+            Line::from(Span::raw("Press y/n or click a button")),
+        ],
+        Modal::IgnoreList {
+            ref jail,
+            ref entries,
+            selected,
+            ref input,
+            removing,
+            ref error,
+        } => {
+            let mut lines = vec![
+                Line::from(Span::styled(format!("Ignore List - {jail}"), state.theme.accent)),
+                Line::from(Span::raw("")),
+            ];
+            if entries.is_empty() {
+                lines.push(Line::from(Span::styled("Nothing is ignored", state.theme.muted)));
+            }
+            let names: Vec<&str> = entries.iter().map(String::as_str).collect();
+            lines.extend(window_lines(&names, selected, input.is_none(), &state.theme));
+            lines.push(Line::from(Span::raw("")));
+            if let Some(input) = input {
+                lines.push(Line::from(Span::raw(format!("Add: {input}_"))));
+            }
+            if let Some(entry) = entries.get(selected).filter(|_| removing) {
+                lines.push(Line::from(Span::styled(
+                    format!("Remove {entry} from the ignore list of {jail}?"),
+                    state.theme.error,
+                )));
+            }
+            if let Some(err) = error {
+                lines.push(Line::from(Span::styled(err.clone(), state.theme.error)));
+            }
+            lines.push(Line::from(Span::styled(
+                "Lost when fail2ban restarts; keep lasting entries in jail.local",
+                state.theme.muted,
+            )));
+            lines.push(Line::from(Span::raw(if input.is_some() {
+                "IP, CIDR block or host name; enter to add, esc to cancel".to_string()
+            } else if removing {
+                format!(
+                    "{} to remove, {} to keep it",
+                    state.keymap.key_hint(Action::Confirm),
+                    state.keymap.key_hint(Action::Cancel)
+                )
+            } else {
+                format!(
+                    "{} to add, {} to remove, {} to reload, esc to close",
+                    state.keymap.key_hint(Action::IgnoreAdd),
+                    state.keymap.key_hint(Action::IgnoreRemove),
+                    state.keymap.key_hint(Action::IgnoreReload)
+                )
+            })));
+            lines
+        }
+        Modal::UnbanEverywhere { ref ip, ref jails } => vec![
+            Line::from(Span::styled(
+                "Confirm Unban Everywhere",
+                state.theme.accent,
+            )),
+            Line::from(Span::raw("")),
+            Line::from(Span::raw(format!(
+                "Unban {ip} from {} jail(s): {}?",
+                jails.len(),
+                jails.join(", ")
+            ))),
+            Line::from(Span::raw("")),
+            Line::from(Span::raw("Press y/n or click a button")),
+        ],
         Modal::UnbanAll { ref jail, step } => {
             let headline = if step == 1 {
                 "Confirm Unban All"
@@ -809,7 +1725,7 @@ fn render_modal(frame: &mut ratatui::Frame, area: Rect, modal: Modal, state: &mu
             vec![
                 Line::from(Span::styled(
                     headline,
-                    Style::default().fg(Color::Rgb(255, 184, 108)).add_modifier(Modifier::BOLD),
+                    state.theme.accent,
                 )),
                 Line::from(Span::raw("")),
                 Line::from(Span::raw(warning)),
@@ -820,125 +1736,741 @@ fn render_modal(frame: &mut ratatui::Frame, area: Rect, modal: Modal, state: &mu
         Modal::BanIp {
             ref jail,
             ref input,
+            ref duration,
+            on_duration,
             ref error,
         } => {
+            let field = |label: &str, value: &str, active: bool| {
+                let cursor = if active { "_" } else { "" };
+                let style = if active { state.theme.text } else { state.theme.muted };
+                Line::from(Span::styled(format!("{label}{value}{cursor}"), style))
+            };
+            let default = state
+                .jails
+                .iter()
+                .find(|status| status.name == *jail)
+                .map(|status| format_time_value(&status.bantime))
+                .unwrap_or_else(|| "--".to_string());
+            let ip = input.trim();
+            let bantime = parse_ban_time(duration);
+            let unusable = bantime
+                .clone()
+                .ok()
+                .and_then(|bantime| state.ban_time_problem(jail, bantime));
+            let problem = match &bantime {
+                _ if !ip.is_empty() && !is_ban_target(ip) => Some("Invalid IP address or CIDR block".to_string()),
+                Err(err) => Some(err.clone()),
+                Ok(_) => unusable.as_ref().map(|why| format!("{why}; {KEEP_JAIL_BANTIME}")),
+            };
+            let until = match bantime {
+                _ if unusable.is_some() => String::new(),
+                Ok(None) => format!("Jail bantime: {default}"),
+                Ok(Some(-1)) => "Never expires".to_string(),
+                Ok(Some(seconds)) => format!(
+                    "Until {}",
+                    format_stamp_local(Some(Utc::now().timestamp().saturating_add(seconds)))
+                ),
+                Err(_) => String::new(),
+            };
             let mut lines = vec![
                 Line::from(Span::styled(
                     "Ban IP",
-                    Style::default().fg(Color::Rgb(255, 184, 108)).add_modifier(Modifier::BOLD),
+                    state.theme.accent,
                 )),
                 Line::from(Span::raw("")),
                 Line::from(Span::raw(format!("Jail: {jail}"))),
                 Line::from(Span::raw("")),
-                Line::from(Span::raw(format!("IP: {input}_"))),
+                field("IP:       ", input, !on_duration),
+                field("Duration: ", duration, on_duration),
+                Line::from(Span::styled(until, state.theme.muted)),
             ];
-            if let Some(err) = error {
-                lines.push(Line::from(Span::raw("")));
+            if let Some(entry) = state.ignored(jail, ip) {
                 lines.push(Line::from(Span::styled(
-                    err.clone(),
-                    Style::default().fg(Color::Rgb(240, 120, 120)),
+                    format!("{ip} is on the ignore list of {jail} ({entry})"),
+                    state.theme.error,
+                )));
+            }
+            lines.push(Line::from(Span::raw("")));
+            if let Some(err) = error.clone().or(problem) {
+                lines.push(Line::from(Span::styled(
+                    err,
+                    state.theme.error,
                 )));
             } else {
-                lines.push(Line::from(Span::raw("")));
-                lines.push(Line::from(Span::raw("Type IP, press enter to ban, esc to cancel")));
+                lines.push(Line::from(Span::raw(
+                    "Duration: empty for the jail's bantime, 1h30m, 7d or permanent",
+                )));
             }
+            lines.push(Line::from(Span::raw("tab switches field, enter to ban, esc to cancel")));
             lines
         }
-    };
-
-    let paragraph = Paragraph::new(Text::from(lines))
-        .alignment(Alignment::Center)
-        .wrap(Wrap { trim: true });
-    frame.render_widget(paragraph, content_area);
-
-    let button_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(button_row);
-
-    let yes_style = Style::default()
-        .bg(Color::Rgb(255, 184, 108))
-        .fg(Color::Rgb(20, 20, 20))
-        .add_modifier(Modifier::BOLD);
-    let no_style = Style::default()
-        .bg(Color::Rgb(88, 196, 220))
-        .fg(Color::Rgb(10, 10, 10))
-        .add_modifier(Modifier::BOLD);
-
-    let yes = Paragraph::new("Confirm").style(yes_style).alignment(Alignment::Center);
-    let no = Paragraph::new("Cancel").style(no_style).alignment(Alignment::Center);
-
-    frame.render_widget(yes, button_chunks[0]);
-    frame.render_widget(no, button_chunks[1]);
-
-    state.modal_yes_rect = Some(button_chunks[0]);
-    state.modal_no_rect = Some(button_chunks[1]);
-}
-
-fn border_style(active: bool) -> Style {
-    if active {
-        Style::default().fg(Color::Rgb(255, 184, 108))
-    } else {
-        Style::default().fg(Color::Rgb(80, 80, 80))
-    }
-}
-
-fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
-        ])
-        .split(area);
-
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(popup_layout[1])[1]
-}
-
-fn total_banned(state: &UiState) -> usize {
-    state.jails.iter().map(|j| j.ips.len()).sum()
-}
-
-fn format_time_value(value: &TimeValue) -> String {
-    if let Some(seconds) = value.seconds {
-        if value.raw.parse::<u64>().is_ok() {
-            return format!("{}s ({})", value.raw, format_duration(seconds));
+        Modal::BanTime {
+            ref jail,
+            ref ip,
+            end_epoch,
+            ref input,
+            ref error,
+        } => {
+            let now = Utc::now().timestamp();
+            let left = format_remaining(end_epoch, None);
+            let left = left.trim_start_matches('(').trim_end_matches(')');
+            let end = new_ban_end(input, end_epoch, now);
+            let unusable = end
+                .as_ref()
+                .ok()
+                .and_then(|end| state.ban_time_problem(jail, Some(end_bantime(*end, now))));
+            let preview = match (end, unusable) {
+                (Ok(_), Some(why)) => Err(why),
+                (Ok(end), None) if end >= PERMANENT_END_EPOCH => Ok("New: never expires".to_string()),
+                (Ok(end), None) => Ok(format!(
+                    "New: {} left, until {}",
+                    format_duration_compact((end - now) as u64),
+                    format_stamp_local(Some(end))
+                )),
+                (Err(_), _) if input.trim().is_empty() => Ok(String::new()),
+                (Err(err), _) => Err(err),
+            };
+            let mut lines = vec![
+                Line::from(Span::styled(
+                    "Change ban time",
+                    state.theme.accent,
+                )),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("{ip} in {jail}"))),
+                Line::from(Span::raw(format!(
+                    "Now: {left} left, until {}",
+                    format_stamp_local(end_epoch)
+                ))),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("Time: {input}_"))),
+            ];
+            match error.clone().map_or(preview, Err) {
+                Ok(preview) => lines.push(Line::from(Span::styled(preview, state.theme.muted))),
+                Err(err) => lines.push(Line::from(Span::styled(err, state.theme.error))),
+            }
+            lines.push(Line::from(Span::raw("")));
+            lines.push(Line::from(Span::raw(
+                "+1h extends, -30m shortens, 2h sets the time left, permanent",
+            )));
+            lines.push(Line::from(Span::raw("enter to apply, esc to cancel")));
+            lines
         }
-        return format!("{} ({})", value.raw, format_duration(seconds));
-    }
-    value.raw.clone()
-}
-
-fn format_duration(mut seconds: u64) -> String {
-    if seconds == 0 {
-        return "0s".to_string();
-    }
-    let days = seconds / 86_400;
-    seconds %= 86_400;
-    let hours = seconds / 3600;
-    seconds %= 3600;
-    let minutes = seconds / 60;
-    let secs = seconds % 60;
-
-    let mut out = String::new();
-    if days > 0 {
-        out.push_str(&format!("{days}d"));
-    }
-    if hours > 0 {
-        if !out.is_empty() {
-            out.push(' ');
+        Modal::Help { .. } => help_lines(&state.keymap, &state.theme),
+        Modal::Audit {
+            ref records,
+            ref error,
+            ..
+        } => audit_lines(records, error.as_deref(), state.audit_path.as_deref(), &state.theme),
+        Modal::UnbanMarked { ref targets } => {
+            let jails: BTreeSet<&str> = targets.iter().map(|(jail, _)| jail.as_str()).collect();
+            vec![
+                Line::from(Span::styled("Confirm Unban Marked", state.theme.accent)),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!(
+                    "Unban {} marked IP(s) from {} jail(s)?",
+                    targets.len(),
+                    jails.len()
+                ))),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw("Press y/n or click a button")),
+            ]
         }
-        out.push_str(&format!("{hours}h"));
-    }
-    if minutes > 0 {
+        Modal::Undo { ref label, count } => {
+            let earlier = match state.journal.len().saturating_sub(1) {
+                0 => String::new(),
+                n => format!(" {n} earlier unban(s) can be undone after this one."),
+            };
+            vec![
+                Line::from(Span::styled("Undo Unban", state.theme.accent)),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("Undo the {label} and ban {count} IP(s) again?"))),
+                Line::from(Span::raw(format!("{}{earlier}", undo_time_note(state)))),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw("Press y/n or click a button")),
+            ]
+        }
+        Modal::BanMarked { ref ips, selected } => {
+            let mut lines = vec![
+                Line::from(Span::styled("Ban Marked IPs", state.theme.accent)),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("Ban {} marked IP(s) in:", ips.len()))),
+                Line::from(Span::raw("")),
+            ];
+            let names: Vec<&str> = state.jails.iter().map(|j| j.name.as_str()).collect();
+            lines.extend(picker_lines(&names, selected, &state.theme));
+            if let Some(jail) = state.jails.get(selected) {
+                let ignored: Vec<&str> = ips
+                    .iter()
+                    .filter(|ip| state.ignored(&jail.name, ip).is_some())
+                    .map(String::as_str)
+                    .collect();
+                if !ignored.is_empty() {
+                    let shown: Vec<&str> = ignored.iter().take(5).copied().collect();
+                    lines.push(Line::from(Span::styled(
+                        format!("On the ignore list of {}: {}", jail.name, shown.join(", ")),
+                        state.theme.error,
+                    )));
+                }
+            }
+            lines.push(Line::from(Span::raw("")));
+            lines.push(Line::from(Span::raw("up/down to pick a jail, enter to ban, esc to cancel")));
+            lines
+        }
+        Modal::BanList {
+            ref jail,
+            ref text,
+            review,
+        } => {
+            let banned = state.jails.iter().find(|j| j.name == *jail).map(|j| j.ips.as_slice());
+            let plan = plan_ban_list(text, banned.unwrap_or_default());
+            let counts = format!(
+                "{} to ban, {} already banned, {} not an IP or CIDR",
+                plan.new.len(),
+                plan.already.len(),
+                plan.invalid.len()
+            );
+            let mut lines = vec![
+                Line::from(Span::styled(format!("Ban a List of IPs in {jail}"), state.theme.accent)),
+                Line::from(Span::raw("")),
+            ];
+            if review {
+                lines.push(Line::from(Span::raw(format!("Ban {} IP(s) and CIDR block(s):", plan.new.len()))));
+                for ip in plan.new.iter().take(BAN_LIST_ROWS) {
+                    lines.push(Line::from(Span::raw(ip.clone())));
+                }
+                if plan.new.len() > BAN_LIST_ROWS {
+                    lines.push(Line::from(Span::styled(
+                        format!("and {} more", plan.new.len() - BAN_LIST_ROWS),
+                        state.theme.muted,
+                    )));
+                }
+                lines.push(Line::from(Span::raw("")));
+                lines.push(Line::from(Span::styled(counts, state.theme.muted)));
+                let ignored: Vec<&str> = plan
+                    .new
+                    .iter()
+                    .filter(|ip| state.ignored(jail, ip).is_some())
+                    .map(String::as_str)
+                    .collect();
+                if !ignored.is_empty() {
+                    let shown: Vec<&str> = ignored.iter().take(5).copied().collect();
+                    lines.push(Line::from(Span::styled(
+                        format!("On the ignore list of {jail}: {}", shown.join(", ")),
+                        state.theme.error,
+                    )));
+                }
+                if !plan.invalid.is_empty() {
+                    let shown: Vec<&str> = plan.invalid.iter().take(5).map(String::as_str).collect();
+                    lines.push(Line::from(Span::styled(
+                        format!("Skipped: {}", shown.join(", ")),
+                        state.theme.error,
+                    )));
+                }
+                lines.push(Line::from(Span::raw("")));
+                lines.push(Line::from(Span::raw("enter to ban, tab to edit the list, esc to cancel")));
+            } else {
+                lines.push(Line::from(Span::raw(
+                    "IPs and CIDR blocks, one per line or separated by spaces or commas:",
+                )));
+                let rows: Vec<&str> = text.split('\n').collect();
+                let hidden = rows.len().saturating_sub(BAN_LIST_ROWS);
+                if hidden > 0 {
+                    lines.push(Line::from(Span::styled(
+                        format!("{hidden} more line(s) above"),
+                        state.theme.muted,
+                    )));
+                }
+                let last = rows.len() - 1;
+                for (i, row) in rows.iter().enumerate().skip(hidden) {
+                    let cursor = if i == last { "_" } else { "" };
+                    lines.push(Line::from(Span::styled(format!("{row}{cursor}"), state.theme.text)));
+                }
+                lines.push(Line::from(Span::raw("")));
+                lines.push(Line::from(Span::styled(counts, state.theme.muted)));
+                lines.push(Line::from(Span::raw("enter for a new line, tab to review, esc to cancel")));
+            }
+            lines
+        }
+        Modal::BanSubnet { cidr, selected, unban } => {
+            let mut lines = vec![
+                Line::from(Span::styled("Ban Subnet", state.theme.accent)),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("Ban {cidr} in:"))),
+                Line::from(Span::raw("")),
+            ];
+            let names: Vec<&str> = state.jails.iter().map(|j| j.name.as_str()).collect();
+            lines.extend(picker_lines(&names, selected, &state.theme));
+            lines.push(Line::from(Span::raw("")));
+            if let Some(jail) = state.jails.get(selected) {
+                let covered = covered_ips(jail, &cidr).len();
+                let then = if unban { "unban them" } else { "keep them" };
+                lines.push(Line::from(Span::raw(format!(
+                    "{covered} IP(s) in it banned in {}: {then}",
+                    jail.name
+                ))));
+                if let Some(entry) = state.ignored(&jail.name, &cidr.to_string()) {
+                    lines.push(Line::from(Span::styled(
+                        format!("{cidr} overlaps the ignore list of {} ({entry})", jail.name),
+                        state.theme.error,
+                    )));
+                }
+            }
+            lines.push(Line::from(Span::styled(
+                "The jail's ban action has to accept CIDR blocks",
+                state.theme.muted,
+            )));
+            lines.push(Line::from(Span::raw("")));
+            lines.push(Line::from(Span::raw(format!(
+                "{}: unban / keep the single IPs, enter to ban, esc to cancel",
+                state.keymap.key_hint(Action::SubnetUnban)
+            ))));
+            lines
+        }
+        Modal::MoveIp {
+            ref jail,
+            ref ip,
+            ref targets,
+            selected,
+            copy,
+            keep_time,
+        } => {
+            let verb = if copy { "Copy" } else { "Move" };
+            let mut lines = vec![
+                Line::from(Span::styled(format!("{verb} IP"), state.theme.accent)),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("{verb} {ip} from {jail} to:"))),
+                Line::from(Span::raw("")),
+            ];
+            let names: Vec<&str> = targets.iter().map(String::as_str).collect();
+            lines.extend(picker_lines(&names, selected, &state.theme));
+            lines.push(Line::from(Span::raw("")));
+            let bantime = state.undo_ban(jail, ip).bantime;
+            let unusable = targets.get(selected).and_then(|target| state.ban_time_problem(target, bantime));
+            let time = match (bantime, unusable) {
+                _ if !keep_time => "for the target jail's bantime".to_string(),
+                (_, Some(why)) => format!("for the target jail's bantime, not the time it has left ({why})"),
+                (Some(-1), None) => "for good".to_string(),
+                (Some(seconds), None) => {
+                    format!("for the {} it has left", format_duration_compact(seconds as u64))
+                }
+                (None, None) => "for the target jail's bantime".to_string(),
+            };
+            lines.push(Line::from(Span::raw(format!("Banned {time}"))));
+            if let Some(target) = targets.get(selected)
+                && state.jails_holding(ip).contains(target)
+            {
+                lines.push(Line::from(Span::styled(
+                    format!("{ip} is already banned in {target}"),
+                    state.theme.error,
+                )));
+            }
+            if let Some(target) = targets.get(selected)
+                && let Some(entry) = state.ignored(target, ip)
+            {
+                lines.push(Line::from(Span::styled(
+                    format!("{ip} is on the ignore list of {target} ({entry})"),
+                    state.theme.error,
+                )));
+            }
+            lines.push(Line::from(Span::raw("")));
+            lines.push(Line::from(Span::raw(format!(
+                "{}: move / copy, {}: keep the time left / use the jail's bantime",
+                state.keymap.key_hint(Action::MoveCopy),
+                state.keymap.key_hint(Action::MoveKeepTime)
+            ))));
+            lines.push(Line::from(Span::raw("up/down to pick a jail, enter to confirm, esc to cancel")));
+            lines
+        }
+        Modal::Report { ref title, lines: ref report } => {
+            let mut lines = vec![
+                Line::from(Span::styled(title.clone(), state.theme.accent)),
+                Line::from(Span::raw("")),
+            ];
+            lines.extend(report.iter().map(|line| Line::from(Span::styled(line.clone(), state.theme.error))));
+            lines.push(Line::from(Span::raw("")));
+            lines.push(Line::from(Span::raw("Press any key to close")));
+            lines
+        }
+        Modal::Palette { ref input, selected } => {
+            let actions = matching_actions(input);
+            let mut lines = vec![
+                Line::from(Span::styled("Command Palette", state.theme.accent)),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!(": {input}_"))),
+                Line::from(Span::raw("")),
+            ];
+            if actions.is_empty() {
+                lines.push(Line::from(Span::raw("  No matching command")));
+            }
+            let first = selected.saturating_sub(PALETTE_ROWS - 1);
+            for (i, action) in actions.iter().enumerate().skip(first).take(PALETTE_ROWS) {
+                let keys = state.keymap.keys_for(*action).join(", ");
+                let text = format!("{:<36}{keys}", action.description());
+                lines.push(if i == selected {
+                    Line::from(Span::styled(format!("> {text}"), state.theme.ip_selected))
+                } else {
+                    Line::from(Span::raw(format!("  {text}")))
+                });
+            }
+            lines.push(Line::from(Span::raw("")));
+            lines.push(Line::from(Span::raw(
+                "Type to search, up/down to select, enter to run, esc to close",
+            )));
+            lines
+        }
+        Modal::Filters { selected, ref name } => {
+            let mut lines = vec![
+                Line::from(Span::styled(
+                    "Saved Filters",
+                    state.theme.accent,
+                )),
+                Line::from(Span::raw("")),
+            ];
+            if state.saved_filters.is_empty() {
+                lines.push(Line::from(Span::raw("No saved filters")));
+            }
+            for (i, filter) in state.saved_filters.iter().enumerate() {
+                let text = format!("{}  {}", filter.name, filter.query);
+                lines.push(if i == selected && name.is_none() {
+                    Line::from(Span::styled(
+                        format!("> {text}"),
+                        state.theme.ip_selected,
+                    ))
+                } else {
+                    Line::from(Span::raw(text))
+                });
+            }
+            lines.push(Line::from(Span::raw("")));
+            lines.push(Line::from(Span::raw(match name {
+                Some(name) => format!("Save '{}' as: {name}_", state.search_query),
+                None => "enter apply, s save current filter, d delete, esc close".to_string(),
+            })));
+            lines
+        }
+        Modal::Lookup {
+            ref input,
+            ref matches,
+            ref error,
+        } => {
+            let mut lines = vec![
+                Line::from(Span::styled(
+                    "Where Is IP",
+                    state.theme.accent,
+                )),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("IP: {input}_"))),
+                Line::from(Span::raw("")),
+            ];
+            match (error, matches) {
+                (Some(err), _) => lines.push(Line::from(Span::styled(
+                    err.clone(),
+                    state.theme.error,
+                ))),
+                (None, Some(found)) if found.is_empty() => {
+                    lines.push(Line::from(Span::raw("Not banned in any jail")));
+                }
+                (None, Some(found)) => {
+                    for m in found {
+                        let remaining = format_remaining(m.end_epoch, m.time_raw.as_deref());
+                        lines.push(Line::from(Span::raw(format!("{}  {remaining}", m.jail))));
+                    }
+                    lines.push(Line::from(Span::raw("")));
+                    lines.push(Line::from(Span::raw(format!(
+                        "Press enter to unban from all {} jail(s), esc to close",
+                        found.len()
+                    ))));
+                }
+                (None, None) => {
+                    lines.push(Line::from(Span::raw("Type IP, press enter to search, esc to cancel")));
+                }
+            }
+            lines
+        }
+        Modal::Export {
+            ref input,
+            scope,
+            ref error,
+            ..
+        } => {
+            let format = match ExportFormat::from_path(Path::new(input.trim())) {
+                ExportFormat::Json => "JSON",
+                ExportFormat::Ndjson => "NDJSON",
+                ExportFormat::Csv => "CSV",
+            };
+            let count: usize = export_rows(state, scope).iter().map(|(_, ips)| ips.len()).sum();
+            let scope = match (scope, state.selected_jail()) {
+                (ExportScope::Marked, _) => format!("Scope: marked IPs ({count} IPs)"),
+                (ExportScope::Filtered, Some(jail)) if !state.view_all => {
+                    format!("Scope: filtered view of {} ({count} IPs)", jail.name)
+                }
+                (ExportScope::Filtered, _) => format!("Scope: filtered view ({count} IPs)"),
+                (ExportScope::All, _) => format!("Scope: all jails ({count} IPs)"),
+            };
+            let mut lines = vec![
+                Line::from(Span::styled(
+                    "Export Snapshot",
+                    state.theme.accent,
+                )),
+                Line::from(Span::raw("")),
+                Line::from(Span::raw(format!("File: {input}_"))),
+                Line::from(Span::raw(format!("Format: {format}  |  {scope}"))),
+            ];
+            lines.push(Line::from(Span::raw("")));
+            if let Some(err) = error {
+                lines.push(Line::from(Span::styled(
+                    err.clone(),
+                    state.theme.error,
+                )));
+            } else {
+                lines.push(Line::from(Span::raw(
+                    "Extension picks .json/.ndjson/.csv, tab toggles scope, enter to write",
+                )));
+            }
+            lines
+        }
+    };
+
+    let mut modal_area = centered_rect(60, 30, area);
+    let wanted = (lines.len() as u16 + 4).min(area.height);
+    let full_screen = match modal {
+        Modal::Help { scroll } | Modal::Audit { scroll, .. } => Some(scroll),
+        _ => None,
+    };
+    if full_screen.is_some() {
+        modal_area = Rect {
+            x: area.x + 1,
+            y: area.y + 1,
+            width: area.width.saturating_sub(2),
+            height: area.height.saturating_sub(2),
+        };
+    } else if modal_area.height < wanted {
+        modal_area.y = area.y + (area.height - wanted) / 2;
+        modal_area.height = wanted;
+    }
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(match modal {
+            Modal::Help { .. } => "Help",
+            Modal::Audit { .. } => "Audit Log",
+            _ => "Action",
+        })
+        .border_style(state.theme.border_active);
+
+    frame.render_widget(block, modal_area);
+
+    let inner = Rect {
+        x: modal_area.x + 1,
+        y: modal_area.y + 1,
+        width: modal_area.width.saturating_sub(2),
+        height: modal_area.height.saturating_sub(2),
+    };
+
+    let content_area = Rect {
+        x: inner.x,
+        y: inner.y,
+        width: inner.width,
+        height: inner.height.saturating_sub(2),
+    };
+
+    let button_row = Rect {
+        x: inner.x,
+        y: inner.y + inner.height.saturating_sub(1),
+        width: inner.width,
+        height: 1,
+    };
+
+    let paragraph = Paragraph::new(Text::from(lines)).wrap(Wrap { trim: true });
+    if let Modal::Report { .. } = modal {
+        frame.render_widget(paragraph, Rect { height: inner.height, ..content_area });
+        state.modal_yes_rect = None;
+        state.modal_no_rect = None;
+        return;
+    }
+    if let Some(scroll) = full_screen {
+        let inner_area = Rect {
+            height: inner.height,
+            ..content_area
+        };
+        frame.render_widget(paragraph.wrap(Wrap { trim: false }).scroll((scroll, 0)), inner_area);
+        state.modal_yes_rect = None;
+        state.modal_no_rect = None;
+        return;
+    }
+    let paragraph = if let Modal::Palette { .. } | Modal::BanList { review: false, .. } = modal {
+        paragraph.wrap(Wrap { trim: false })
+    } else {
+        paragraph.alignment(Alignment::Center)
+    };
+    frame.render_widget(paragraph, content_area);
+
+    let button_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(button_row);
+
+    let yes_style = state.theme.confirm_button;
+    let no_style = state.theme.cancel_button;
+
+    let yes = Paragraph::new("Confirm").style(yes_style).alignment(Alignment::Center);
+    let no = Paragraph::new("Cancel").style(no_style).alignment(Alignment::Center);
+
+    frame.render_widget(yes, button_chunks[0]);
+    frame.render_widget(no, button_chunks[1]);
+
+    state.modal_yes_rect = Some(button_chunks[0]);
+    state.modal_no_rect = Some(button_chunks[1]);
+}
+
+/// Keys handled directly by prompts and lists rather than through the keymap.
+const FIXED_KEYS: [(&str, &str, &str); 10] = [
+    ("Filter", "enter / esc", "apply / cancel the filter being typed"),
+    ("Filter", "s, d", "save current / delete, in saved filters"),
+    ("Dialogs", "enter", "submit a text prompt"),
+    ("Dialogs", "esc", "close a prompt or list"),
+    ("Dialogs", "tab", "toggle export scope, or IP and duration when banning"),
+    ("Dialogs", "up/down, ctrl-p/n", "move in the command palette"),
+    ("General", "click", "select a jail or IP, or sort by a column header"),
+    ("General", "ctrl-c", "quit from anywhere"),
+    ("General", "pgup/pgdn", "scroll this screen"),
+    ("Filter", "jail:, ip:, left<", "query fields, see the README"),
+];
+
+fn help_lines(keymap: &Keymap, theme: &Theme) -> Vec<Line<'static>> {
+    let accent = theme.accent;
+    let mut lines = vec![Line::from(Span::styled("Keys", accent))];
+    let row = |keys: String, description: &str| {
+        Line::from(vec![
+            Span::styled(format!("  {keys:<20}"), theme.info),
+            Span::raw(description.to_string()),
+        ])
+    };
+    for section in Action::SECTIONS {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(section, accent)));
+        for action in Action::ALL.into_iter().filter(|a| a.section() == section) {
+            let keys = keymap.keys_for(action);
+            let keys = if keys.is_empty() {
+                "(unbound)".to_string()
+            } else {
+                keys.join(", ")
+            };
+            lines.push(row(keys, action.description()));
+        }
+        for (_, keys, description) in FIXED_KEYS.iter().filter(|(s, _, _)| *s == section) {
+            lines.push(row(keys.to_string(), description));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("up/down to scroll, any other key to close"));
+    lines
+}
+
+fn audit_lines(
+    records: &[AuditRecord],
+    error: Option<&str>,
+    path: Option<&Path>,
+    theme: &Theme,
+) -> Vec<Line<'static>> {
+    let title = match path {
+        Some(path) => format!("{}, newest first", path.display()),
+        None => "Audit log".to_string(),
+    };
+    let mut lines = vec![Line::from(Span::styled(title, theme.accent)), Line::from("")];
+    if let Some(error) = error {
+        lines.push(Line::from(Span::styled(error.to_string(), theme.error)));
+    } else if records.is_empty() {
+        lines.push(Line::from("No actions recorded yet"));
+    }
+    for record in records {
+        let time = DateTime::parse_from_rfc3339(&record.time)
+            .map(|time| time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| record.time.clone());
+        let target = if record.ips.is_empty() {
+            record.args.join(" ")
+        } else {
+            record.ips.join(" ")
+        };
+        let mut spans = vec![
+            Span::styled(format!("{time}  "), theme.muted),
+            Span::styled(format!("{:<10} ", record.user), theme.info),
+            Span::raw(format!(
+                "{:<4} {:<6} {:<16} {target}  ",
+                record.source, record.action, record.jail
+            )),
+        ];
+        match &record.error {
+            Some(err) => spans.push(Span::styled(format!("error: {err}"), theme.error)),
+            None => spans.push(Span::styled(record.result.clone(), theme.fresh)),
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("up/down to scroll, any other key to close"));
+    lines
+}
+
+fn border_style(theme: &Theme, active: bool) -> Style {
+    if active { theme.border_active } else { theme.border }
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(popup_layout[1])[1]
+}
+
+fn total_banned(state: &UiState) -> usize {
+    state.jails.iter().map(|j| j.ips.len()).sum()
+}
+
+fn format_time_value(value: &TimeValue) -> String {
+    if let Some(seconds) = value.seconds {
+        if value.raw.parse::<u64>().is_ok() {
+            return format!("{}s ({})", value.raw, format_duration(seconds));
+        }
+        return format!("{} ({})", value.raw, format_duration(seconds));
+    }
+    value.raw.clone()
+}
+
+fn format_duration(mut seconds: u64) -> String {
+    if seconds == 0 {
+        return "0s".to_string();
+    }
+    let days = seconds / 86_400;
+    seconds %= 86_400;
+    let hours = seconds / 3600;
+    seconds %= 3600;
+    let minutes = seconds / 60;
+    let secs = seconds % 60;
+
+    let mut out = String::new();
+    if days > 0 {
+        out.push_str(&format!("{days}d"));
+    }
+    if hours > 0 {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(&format!("{hours}h"));
+    }
+    if minutes > 0 {
         if !out.is_empty() {
             out.push(' ');
         }
@@ -978,6 +2510,9 @@ fn format_duration_compact(mut seconds: u64) -> String {
 }
 
 fn format_remaining(end_epoch: Option<i64>, raw: Option<&str>) -> String {
+    if end_epoch.is_some_and(|end| end >= PERMANENT_END_EPOCH) {
+        return "(permanent)".to_string();
+    }
     if let Some(end_epoch) = end_epoch {
         let now = Utc::now().timestamp();
         let remaining = if end_epoch <= now {
@@ -999,6 +2534,67 @@ fn format_remaining(end_epoch: Option<i64>, raw: Option<&str>) -> String {
     "--".to_string()
 }
 
+/// Reads the ban dialog's duration: empty for the jail's bantime,
+/// `permanent`, plain seconds or a duration such as `1h30m` or `7d`.
+fn parse_ban_time(input: &str) -> Result<Option<i64>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    if input.eq_ignore_ascii_case("permanent") {
+        return Ok(Some(-1));
+    }
+    let mut after_digit = false;
+    for ch in input.chars() {
+        let unit = "wdhmsWDHMS".contains(ch);
+        if !(ch.is_ascii_digit() || ch.is_whitespace() || (unit && after_digit)) {
+            return Err(format!("Invalid duration \"{input}\" (try 1h30m, 7d or permanent)"));
+        }
+        after_digit = ch.is_ascii_digit();
+    }
+    match parse_duration_string(input).or_else(|| input.parse().ok()) {
+        Some(0) => Err("Duration must be longer than 0s".to_string()),
+        Some(seconds) => i64::try_from(seconds)
+            .map(Some)
+            .map_err(|_| "Duration is too long".to_string()),
+        None => Err(format!("Invalid duration \"{input}\" (try 1h30m, 7d or permanent)")),
+    }
+}
+
+/// The end time asked for in the ban time dialog: `+1h` / `-30m` move the
+/// current end, `2h` is the new time left and `permanent` never ends.
+fn new_ban_end(input: &str, end_epoch: Option<i64>, now: i64) -> Result<i64, String> {
+    let input = input.trim();
+    let (sign, time) = if let Some(time) = input.strip_prefix('+') {
+        (1, time)
+    } else if let Some(time) = input.strip_prefix('-') {
+        (-1, time)
+    } else {
+        (0, input)
+    };
+    let end = match parse_ban_time(time)? {
+        None => return Err("Enter a time such as +1h, -30m, 2h or permanent".to_string()),
+        Some(-1) if sign == 0 => PERMANENT_END_EPOCH,
+        Some(-1) => return Err("permanent can't be added or taken off".to_string()),
+        Some(seconds) if sign == 0 => now.saturating_add(seconds),
+        Some(seconds) => {
+            let Some(end) = end_epoch.filter(|end| *end < PERMANENT_END_EPOCH) else {
+                return Err("This ban has no end time to move; enter the time left instead".to_string());
+            };
+            end.saturating_add(sign * seconds)
+        }
+    };
+    if end <= now {
+        return Err("That ends the ban in the past; unban the IP instead".to_string());
+    }
+    Ok(end.min(PERMANENT_END_EPOCH))
+}
+
+/// The ban time that makes a ban made at `now` end at `end`.
+fn end_bantime(end: i64, now: i64) -> i64 {
+    if end >= PERMANENT_END_EPOCH { -1 } else { end - now }
+}
+
 fn parse_duration_string(input: &str) -> Option<u64> {
     let mut total = 0u64;
     let mut num: Option<u64> = None;
@@ -1007,30 +2603,30 @@ fn parse_duration_string(input: &str) -> Option<u64> {
     for ch in input.chars() {
         if ch.is_ascii_digit() {
             let digit = ch.to_digit(10)? as u64;
-            num = Some(num.unwrap_or(0) * 10 + digit);
+            num = Some(num.unwrap_or(0).saturating_mul(10).saturating_add(digit));
         } else {
             let Some(value) = num.take() else {
                 continue;
             };
             match ch {
                 'w' | 'W' => {
-                    total += value * 7 * 86_400;
+                    total = total.saturating_add(value.saturating_mul(7 * 86_400));
                     has_unit = true;
                 }
                 'd' | 'D' => {
-                    total += value * 86_400;
+                    total = total.saturating_add(value.saturating_mul(86_400));
                     has_unit = true;
                 }
                 'h' | 'H' => {
-                    total += value * 3600;
+                    total = total.saturating_add(value.saturating_mul(3600));
                     has_unit = true;
                 }
                 'm' | 'M' => {
-                    total += value * 60;
+                    total = total.saturating_add(value.saturating_mul(60));
                     has_unit = true;
                 }
                 's' | 'S' => {
-                    total += value;
+                    total = total.saturating_add(value);
                     has_unit = true;
                 }
                 _ => {}
@@ -1039,7 +2635,7 @@ fn parse_duration_string(input: &str) -> Option<u64> {
     }
 
     if let Some(value) = num {
-        total += value;
+        total = total.saturating_add(value);
     }
 
     if has_unit {
@@ -1083,281 +2679,1791 @@ fn remaining_seconds(end_epoch: Option<i64>) -> Option<u64> {
     }
 }
 
-fn current_ip_view<'a>(state: &UiState, jail: &'a JailStatus) -> Vec<&'a IpEntry> {
-    let query = state.search_query.trim().to_lowercase();
-    let mut view: Vec<&IpEntry> = jail
-        .ips
-        .iter()
-        .filter(|entry| {
-            if query.is_empty() {
-                true
-            } else {
-                entry.ip.to_lowercase().contains(&query)
+/// IPs of the view that fall in one CIDR block.
+struct SubnetGroup<'a> {
+    cidr: Cidr,
+    rows: Vec<IpRow<'a>>,
+}
+
+/// Groups `view` by the configured IPv4 and IPv6 prefixes, biggest groups
+/// first.
+fn subnet_groups<'a>(state: &UiState, view: &[IpRow<'a>]) -> Vec<SubnetGroup<'a>> {
+    let mut groups: BTreeMap<Cidr, Vec<IpRow<'a>>> = BTreeMap::new();
+    for row in view {
+        let Some(addr) = row.entry.addr else {
+            continue;
+        };
+        let prefix = if addr.is_ipv4() { state.group_prefix.0 } else { state.group_prefix.1 };
+        groups.entry(Cidr::enclosing(addr, prefix)).or_default().push(*row);
+    }
+    let mut groups: Vec<SubnetGroup> = groups
+        .into_iter()
+        .map(|(cidr, rows)| SubnetGroup { cidr, rows })
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.rows.len()));
+    groups
+}
+
+fn current_ip_view<'a>(state: &UiState, jails: &'a [JailStatus]) -> Vec<IpRow<'a>> {
+    let source: Vec<&JailStatus> = if state.view_all {
+        jails.iter().collect()
+    } else {
+        jails.get(state.selected_jail_index()).into_iter().collect()
+    };
+    let mut view: Vec<IpRow> = source
+        .into_iter()
+        .flat_map(|jail| {
+            let permanent_jail = jail.bantime.raw.trim() == "-1";
+            jail.ips
+                .iter()
+                .filter(move |entry| {
+                    state.filter.matches(&Candidate {
+                        ip: &entry.ip,
+                        addr: entry.addr.as_ref(),
+                        jail: &jail.name,
+                        remaining: remaining_seconds(entry.end_epoch),
+                        permanent: permanent_jail
+                            || entry.end_epoch.is_some_and(|end| end >= PERMANENT_END_EPOCH),
+                    })
+                })
+                .map(move |entry| IpRow {
+                    jail: &jail.name,
+                    entry,
+                })
+        })
+        .collect();
+
+    view.sort_by(|a, b| {
+        let by_addr = || compare_entries(a.entry, b.entry);
+        let by_ip = || by_addr().then_with(|| a.jail.cmp(b.jail));
+        let order = match state.sort_column {
+            Column::Ip => by_ip(),
+            Column::Jail => a.jail.cmp(b.jail).then_with(by_addr),
+            Column::Left | Column::Expires => a
+                .entry
+                .end_epoch
+                .unwrap_or(i64::MAX)
+                .cmp(&b.entry.end_epoch.unwrap_or(i64::MAX))
+                .then_with(by_ip),
+            Column::Started => a
+                .entry
+                .start_epoch
+                .unwrap_or(i64::MAX)
+                .cmp(&b.entry.start_epoch.unwrap_or(i64::MAX))
+                .then_with(by_ip),
+            Column::Raw => a.entry.time_raw.cmp(&b.entry.time_raw).then_with(by_ip),
+        };
+        if state.sort_desc { order.reverse() } else { order }
+    });
+
+    view
+}
+
+/// Numeric address order; entries whose IP did not parse sort last.
+fn compare_entries(a: &IpEntry, b: &IpEntry) -> std::cmp::Ordering {
+    match (&a.addr, &b.addr) {
+        (Some(x), Some(y)) => compare_addrs(x, y),
+        (x, y) => y.is_some().cmp(&x.is_some()).then_with(|| a.ip.cmp(&b.ip)),
+    }
+}
+
+fn locate_ip(backend: &mut dyn Fail2banBackend, jails: &[JailStatus], ip: &str) -> Vec<IpMatch> {
+    let names = backend.banned_in(ip).unwrap_or_else(|_| {
+        jails
+            .iter()
+            .filter(|j| j.ips.iter().any(|e| e.ip == ip))
+            .map(|j| j.name.clone())
+            .collect()
+    });
+    names
+        .into_iter()
+        .map(|jail| {
+            let entry = jails
+                .iter()
+                .find(|j| j.name == jail)
+                .and_then(|j| j.ips.iter().find(|e| e.ip == ip));
+            IpMatch {
+                jail,
+                end_epoch: entry.and_then(|e| e.end_epoch),
+                time_raw: entry.and_then(|e| e.time_raw.clone()),
             }
         })
+        .collect()
+}
+
+/// Returns the jails the unban failed in, with the error.
+fn unban_everywhere(state: &UiState, ip: &str, jails: &[String]) -> Vec<(String, String)> {
+    let mut backend = state.backend();
+    jails
+        .iter()
+        .filter_map(|jail| {
+            backend
+                .unban(jail, &[ip])
+                .err()
+                .map(|err| (jail.clone(), err.to_string()))
+        })
+        .collect()
+}
+
+fn export_rows(state: &UiState, scope: ExportScope) -> Vec<(&JailStatus, Vec<&IpEntry>)> {
+    if scope == ExportScope::Marked {
+        return state
+            .jails
+            .iter()
+            .map(|jail| {
+                let ips: Vec<&IpEntry> = jail
+                    .ips
+                    .iter()
+                    .filter(|entry| state.marked.contains(&(jail.name.clone(), entry.ip.clone())))
+                    .collect();
+                (jail, ips)
+            })
+            .filter(|(_, ips)| !ips.is_empty())
+            .collect();
+    }
+    if scope == ExportScope::Filtered {
+        let view = current_ip_view(state, &state.jails);
+        return state
+            .jails
+            .iter()
+            .map(|jail| {
+                let ips: Vec<&IpEntry> = view
+                    .iter()
+                    .filter(|row| row.jail == jail.name)
+                    .map(|row| row.entry)
+                    .collect();
+                (jail, ips)
+            })
+            .filter(|(_, ips)| !ips.is_empty())
+            .collect();
+    }
+    state
+        .jails
+        .iter()
+        .map(|jail| (jail, jail.ips.iter().collect()))
+        .collect()
+}
+
+const BATCH_SIZE: usize = 50;
+/// Lines of the ban list shown while typing it and when reviewing it.
+const BAN_LIST_ROWS: usize = 10;
+
+/// Outcome of a bulk ban or unban: how many IPs went through, the jail, IP
+/// and error of each one that did not, and problems not tied to one IP.
+#[derive(Debug, Default)]
+struct BulkReport {
+    done: usize,
+    failed: Vec<(String, String, String)>,
+    warnings: Vec<String>,
+}
+
+impl BulkReport {
+    fn summary(&self, verb: &str, what: &str) -> String {
+        if self.failed.is_empty() {
+            format!("{verb} {} {what}", self.done)
+        } else {
+            format!(
+                "{verb} {} of {} {what}, {} failed",
+                self.done,
+                self.done + self.failed.len(),
+                self.failed.len()
+            )
+        }
+    }
+}
+
+/// Sends `ips` to `op` in batches. A rejected batch is retried one IP at a
+/// time so that each failure is reported against its own IP; that is safe
+/// for bans, as banning an IP twice is not an error.
+fn run_batched(
+    report: &mut BulkReport,
+    jail: &str,
+    ips: &[&str],
+    mut op: impl FnMut(&[&str]) -> Result<()>,
+) {
+    for chunk in ips.chunks(BATCH_SIZE) {
+        if op(chunk).is_ok() {
+            report.done += chunk.len();
+            continue;
+        }
+        for ip in chunk {
+            match op(&[ip]) {
+                Ok(()) => report.done += 1,
+                Err(err) => report
+                    .failed
+                    .push((jail.to_string(), ip.to_string(), format!("{err:#}"))),
+            }
+        }
+    }
+}
+
+/// Unbans `ips` from `jail` in batches. fail2ban may have unbanned part of a
+/// batch it rejects, so the jail is asked what it still holds: only those IPs
+/// are retried one at a time. The others count as unbanned if the jail held
+/// them before, and as failures if they were not banned to begin with.
+fn unban_batched(backend: &mut dyn Fail2banBackend, report: &mut BulkReport, jail: &str, ips: &[&str]) {
+    fn held(backend: &mut dyn Fail2banBackend, jail: &str) -> Option<Vec<String>> {
+        backend.jail_status(jail).ok().map(|status| status.ips)
+    }
+    // Without an answer from the jail, assume it holds everything.
+    let holds = |list: &Option<Vec<String>>, ip: &str| {
+        list.as_ref().is_none_or(|list| list.iter().any(|b| b == ip))
+    };
+
+    let before = held(backend, jail);
+    for chunk in ips.chunks(BATCH_SIZE) {
+        if backend.unban(jail, chunk).is_ok() {
+            report.done += chunk.len();
+            continue;
+        }
+        let after = held(backend, jail);
+        for ip in chunk {
+            if !holds(&after, ip) {
+                if holds(&before, ip) {
+                    report.done += 1;
+                } else {
+                    report.failed.push((jail.to_string(), ip.to_string(), "not banned".to_string()));
+                }
+                continue;
+            }
+            match backend.unban(jail, &[ip]) {
+                Ok(()) => report.done += 1,
+                Err(err) => report
+                    .failed
+                    .push((jail.to_string(), ip.to_string(), format!("{err:#}"))),
+            }
+        }
+    }
+}
+
+/// What the ban dialogs say when a time can't be given.
+const KEEP_JAIL_BANTIME: &str = "leave the time empty to ban for the jail's bantime";
+
+/// How [`ban_for`] gives bans a time of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BanTimeWay {
+    /// The server takes the time with the ban.
+    Own,
+    /// The jail's bantime already is the time asked for.
+    JailBantime,
+    /// The jail's bantime, which is this, is changed while banning.
+    Override(i64),
+}
+
+/// How bans of `secs` seconds (`-1` for good) can be made in a jail whose
+/// bantime is `current`, or why they would get the jail's bantime instead.
+fn ban_time_way(
+    own_ban_times: bool,
+    current: Option<i64>,
+    secs: i64,
+    override_bantime: bool,
+) -> Result<BanTimeWay, &'static str> {
+    let same = current.is_some_and(|current| current == secs || (current < 0 && secs < 0));
+    match current {
+        _ if own_ban_times => Ok(BanTimeWay::Own),
+        _ if same => Ok(BanTimeWay::JailBantime),
+        Some(original) if override_bantime && secs >= 0 => Ok(BanTimeWay::Override(original)),
+        _ if !override_bantime => Err("the server can't give a ban a time of its own (bantime_override is off)"),
+        _ if secs < 0 => Err("a permanent ban needs the jail's bantime at -1, which f2bs never sets"),
+        _ => Err("could not read the jail's bantime"),
+    }
+}
+
+/// [`ban_time_way`] for `jail`, asking `backend` for its bantime.
+fn check_ban_time(
+    backend: &mut dyn Fail2banBackend,
+    jail: &str,
+    secs: i64,
+    override_bantime: bool,
+) -> Result<BanTimeWay, &'static str> {
+    if backend.own_ban_times() {
+        return Ok(BanTimeWay::Own);
+    }
+    let current = backend
+        .get(jail, &["bantime"])
+        .ok()
+        .and_then(|value| value.trim().parse::<i64>().ok());
+    ban_time_way(false, current, secs, override_bantime)
+}
+
+/// Bans `ips` in `jail` for `bantime` seconds (`-1` for good, `None` for the
+/// jail's bantime), the way [`check_ban_time`] finds. When the time can't be
+/// given the IPs are banned for the jail's bantime and that is warned about;
+/// callers that must not fall back check first.
+fn ban_for(
+    backend: &mut dyn Fail2banBackend,
+    report: &mut BulkReport,
+    jail: &str,
+    ips: &[&str],
+    bantime: Option<i64>,
+    override_bantime: bool,
+) {
+    let Some(secs) = bantime else {
+        run_batched(report, jail, ips, |chunk| backend.ban(jail, chunk));
+        return;
+    };
+    match check_ban_time(backend, jail, secs, override_bantime) {
+        Ok(BanTimeWay::Own) => run_batched(report, jail, ips, |chunk| backend.ban_with_time(jail, chunk, secs)),
+        Ok(BanTimeWay::JailBantime) => run_batched(report, jail, ips, |chunk| backend.ban(jail, chunk)),
+        Ok(BanTimeWay::Override(original)) => ban_with_override(backend, report, jail, ips, secs, original),
+        Err(why) => {
+            report
+                .warnings
+                .push(format!("{jail}: {why}, banned for the jail's bantime instead"));
+            run_batched(report, jail, ips, |chunk| backend.ban(jail, chunk));
+        }
+    }
+}
+
+/// How many times putting a jail's bantime back is tried before giving up.
+const RESTORE_TRIES: usize = 3;
+
+/// A jail whose bantime f2bs changed. The original is put back by
+/// [`restore`](Self::restore), or when the override is dropped on an early
+/// return or a panic.
+struct BantimeOverride<'a> {
+    backend: &'a mut dyn Fail2banBackend,
+    jail: &'a str,
+    original: i64,
+    restored: bool,
+}
+
+impl BantimeOverride<'_> {
+    fn restore(&mut self) -> Result<()> {
+        self.restored = true;
+        let original = self.original.to_string();
+        let mut result = Ok(String::new());
+        for _ in 0..RESTORE_TRIES {
+            result = self.backend.set(self.jail, &["bantime", &original]);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result.map(|_| ())
+    }
+}
+
+impl Drop for BantimeOverride<'_> {
+    fn drop(&mut self) {
+        if !self.restored {
+            let _ = self.restore();
+        }
+    }
+}
+
+/// Bans `ips` in `jail` for `secs` seconds on a server without per-ban times,
+/// turned on by `bantime_override` in the config. `banip` takes no time, and
+/// a ban without a time of its own follows the jail's bantime even after that
+/// changes. fail2ban only gives a ban its own time when the IP is banned
+/// again and the new ban would end later, so the jail's bantime is set to
+/// `secs` (`original` is what it was), the IPs are banned twice and the
+/// original is put back. Bans fail2ban makes in the jail meanwhile get
+/// `secs` too. The times are read back at the end, and IPs that still
+/// follow the jail's bantime are warned about.
+fn ban_with_override(
+    backend: &mut dyn Fail2banBackend,
+    report: &mut BulkReport,
+    jail: &str,
+    ips: &[&str],
+    secs: i64,
+    original: i64,
+) {
+    let started = Utc::now().timestamp();
+    let mut guard = BantimeOverride {
+        backend,
+        jail,
+        original,
+        restored: false,
+    };
+    if let Err(err) = guard.backend.set(jail, &["bantime", &secs.to_string()]) {
+        // The set may have gone through even though it reported an error.
+        let restored = guard.restore();
+        report.warnings.push(format!(
+            "{jail}: could not set bantime to {secs} ({err:#}), banned for the jail's bantime instead"
+        ));
+        if let Err(err) = restored {
+            report.warnings.push(restore_warning(jail, original, &err));
+        }
+        run_batched(report, jail, ips, |chunk| guard.backend.ban(jail, chunk));
+        return;
+    }
+
+    let failed_before = report.failed.len();
+    run_batched(report, jail, ips, |chunk| guard.backend.ban(jail, chunk));
+    let failed = &report.failed[failed_before..];
+    let banned: Vec<&str> = ips
+        .iter()
+        .copied()
+        .filter(|ip| !failed.iter().any(|(_, failed, _)| failed == ip))
         .collect();
+    // Errors show up as bans without their own time below.
+    for chunk in banned.chunks(BATCH_SIZE) {
+        let _ = guard.backend.ban(jail, chunk);
+    }
+    if let Err(err) = guard.restore() {
+        report.warnings.push(restore_warning(jail, original, &err));
+    }
+
+    let Ok(output) = guard.backend.get(jail, &["banip", "--with-time"]) else {
+        return;
+    };
+    // A ban's own time is `secs` plus the time between its two bans.
+    let slack = Utc::now().timestamp() - started + 1;
+    for entry in parse_banip_with_time(&output, None) {
+        if !banned.contains(&entry.ip.as_str()) {
+            continue;
+        }
+        let kept = entry
+            .time_raw
+            .as_deref()
+            .and_then(parse_ban_seconds)
+            .is_none_or(|kept| (secs..=secs + slack).contains(&kept));
+        if !kept {
+            report.warnings.push(format!(
+                "{jail}: {} got the jail's bantime, fail2ban did not keep a time of its own",
+                entry.ip
+            ));
+        }
+    }
+}
 
-    match state.sort_mode {
-        SortMode::Ip => {
-            view.sort_by(|a, b| a.ip.cmp(&b.ip));
+fn restore_warning(jail: &str, original: i64, err: &anyhow::Error) -> String {
+    format!(
+        "{jail}: could not set bantime back to {original} ({err:#}); \
+         run `fail2ban-client set {jail} bantime {original}`"
+    )
+}
+
+/// Gives the ban of `ip` in `jail` a new time. fail2ban can't change a ban in
+/// place, so the IP is unbanned and banned again; if the new ban fails the
+/// old one is put back with `previous` (as in [`UndoBan::bantime`]). A time
+/// the server can't give is refused before the unban. Returns the warnings
+/// of the bantime override.
+fn reban_for(
+    backend: &mut dyn Fail2banBackend,
+    jail: &str,
+    ip: &str,
+    bantime: i64,
+    previous: Option<i64>,
+    override_bantime: bool,
+) -> Result<Vec<String>> {
+    if let Err(why) = check_ban_time(backend, jail, bantime, override_bantime) {
+        return Err(anyhow!("{jail}: {why}"));
+    }
+    backend.unban(jail, &[ip])?;
+    let mut report = BulkReport::default();
+    ban_for(backend, &mut report, jail, &[ip], Some(bantime), override_bantime);
+    let Some((_, _, err)) = report.failed.first() else {
+        return Ok(report.warnings);
+    };
+    let mut rollback = BulkReport::default();
+    ban_for(backend, &mut rollback, jail, &[ip], previous, override_bantime);
+    if rollback.failed.is_empty() {
+        Err(anyhow!("{err} (the old ban was put back)"))
+    } else {
+        Err(anyhow!("{err}; putting the old ban back failed too, {ip} is no longer banned"))
+    }
+}
+
+/// Bans `ip` in `to` for `bantime` and, unless copying, unbans it from
+/// `from`. If that unban fails the new ban is taken back, so the IP stays
+/// where it was. Returns the warnings of the bantime override.
+fn move_ip(
+    backend: &mut dyn Fail2banBackend,
+    from: &str,
+    to: &str,
+    ip: &str,
+    bantime: Option<i64>,
+    copy: bool,
+    override_bantime: bool,
+) -> Result<Vec<String>> {
+    let already_in_target = backend.banned_in(ip).is_ok_and(|jails| jails.iter().any(|jail| jail == to));
+    let mut report = BulkReport::default();
+    ban_for(backend, &mut report, to, &[ip], bantime, override_bantime);
+    if let Some((_, _, err)) = report.failed.first() {
+        return Err(anyhow!("ban in {to}: {err}"));
+    }
+    if copy {
+        return Ok(report.warnings);
+    }
+    let Err(err) = backend.unban(from, &[ip]) else {
+        return Ok(report.warnings);
+    };
+    if already_in_target {
+        return Err(anyhow!("unban from {from}: {err:#}"));
+    }
+    match backend.unban(to, &[ip]) {
+        Ok(()) => Err(anyhow!("unban from {from}: {err:#} (the ban in {to} was taken back)")),
+        Err(rollback) => Err(anyhow!(
+            "unban from {from}: {err:#}; taking back the ban in {to} failed too: {rollback:#}"
+        )),
+    }
+}
+
+/// Bans the new entries of `plan` in `jail` in batches. Words that are not
+/// an IP or CIDR block are reported one by one as warnings.
+fn ban_list(backend: &mut dyn Fail2banBackend, jail: &str, plan: &BanPlan) -> BulkReport {
+    let ips: Vec<&str> = plan.new.iter().map(String::as_str).collect();
+    let mut report = BulkReport::default();
+    run_batched(&mut report, jail, &ips, |chunk| backend.ban(jail, chunk));
+    report
+        .warnings
+        .extend(plan.invalid.iter().map(|word| format!("{word}: not an IP or CIDR, skipped")));
+    report
+}
+
+/// IPs banned one by one in `jail` that `cidr` holds, not counting `cidr`
+/// itself.
+fn covered_ips(jail: &JailStatus, cidr: &Cidr) -> Vec<String> {
+    jail.ips
+        .iter()
+        .filter(|entry| entry.addr.is_some_and(|addr| cidr.contains(&addr)))
+        .map(|entry| entry.ip.clone())
+        .collect()
+}
+
+/// Drops the bans whose unban failed, so undo only restores real unbans.
+fn without_failures(bans: Vec<UndoBan>, report: &BulkReport) -> Vec<UndoBan> {
+    bans.into_iter()
+        .filter(|ban| !report.failed.iter().any(|(jail, ip, _)| *jail == ban.jail && *ip == ban.ip))
+        .collect()
+}
+
+/// What undoing the last journal entry does to the ban times.
+fn undo_time_note(state: &UiState) -> String {
+    let bans = state.journal.last().map_or(&[][..], |entry| &entry.bans[..]);
+    let problems: Vec<String> = bans
+        .iter()
+        .filter_map(|ban| state.ban_time_problem(&ban.jail, journal::restored_bantime(ban)))
+        .collect();
+    match problems.first() {
+        None => "The time each ban had left is restored.".to_string(),
+        Some(why) if problems.len() == bans.len() => {
+            format!("The times the bans had left can't be restored, they get the jail's bantime ({why}).")
+        }
+        Some(why) => format!(
+            "{} of them can't get the time they had left and get the jail's bantime ({why}).",
+            problems.len()
+        ),
+    }
+}
+
+/// Sets the status to `summary` and lists any failures in a report dialog.
+fn finish_bulk(state: &mut UiState, summary: String, report: &BulkReport) {
+    let lines: Vec<String> = report
+        .failed
+        .iter()
+        .map(|(jail, ip, err)| format!("{ip} ({jail}): {err}"))
+        .chain(report.warnings.iter().cloned())
+        .collect();
+    state.modal = (!lines.is_empty()).then(|| Modal::Report {
+        title: summary.clone(),
+        lines,
+    });
+    state.set_status(summary);
+    state.refresh();
+}
+
+fn unban_all_in_jail(
+    backend: &mut dyn Fail2banBackend,
+    jails: &[JailStatus],
+    jail: &str,
+) -> Result<BulkReport> {
+    let Some(jail_status) = jails.iter().find(|j| j.name == jail) else {
+        return Err(anyhow!("jail not found"));
+    };
+    let ips: Vec<&str> = jail_status.ips.iter().map(|entry| entry.ip.as_str()).collect();
+    let mut report = BulkReport::default();
+    unban_batched(backend, &mut report, jail, &ips);
+    Ok(report)
+}
+
+fn main() -> Result<()> {
+    let cli = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprintln!("f2bs: {err}\n\n{USAGE}");
+            std::process::exit(EXIT_USAGE);
         }
-        SortMode::TimeLeft => {
-            view.sort_by_key(|entry| remaining_seconds(entry.end_epoch).unwrap_or(u64::MAX));
+    };
+    match cli.command {
+        CliCommand::Help => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+        CliCommand::Version => {
+            println!("f2bs {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
         }
+        _ => {}
     }
 
-    view
-}
-
-fn unban_all_in_jail(state: &UiState, jail: &str) -> Result<usize> {
-    let Some(jail_status) = state.jails.iter().find(|j| j.name == jail) else {
-        return Err(anyhow!("jail not found"));
+    let (config, config_warning) = Config::load();
+    let tui = cli.command == CliCommand::Tui;
+    let audit = AuditLog::from_config(&config, if tui { "tui" } else { "cli" });
+    let audit_warning = match backend_kind(cli.backend.as_deref()).as_str() {
+        "demo" => None,
+        _ => audit.check().err().map(|err| format!("audit log: {err:#}")),
     };
-    if jail_status.ips.is_empty() {
-        return Ok(0);
-    }
 
-    let mut total = 0;
-    for chunk in jail_status.ips.chunks(50) {
-        let mut args: Vec<String> = Vec::with_capacity(3 + chunk.len());
-        args.push("set".to_string());
-        args.push(jail.to_string());
-        args.push("unbanip".to_string());
-        for entry in chunk {
-            args.push(entry.ip.clone());
+    let mut fail2ban = match open_backend(cli.backend.as_deref(), cli.socket.as_deref(), audit) {
+        Ok(backend) => backend,
+        Err(err) => {
+            eprintln!("f2bs: {err:#}");
+            std::process::exit(EXIT_FAILURE);
         }
-        let refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-        run_fail2ban(&refs)?;
-        total += chunk.len();
+    };
+    if !tui {
+        if let Some(warning) = audit_warning.filter(|_| cli.command.modifies()) {
+            eprintln!("f2bs: {warning}");
+        }
+        let code = cli::run(&cli, fail2ban.as_mut()).unwrap_or_else(|err| {
+            eprintln!("f2bs: {err:#}");
+            EXIT_FAILURE
+        });
+        drop(fail2ban);
+        std::process::exit(code);
     }
 
-    Ok(total)
-}
-
-fn main() -> Result<()> {
     enable_raw_mode().context("enable raw mode")?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture).context("enter alternate screen")?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste).context("enter alternate screen")?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend).context("init terminal")?;
 
-    let res = run_app(&mut terminal);
+    let warning = [config_warning, audit_warning].into_iter().flatten().collect::<Vec<_>>();
+    let warning = (!warning.is_empty()).then(|| warning.join("; "));
+    let res = run_app(&mut terminal, fail2ban, config, warning);
 
     disable_raw_mode().ok();
     execute!(
         terminal.backend_mut(),
+        DisableBracketedPaste,
         DisableMouseCapture,
         LeaveAlternateScreen
     )
     .ok();
     terminal.show_cursor().ok();
 
-    res
+    if let Err(err) = res?.save() {
+        eprintln!("f2bs: could not save preferences: {err:#}");
+    }
+    Ok(())
 }
 
-fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
-    let mut state = UiState::new();
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    backend: Box<dyn Fail2banBackend>,
+    config: Config,
+    config_warning: Option<String>,
+) -> Result<Config> {
+    let mut state = UiState::new(backend, config);
+    if let Some(warning) = config_warning {
+        state.set_status(warning);
+    }
     state.refresh();
 
     loop {
-        if state.autorefresh && state.last_refresh.elapsed() >= state.refresh_interval {
+        state.poll_refresh();
+        if state.autorefresh
+            && !state.refreshing()
+            && state.last_refresh.elapsed() >= state.refresh_interval
+        {
             state.refresh();
         }
         terminal.draw(|frame| draw_ui(frame, &mut state))?;
 
         if event::poll(Duration::from_millis(200))? {
-            match event::read()? {
-                Event::Key(key) => {
-                    if handle_key(key, &mut state)? {
-                        break;
+            let quit = match event::read()? {
+                Event::Key(key) => handle_key(key, &mut state)?,
+                Event::Mouse(mouse) => handle_mouse(mouse, &mut state)?,
+                Event::Paste(text) => {
+                    handle_paste(&text, &mut state)?;
+                    false
+                }
+                _ => false,
+            };
+            if quit {
+                break;
+            }
+        }
+    }
+
+    Ok(state.config())
+}
+
+/// Pasted text goes whole into the ban list and is typed into any other
+/// prompt. Outside prompts it is ignored rather than run as key bindings.
+fn handle_paste(text: &str, state: &mut UiState) -> Result<()> {
+    if let Some(Modal::BanList { text: list, .. }) = &mut state.modal {
+        list.push_str(&text.replace("\r\n", "\n").replace('\r', "\n"));
+        return Ok(());
+    }
+    if state.modal.is_none() && !state.search_mode {
+        return Ok(());
+    }
+    for c in text.chars().filter(|c| !c.is_control()) {
+        handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::empty()), state)?;
+    }
+    Ok(())
+}
+
+fn handle_key(key: KeyEvent, state: &mut UiState) -> Result<bool> {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return Ok(true);
+    }
+
+    if let Some(modal) = state.modal.clone() {
+        return handle_modal_key(key, state, modal);
+    }
+
+    if state.search_mode {
+        return handle_search_key(key, state);
+    }
+
+    match state.keymap.resolve(KeyContext::Main, &key) {
+        Resolved::Action(action) => run_action(action, state),
+        Resolved::Pending => {
+            let pending = state.keymap.pending().unwrap_or_default();
+            state.set_status(format!("{pending} …"));
+            Ok(false)
+        }
+        Resolved::Unbound => Ok(false),
+    }
+}
+
+fn run_action(action: Action, state: &mut UiState) -> Result<bool> {
+    match action {
+        Action::Quit => return Ok(true),
+        Action::Refresh => state.refresh(),
+        Action::ToggleAutorefresh => {
+            state.autorefresh = !state.autorefresh;
+            state.set_status(if state.autorefresh {
+                "Auto-refresh enabled"
+            } else {
+                "Auto-refresh disabled"
+            });
+        }
+        Action::SortNext => {
+            let columns = state.sortable_columns();
+            let next = columns
+                .iter()
+                .position(|c| *c == state.sort_column)
+                .map(|i| columns[(i + 1) % columns.len()])
+                .unwrap_or(Column::Ip);
+            state.sort_by(next);
+        }
+        Action::SortReverse => state.sort_by(state.sort_column),
+        Action::Ban if state.subnets && state.focus == Focus::Ips => {
+            if let Some((cidr, _)) = state.selected_group() {
+                let names: Vec<String> = state.jails.iter().map(|j| j.name.clone()).collect();
+                state.load_ignore_lists(&names);
+                state.modal = Some(Modal::BanSubnet {
+                    cidr,
+                    selected: state.selected_jail_index(),
+                    unban: true,
+                });
+                state.set_status(format!("Pick the jail to ban {cidr} in"));
+            }
+        }
+        Action::Ban if !state.marked.is_empty() => {
+            let ips: BTreeSet<String> = state.marked.iter().map(|(_, ip)| ip.clone()).collect();
+            let names: Vec<String> = state.jails.iter().map(|j| j.name.clone()).collect();
+            state.load_ignore_lists(&names);
+            state.modal = Some(Modal::BanMarked {
+                ips: ips.into_iter().collect(),
+                selected: state.selected_jail_index(),
+            });
+            state.set_status("Pick the jail to ban the marked IPs in");
+        }
+        Action::Ban => {
+            if let Some(jail) = state.selected_jail().map(|j| j.name.clone()) {
+                state.load_ignore_list(&jail).ok();
+                state.modal = Some(Modal::BanIp {
+                    jail,
+                    input: String::new(),
+                    duration: String::new(),
+                    on_duration: false,
+                    error: None,
+                });
+                state.set_status("Enter IP to ban");
+            }
+        }
+        Action::IgnoreList => {
+            if let Some(jail) = state.selected_jail().map(|j| j.name.clone()) {
+                let (entries, error) = match state.load_ignore_list(&jail) {
+                    Ok(entries) => (entries, None),
+                    Err(err) => (Vec::new(), Some(format!("{err:#}"))),
+                };
+                state.modal = Some(Modal::IgnoreList {
+                    jail,
+                    entries,
+                    selected: 0,
+                    input: None,
+                    removing: false,
+                    error,
+                });
+            }
+        }
+        Action::UnbanAndIgnore => {
+            if let Some((jail, ip)) = state.selected_row() {
+                state.modal = Some(Modal::UnbanIgnore { jail, ip });
+            }
+        }
+        Action::BanList => {
+            if let Some(jail) = state.selected_jail().map(|j| j.name.clone()) {
+                state.load_ignore_list(&jail).ok();
+                state.modal = Some(Modal::BanList {
+                    jail,
+                    text: String::new(),
+                    review: false,
+                });
+                state.set_status("Paste or type the IPs to ban");
+            }
+        }
+        Action::Export => {
+            state.modal = Some(Modal::Export {
+                input: default_export_name(),
+                scope: if state.marked.is_empty() {
+                    ExportScope::All
+                } else {
+                    ExportScope::Marked
+                },
+                error: None,
+                overwrite: false,
+            });
+            state.set_status("Enter file name to export to");
+        }
+        Action::Filter => {
+            state.search_mode = true;
+        }
+        Action::ClearFilter => {
+            state.set_filter("");
+            state.ip_state.select(Some(0));
+            state.set_status("Filter cleared");
+        }
+        Action::SwitchPanel => {
+            state.focus = if state.focus == Focus::Jails {
+                Focus::Ips
+            } else {
+                Focus::Jails
+            }
+        }
+        Action::Up => match state.focus {
+            Focus::Jails => state.move_jail(-1),
+            Focus::Ips => state.move_ip(-1),
+        },
+        Action::Down => match state.focus {
+            Focus::Jails => state.move_jail(1),
+            Focus::Ips => state.move_ip(1),
+        },
+        Action::Activate if state.focus == Focus::Jails => state.focus = Focus::Ips,
+        Action::Activate if state.subnets && state.marked.is_empty() => {
+            if let Some((cidr, _)) = state.selected_group() {
+                state.subnets = false;
+                state.set_filter(&format!("cidr:{cidr}"));
+                state.ip_state.select(Some(0));
+                state.set_status(format!("Showing the IPs in {cidr}, x clears the filter"));
+            }
+        }
+        Action::Activate | Action::Unban => {
+            if !state.marked.is_empty() {
+                state.modal = Some(Modal::UnbanMarked {
+                    targets: state.marked.iter().cloned().collect(),
+                });
+            } else if let Some((jail, ip)) = state.selected_row() {
+                state.modal = Some(Modal::UnbanIp { jail, ip });
+            }
+        }
+        Action::AuditLog => {
+            let (records, error) = match &state.audit_path {
+                Some(path) => match audit::read_records(path, audit::PANEL_RECORDS) {
+                    Ok(records) => (records, None),
+                    Err(err) => (Vec::new(), Some(format!("{err:#}"))),
+                },
+                None => (Vec::new(), Some("The audit log file is turned off (audit_log = \"\")".to_string())),
+            };
+            state.modal = Some(Modal::Audit {
+                records,
+                scroll: 0,
+                error,
+            });
+        }
+        Action::Undo => match state.journal.last() {
+            Some(entry) => {
+                state.modal = Some(Modal::Undo {
+                    label: entry.label.clone(),
+                    count: entry.bans.len(),
+                });
+            }
+            None => state.set_status("Nothing to undo"),
+        },
+        Action::Subnets => {
+            state.subnets = !state.subnets;
+            state.ip_state.select(Some(0));
+            state.set_status(if state.subnets {
+                "Subnet view: enter shows a block's IPs, b bans the whole block"
+            } else {
+                "Subnet view off"
+            });
+        }
+        Action::ToggleMark => state.toggle_mark(),
+        Action::MarkRange => state.mark_range(),
+        Action::MarkAll => state.mark_all_visible(),
+        Action::ClearMarks => {
+            if !state.marked.is_empty() {
+                state.marked.clear();
+                state.mark_anchor = None;
+                state.set_status("Marks cleared");
+            }
+        }
+        Action::UnbanEverywhere => {
+            if let Some((_, ip)) = state.selected_row() {
+                let jails = state.jails_holding(&ip);
+                state.modal = Some(Modal::UnbanEverywhere { ip, jails });
+            }
+        }
+        Action::BanTime => {
+            if let Some((jail, ip)) = state.selected_row() {
+                let end_epoch = state
+                    .jails
+                    .iter()
+                    .find(|j| j.name == jail)
+                    .and_then(|j| j.ips.iter().find(|e| e.ip == ip))
+                    .and_then(|e| e.end_epoch);
+                state.modal = Some(Modal::BanTime {
+                    jail,
+                    ip,
+                    end_epoch,
+                    input: String::new(),
+                    error: None,
+                });
+                state.set_status("Enter the new ban time");
+            }
+        }
+        Action::MoveToJail => {
+            if let Some((jail, ip)) = state.selected_row() {
+                let targets: Vec<String> = state
+                    .jails
+                    .iter()
+                    .map(|j| j.name.clone())
+                    .filter(|name| *name != jail)
+                    .collect();
+                if targets.is_empty() {
+                    state.set_status("There is no other jail to move to");
+                } else {
+                    state.load_ignore_lists(&targets);
+                    // The time left can only be carried over with a time of its own.
+                    let keep_time = state.own_ban_times || state.bantime_override;
+                    state.modal = Some(Modal::MoveIp {
+                        jail,
+                        ip,
+                        targets,
+                        selected: 0,
+                        copy: false,
+                        keep_time,
+                    });
+                    state.set_status("Pick the jail to move the IP to");
+                }
+            }
+        }
+        Action::SavedFilters => {
+            state.modal = Some(Modal::Filters {
+                selected: 0,
+                name: None,
+            });
+            state.set_status(if state.saved_filters.is_empty() {
+                "No saved filters yet: press s to save the current one"
+            } else {
+                "Pick a saved filter"
+            });
+        }
+        Action::Whereis => {
+            let input = state.selected_row().map(|(_, ip)| ip).unwrap_or_default();
+            state.modal = Some(Modal::Lookup {
+                input,
+                matches: None,
+                error: None,
+            });
+            state.set_status("Enter IP to look up");
+        }
+        Action::AllJails => {
+            state.view_all = !state.view_all;
+            state.ip_state.select(Some(0));
+            state.set_status(if state.view_all {
+                "Showing banned IPs from all jails"
+            } else {
+                "Showing banned IPs for the selected jail"
+            });
+        }
+        Action::UnbanAll => {
+            if let Some(jail) = state.selected_jail() {
+                state.modal = Some(Modal::UnbanAll {
+                    jail: jail.name.clone(),
+                    step: 1,
+                });
+            }
+        }
+        Action::Help => {
+            state.modal = Some(Modal::Help { scroll: 0 });
+        }
+        Action::Palette => {
+            state.modal = Some(Modal::Palette {
+                input: String::new(),
+                selected: 0,
+            });
+        }
+        Action::Confirm
+        | Action::Cancel
+        | Action::IgnoreAdd
+        | Action::IgnoreRemove
+        | Action::IgnoreReload
+        | Action::MoveCopy
+        | Action::MoveKeepTime
+        | Action::SubnetUnban => {}
+    }
+
+    Ok(false)
+}
+
+fn handle_search_key(key: KeyEvent, state: &mut UiState) -> Result<bool> {
+    match key.code {
+        KeyCode::Esc => {
+            state.search_mode = false;
+            state.set_status("Filter canceled");
+        }
+        KeyCode::Enter => {
+            if state.filter_error.is_some() {
+                return Ok(false);
+            }
+            state.search_mode = false;
+            state.ip_state.select(Some(0));
+            state.set_status("Filter applied");
+        }
+        KeyCode::Backspace => {
+            let mut query = state.search_query.clone();
+            query.pop();
+            state.set_filter(&query);
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            let query = format!("{}{c}", state.search_query);
+            state.set_filter(&query);
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn handle_filters_key(key: KeyEvent, state: &mut UiState, mut selected: usize, name: Option<String>) {
+    if let Some(mut name) = name {
+        match key.code {
+            KeyCode::Esc => {
+                state.modal = Some(Modal::Filters { selected, name: None });
+                return;
+            }
+            KeyCode::Backspace => {
+                name.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => name.push(c),
+            KeyCode::Enter if !name.trim().is_empty() => {
+                let name = name.trim().to_string();
+                let filter = SavedFilter {
+                    name: name.clone(),
+                    query: state.search_query.clone(),
+                };
+                selected = match state.saved_filters.iter().position(|f| f.name == name) {
+                    Some(index) => {
+                        state.saved_filters[index] = filter;
+                        index
+                    }
+                    None => {
+                        state.saved_filters.push(filter);
+                        state.saved_filters.len() - 1
+                    }
+                };
+                state.modal = Some(Modal::Filters { selected, name: None });
+                save_config(state, format!("Saved filter '{name}'"));
+                return;
+            }
+            _ => {}
+        }
+        state.modal = Some(Modal::Filters {
+            selected,
+            name: Some(name),
+        });
+        return;
+    }
+
+    let count = state.saved_filters.len();
+    match key.code {
+        KeyCode::Esc | KeyCode::Char('q') => {
+            state.modal = None;
+            return;
+        }
+        KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => selected = (selected + 1).min(count.saturating_sub(1)),
+        KeyCode::Char('s') => {
+            if state.search_query.trim().is_empty() || state.filter_error.is_some() {
+                state.set_status("Set a valid filter with / before saving it");
+            } else {
+                state.modal = Some(Modal::Filters {
+                    selected,
+                    name: Some(String::new()),
+                });
+                return;
+            }
+        }
+        KeyCode::Char('d') if selected < count => {
+            let removed = state.saved_filters.remove(selected);
+            selected = selected.min(state.saved_filters.len().saturating_sub(1));
+            save_config(state, format!("Deleted filter '{}'", removed.name));
+        }
+        KeyCode::Enter if selected < count => {
+            let filter = state.saved_filters[selected].clone();
+            state.set_filter(&filter.query);
+            state.ip_state.select(Some(0));
+            state.modal = None;
+            state.set_status(format!("Filter '{}' applied", filter.name));
+            return;
+        }
+        _ => {}
+    }
+    state.modal = Some(Modal::Filters { selected, name: None });
+}
+
+fn save_config(state: &mut UiState, done: String) {
+    match state.config().save() {
+        Ok(_) => state.set_status(done),
+        Err(err) => state.set_status(format!("{done}, but saving the config failed: {err:#}")),
+    }
+}
+
+/// Scrolling for the full-screen views; `None` when the key closes the view.
+fn scrolled(key: &KeyEvent, scroll: u16) -> Option<u16> {
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Some(scroll.saturating_sub(1)),
+        KeyCode::Down | KeyCode::Char('j') => Some(scroll + 1),
+        KeyCode::PageUp => Some(scroll.saturating_sub(10)),
+        KeyCode::PageDown => Some(scroll + 10),
+        _ => None,
+    }
+}
+
+fn handle_modal_key(key: KeyEvent, state: &mut UiState, modal: Modal) -> Result<bool> {
+    if let Modal::BanIp { jail, mut input, mut duration, mut on_duration, .. } = modal {
+        match key.code {
+            KeyCode::Esc => {
+                state.modal = None;
+                state.set_status("Action canceled");
+                return Ok(false);
+            }
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                on_duration = !on_duration;
+            }
+            KeyCode::Backspace => {
+                let field = if on_duration { &mut duration } else { &mut input };
+                field.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let field = if on_duration { &mut duration } else { &mut input };
+                field.push(c);
+            }
+            KeyCode::Enter => {
+                let ip = input.trim().to_string();
+                let valid_ip = is_ban_target(&ip);
+                let override_bantime = state.bantime_override;
+                let checked = parse_ban_time(&duration).and_then(|bantime| match bantime {
+                    Some(secs) => check_ban_time(state.backend().as_mut(), &jail, secs, override_bantime)
+                        .map(|_| bantime)
+                        .map_err(|why| format!("{jail}: {why}; {KEEP_JAIL_BANTIME}")),
+                    None => Ok(None),
+                });
+                let bantime = match checked {
+                    Ok(bantime) if valid_ip => bantime,
+                    result => {
+                        let error = match result {
+                            Err(err) if valid_ip => err,
+                            _ => "Invalid IP address or CIDR block".to_string(),
+                        };
+                        state.modal = Some(Modal::BanIp {
+                            jail,
+                            input,
+                            duration,
+                            on_duration: valid_ip,
+                            error: Some(error),
+                        });
+                        return Ok(false);
+                    }
+                };
+                let mut report = BulkReport::default();
+                ban_for(state.backend().as_mut(), &mut report, &jail, &[&ip], bantime, override_bantime);
+                if let Some((_, _, err)) = report.failed.first() {
+                    let error = Some(format!("Ban failed: {err}"));
+                    state.modal = Some(Modal::BanIp { jail, input, duration, on_duration, error });
+                    return Ok(false);
+                }
+                let time = match bantime {
+                    Some(-1) => " for good".to_string(),
+                    Some(seconds) => format!(" for {}", format_duration(seconds as u64)),
+                    None => String::new(),
+                };
+                let mut status = format!("Banned {ip} in {jail}{time}");
+                if let Some(entry) = state.ignored(&jail, &ip) {
+                    status.push_str(&format!(" (it is on the ignore list as {entry})"));
+                }
+                for warning in &report.warnings {
+                    status.push_str(&format!(" ({warning})"));
+                }
+                state.set_status(status);
+                state.modal = None;
+                state.refresh();
+                return Ok(false);
+            }
+            _ => {}
+        }
+        state.modal = Some(Modal::BanIp {
+            jail,
+            input,
+            duration,
+            on_duration,
+            error: None,
+        });
+        return Ok(false);
+    }
+
+    if let Modal::BanTime { jail, ip, end_epoch, mut input, .. } = modal {
+        match key.code {
+            KeyCode::Esc => {
+                state.modal = None;
+                state.set_status("Action canceled");
+                return Ok(false);
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.push(c);
+            }
+            KeyCode::Enter => {
+                let now = Utc::now().timestamp();
+                let end = match new_ban_end(&input, end_epoch, now) {
+                    Ok(end) => end,
+                    Err(err) => {
+                        state.modal = Some(Modal::BanTime { jail, ip, end_epoch, input, error: Some(err) });
+                        return Ok(false);
                     }
-                }
-                Event::Mouse(mouse) => {
-                    if handle_mouse(mouse, &mut state)? {
-                        break;
+                };
+                let bantime = end_bantime(end, now);
+                let previous = state.undo_ban(&jail, &ip).bantime;
+                let override_bantime = state.bantime_override;
+                let result = reban_for(state.backend().as_mut(), &jail, &ip, bantime, previous, override_bantime);
+                match result {
+                    Ok(warnings) if !warnings.is_empty() => {
+                        // The new time is unknown; the refresh shows it.
+                        state.set_status(format!("{ip} was banned again in {jail}, but {}", warnings.join("; ")));
+                        state.modal = None;
+                        state.refresh();
+                    }
+                    Ok(_) => {
+                        if let Some(entry) = state
+                            .jails
+                            .iter_mut()
+                            .find(|j| j.name == jail)
+                            .and_then(|j| j.ips.iter_mut().find(|e| e.ip == ip))
+                        {
+                            entry.start_epoch = Some(now);
+                            entry.end_epoch = Some(end);
+                            entry.time_raw = None;
+                        }
+                        state.set_status(if bantime < 0 {
+                            format!("{ip} is now banned in {jail} for good")
+                        } else {
+                            format!("{ip} is now banned in {jail} until {}", format_stamp_local(Some(end)))
+                        });
+                        state.modal = None;
+                        state.refresh();
+                    }
+                    Err(err) => {
+                        let error = Some(format!("Change failed: {err:#}"));
+                        state.modal = Some(Modal::BanTime { jail, ip, end_epoch, input, error });
                     }
                 }
-                _ => {}
+                return Ok(false);
             }
+            _ => {}
         }
+        state.modal = Some(Modal::BanTime {
+            jail,
+            ip,
+            end_epoch,
+            input,
+            error: None,
+        });
+        return Ok(false);
     }
 
-    Ok(())
-}
+    if let Modal::Help { scroll } = modal {
+        state.modal = scrolled(&key, scroll).map(|scroll| Modal::Help { scroll });
+        return Ok(false);
+    }
 
-fn handle_key(key: KeyEvent, state: &mut UiState) -> Result<bool> {
-    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
-        return Ok(true);
+    if let Modal::Audit { records, scroll, error } = modal {
+        state.modal = scrolled(&key, scroll).map(|scroll| Modal::Audit {
+            records,
+            scroll,
+            error,
+        });
+        return Ok(false);
     }
 
-    if let Some(modal) = state.modal.clone() {
-        return handle_modal_key(key, state, modal);
+    if let Modal::Filters { selected, name } = modal {
+        handle_filters_key(key, state, selected, name);
+        return Ok(false);
     }
 
-    if state.search_mode {
-        return handle_search_key(key, state);
+    if let Modal::Report { .. } = modal {
+        state.modal = None;
+        return Ok(false);
     }
 
-    match key.code {
-        KeyCode::Char('q') | KeyCode::Char('Q') => return Ok(true),
-        KeyCode::Char('r') | KeyCode::Char('R') => state.refresh(),
-        KeyCode::Char('t') | KeyCode::Char('T') => {
-            state.autorefresh = !state.autorefresh;
-            state.set_status(if state.autorefresh {
-                "Auto-refresh enabled"
-            } else {
-                "Auto-refresh disabled"
-            });
+    if let Modal::BanMarked { ips, mut selected } = modal {
+        match key.code {
+            KeyCode::Esc => {
+                state.modal = None;
+                state.set_status("Action canceled");
+                return Ok(false);
+            }
+            KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                selected = (selected + 1).min(state.jails.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let Some(jail) = state.jails.get(selected).map(|j| j.name.clone()) else {
+                    return Ok(false);
+                };
+                let refs: Vec<&str> = ips.iter().map(String::as_str).collect();
+                let mut report = BulkReport::default();
+                run_batched(&mut report, &jail, &refs, |chunk| state.backend().ban(&jail, chunk));
+                finish_bulk(state, report.summary("Banned", &format!("marked IPs in {jail}")), &report);
+                return Ok(false);
+            }
+            _ => {}
         }
-        KeyCode::Char('s') | KeyCode::Char('S') => {
-            state.sort_mode = match state.sort_mode {
-                SortMode::Ip => SortMode::TimeLeft,
-                SortMode::TimeLeft => SortMode::Ip,
+        state.modal = Some(Modal::BanMarked { ips, selected });
+        return Ok(false);
+    }
+
+    if let Modal::IgnoreList {
+        jail,
+        mut entries,
+        mut selected,
+        mut input,
+        removing,
+        ..
+    } = modal
+    {
+        let mut error = None;
+        let mut reload = false;
+        if removing {
+            let confirmed = match state.keymap.resolve(KeyContext::Confirm, &key) {
+                Resolved::Action(Action::Confirm) => Some(true),
+                Resolved::Action(Action::Cancel) => Some(false),
+                _ => match key.code {
+                    KeyCode::Enter => Some(true),
+                    KeyCode::Esc => Some(false),
+                    _ => None,
+                },
             };
-            state.ip_state.select(Some(0));
-            state.set_status("Sort mode updated");
-        }
-        KeyCode::Char('b') | KeyCode::Char('B') => {
-            if let Some(jail) = state.selected_jail() {
-                state.modal = Some(Modal::BanIp {
-                    jail: jail.name.clone(),
-                    input: String::new(),
-                    error: None,
-                });
-                state.set_status("Enter IP to ban");
+            if confirmed == Some(true)
+                && let Some(entry) = entries.get(selected).cloned()
+            {
+                let result = state.backend().set(&jail, &["delignoreip", &entry]);
+                match result {
+                    Ok(_) => {
+                        state.set_status(format!("Removed {entry} from the ignore list of {jail}"));
+                        reload = true;
+                    }
+                    Err(err) => error = Some(format!("Could not remove {entry}: {err:#}")),
+                }
             }
-        }
-        KeyCode::Char('/') => {
-            state.search_mode = true;
-        }
-        KeyCode::Char('x') | KeyCode::Char('X') => {
-            state.search_query.clear();
-            state.ip_state.select(Some(0));
-            state.set_status("Filter cleared");
-        }
-        KeyCode::Tab => {
-            state.focus = if state.focus == Focus::Jails {
-                Focus::Ips
-            } else {
-                Focus::Jails
+            if confirmed.is_none() {
+                state.modal = Some(Modal::IgnoreList {
+                    jail,
+                    entries,
+                    selected,
+                    input,
+                    removing,
+                    error,
+                });
+                return Ok(false);
             }
-        }
-        KeyCode::Up | KeyCode::Char('k') => match state.focus {
-            Focus::Jails => state.move_jail(-1),
-            Focus::Ips => state.move_ip(-1),
-        },
-        KeyCode::Down | KeyCode::Char('j') => match state.focus {
-            Focus::Jails => state.move_jail(1),
-            Focus::Ips => state.move_ip(1),
-        },
-        KeyCode::Enter => {
-            if state.focus == Focus::Jails {
-                state.focus = Focus::Ips;
-            } else if let Some(jail) = state.selected_jail() {
-                let view = current_ip_view(state, jail);
-                if let Some(entry) = state.selected_ip(&view) {
-                    state.modal = Some(Modal::UnbanIp {
-                        jail: jail.name.clone(),
-                        ip: entry.ip.clone(),
-                    });
+        } else if let Some(mut text) = input.take() {
+            match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter if !text.trim().is_empty() => {
+                    let entry = text.trim().to_string();
+                    let result = state.backend().set(&jail, &["addignoreip", &entry]);
+                    match result {
+                        Ok(_) => {
+                            state.set_status(format!("Added {entry} to the ignore list of {jail}"));
+                            reload = true;
+                        }
+                        Err(err) => {
+                            error = Some(format!("Could not add {entry}: {err:#}"));
+                            input = Some(text);
+                        }
+                    }
+                }
+                KeyCode::Backspace => {
+                    text.pop();
+                    input = Some(text);
+                }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) && !c.is_whitespace() => {
+                    text.push(c);
+                    input = Some(text);
                 }
+                _ => input = Some(text),
             }
-        }
-        KeyCode::Char('u') | KeyCode::Char('U') => {
-            if let Some(jail) = state.selected_jail() {
-                let view = current_ip_view(state, jail);
-                if let Some(entry) = state.selected_ip(&view) {
-                    state.modal = Some(Modal::UnbanIp {
-                        jail: jail.name.clone(),
-                        ip: entry.ip.clone(),
+        } else {
+            let action = match state.keymap.resolve(KeyContext::IgnoreList, &key) {
+                Resolved::Action(action) => Some(action),
+                Resolved::Pending => return Ok(false),
+                Resolved::Unbound => None,
+            };
+            match action {
+                Some(Action::IgnoreAdd) => input = Some(String::new()),
+                Some(Action::IgnoreRemove) if !entries.is_empty() => {
+                    state.modal = Some(Modal::IgnoreList {
+                        jail,
+                        entries,
+                        selected,
+                        input,
+                        removing: true,
+                        error: None,
                     });
+                    return Ok(false);
+                }
+                Some(Action::IgnoreReload) => reload = true,
+                _ if key.code == KeyCode::Esc || state.keymap.matches(Action::Quit, &key) => {
+                    state.modal = None;
+                    return Ok(false);
+                }
+                _ if state.keymap.matches(Action::Up, &key) => selected = selected.saturating_sub(1),
+                _ if state.keymap.matches(Action::Down, &key) => {
+                    selected = (selected + 1).min(entries.len().saturating_sub(1));
                 }
+                _ => {}
             }
         }
-        KeyCode::Char('A') => {
-            if let Some(jail) = state.selected_jail() {
-                state.modal = Some(Modal::UnbanAll {
-                    jail: jail.name.clone(),
-                    step: 1,
-                });
+        if reload {
+            match state.load_ignore_list(&jail) {
+                Ok(list) => entries = list,
+                Err(err) => error = Some(format!("{err:#}")),
             }
+            selected = selected.min(entries.len().saturating_sub(1));
         }
-        KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::SHIFT) => {
-            if let Some(jail) = state.selected_jail() {
-                state.modal = Some(Modal::UnbanAll {
-                    jail: jail.name.clone(),
-                    step: 1,
-                });
+        state.modal = Some(Modal::IgnoreList {
+            jail,
+            entries,
+            selected,
+            input,
+            removing: false,
+            error,
+        });
+        return Ok(false);
+    }
+
+    if let Modal::BanSubnet { cidr, mut selected, mut unban } = modal {
+        let action = match state.keymap.resolve(KeyContext::BanSubnet, &key) {
+            Resolved::Action(action) => Some(action),
+            Resolved::Pending => return Ok(false),
+            Resolved::Unbound => None,
+        };
+        match key.code {
+            _ if action == Some(Action::SubnetUnban) => unban = !unban,
+            KeyCode::Esc => {
+                state.modal = None;
+                state.set_status("Action canceled");
+                return Ok(false);
+            }
+            _ if state.keymap.matches(Action::Up, &key) => selected = selected.saturating_sub(1),
+            _ if state.keymap.matches(Action::Down, &key) => {
+                selected = (selected + 1).min(state.jails.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let Some(jail) = state.jails.get(selected) else {
+                    return Ok(false);
+                };
+                let name = jail.name.clone();
+                let covered = covered_ips(jail, &cidr);
+                let bans: Vec<UndoBan> = covered.iter().map(|ip| state.undo_ban(&name, ip)).collect();
+                let block = cidr.to_string();
+                let mut report = BulkReport::default();
+                run_batched(&mut report, &name, &[&block], |chunk| state.backend().ban(&name, chunk));
+                if !report.failed.is_empty() || !unban || covered.is_empty() {
+                    let summary = if report.failed.is_empty() {
+                        format!("Banned {cidr} in {name}")
+                    } else {
+                        format!("Could not ban {cidr} in {name}")
+                    };
+                    finish_bulk(state, summary, &report);
+                    return Ok(false);
+                }
+                let ips: Vec<&str> = covered.iter().map(String::as_str).collect();
+                let mut unbans = BulkReport::default();
+                unban_batched(state.backend().as_mut(), &mut unbans, &name, &ips);
+                state.journal.record(
+                    format!("unban of the IPs under {cidr} in {name}"),
+                    without_failures(bans, &unbans),
+                );
+                let summary = format!("Banned {cidr} in {name}, {}", unbans.summary("unbanned", "IPs it covers"));
+                finish_bulk(state, summary, &unbans);
+                return Ok(false);
             }
+            _ => {}
         }
-        _ => {}
+        state.modal = Some(Modal::BanSubnet { cidr, selected, unban });
+        return Ok(false);
     }
 
-    Ok(false)
-}
-
-fn handle_search_key(key: KeyEvent, state: &mut UiState) -> Result<bool> {
-    match key.code {
-        KeyCode::Esc => {
-            state.search_mode = false;
-            state.set_status("Filter canceled");
-        }
-        KeyCode::Enter => {
-            state.search_mode = false;
-            state.ip_state.select(Some(0));
-            state.set_status("Filter applied");
+    if let Modal::BanList { jail, mut text, review } = modal {
+        match key.code {
+            KeyCode::Esc => {
+                state.modal = None;
+                state.set_status("Action canceled");
+                return Ok(false);
+            }
+            KeyCode::Tab | KeyCode::BackTab if review => {
+                state.modal = Some(Modal::BanList { jail, text, review: false });
+                return Ok(false);
+            }
+            KeyCode::Tab => {
+                let banned = state.jails.iter().find(|j| j.name == jail).map(|j| j.ips.as_slice());
+                if plan_ban_list(&text, banned.unwrap_or_default()).new.is_empty() {
+                    state.set_status(format!("Nothing in the list that isn't banned in {jail} already"));
+                } else {
+                    state.modal = Some(Modal::BanList { jail, text, review: true });
+                    return Ok(false);
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('y') | KeyCode::Char('Y') if review => {
+                let banned = state.jails.iter().find(|j| j.name == jail).map(|j| j.ips.as_slice());
+                let plan = plan_ban_list(&text, banned.unwrap_or_default());
+                let report = ban_list(state.backend().as_mut(), &jail, &plan);
+                finish_bulk(state, report.summary("Banned", &format!("IPs in {jail}")), &report);
+                return Ok(false);
+            }
+            _ if review => {}
+            KeyCode::Enter => text.push('\n'),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => text.push(c),
+            _ => {}
         }
-        KeyCode::Backspace => {
-            state.search_query.pop();
+        state.modal = Some(Modal::BanList { jail, text, review });
+        return Ok(false);
+    }
+
+    if let Modal::MoveIp {
+        jail,
+        ip,
+        targets,
+        mut selected,
+        mut copy,
+        mut keep_time,
+    } = modal
+    {
+        let action = match state.keymap.resolve(KeyContext::MoveIp, &key) {
+            Resolved::Action(action) => Some(action),
+            Resolved::Pending => return Ok(false),
+            Resolved::Unbound => None,
+        };
+        match key.code {
+            _ if action == Some(Action::MoveCopy) => copy = !copy,
+            _ if action == Some(Action::MoveKeepTime) => keep_time = !keep_time,
+            KeyCode::Esc => {
+                state.modal = None;
+                state.set_status("Action canceled");
+                return Ok(false);
+            }
+            _ if state.keymap.matches(Action::Up, &key) => selected = selected.saturating_sub(1),
+            _ if state.keymap.matches(Action::Down, &key) => {
+                selected = (selected + 1).min(targets.len().saturating_sub(1));
+            }
+            KeyCode::Enter => {
+                let Some(target) = targets.get(selected) else {
+                    return Ok(false);
+                };
+                let bantime = if keep_time { state.undo_ban(&jail, &ip).bantime } else { None };
+                let override_bantime = state.bantime_override;
+                let result =
+                    move_ip(state.backend().as_mut(), &jail, target, &ip, bantime, copy, override_bantime);
+                state.modal = None;
+                match result {
+                    Ok(warnings) => {
+                        let mut status = if copy {
+                            format!("Copied {ip} from {jail} to {target}")
+                        } else {
+                            format!("Moved {ip} from {jail} to {target}")
+                        };
+                        for warning in &warnings {
+                            status.push_str(&format!(" ({warning})"));
+                        }
+                        state.set_status(status);
+                    }
+                    Err(err) => state.set_status(format!("{} failed: {err:#}", if copy { "Copy" } else { "Move" })),
+                }
+                state.refresh();
+                return Ok(false);
+            }
+            _ => {}
         }
-        KeyCode::Char(c) => {
-            if !key.modifiers.contains(KeyModifiers::CONTROL) {
-                state.search_query.push(c);
+        state.modal = Some(Modal::MoveIp {
+            jail,
+            ip,
+            targets,
+            selected,
+            copy,
+            keep_time,
+        });
+        return Ok(false);
+    }
+
+    if let Modal::Palette {
+        mut input,
+        mut selected,
+    } = modal
+    {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => {
+                state.modal = None;
+                return Ok(false);
+            }
+            KeyCode::Enter => {
+                state.modal = None;
+                return match matching_actions(&input).get(selected) {
+                    Some(&action) => run_action(action, state),
+                    None => Ok(false),
+                };
+            }
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => selected = selected.saturating_sub(1),
+            KeyCode::Down => selected += 1,
+            KeyCode::Char('n') if ctrl => selected += 1,
+            KeyCode::Backspace => {
+                input.pop();
+                selected = 0;
             }
+            KeyCode::Char(c) if !ctrl => {
+                input.push(c);
+                selected = 0;
+            }
+            _ => {}
         }
-        _ => {}
+        let selected = selected.min(matching_actions(&input).len().saturating_sub(1));
+        state.modal = Some(Modal::Palette { input, selected });
+        return Ok(false);
     }
-    Ok(false)
-}
 
-fn handle_modal_key(key: KeyEvent, state: &mut UiState, modal: Modal) -> Result<bool> {
-    if let Modal::BanIp { jail, mut input, .. } = modal
+    if let Modal::Lookup {
+        mut input, matches, ..
+    } = modal
     {
+        let mut matches = matches;
         match key.code {
             KeyCode::Esc => {
                 state.modal = None;
-                state.set_status("Action canceled");
+                state.set_status("Lookup closed");
                 return Ok(false);
             }
             KeyCode::Backspace => {
                 input.pop();
+                matches = None;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.push(c);
+                matches = None;
+            }
+            KeyCode::Enter => {
+                let ip = input.trim().to_string();
+                if let Some(found) = matches.as_ref().filter(|m| !m.is_empty()) {
+                    let jails = found.iter().map(|m| m.jail.clone()).collect();
+                    state.modal = Some(Modal::UnbanEverywhere { ip, jails });
+                    return Ok(false);
+                }
+                if ip.parse::<IpAddr>().is_err() {
+                    state.modal = Some(Modal::Lookup {
+                        input,
+                        matches: None,
+                        error: Some("Invalid IP address".to_string()),
+                    });
+                    return Ok(false);
+                }
+                let found = locate_ip(state.backend().as_mut(), &state.jails, &ip);
+                state.set_status(format!("{ip} is banned in {} jail(s)", found.len()));
+                matches = Some(found);
+            }
+            _ => {}
+        }
+        state.modal = Some(Modal::Lookup {
+            input,
+            matches,
+            error: None,
+        });
+        return Ok(false);
+    }
+
+    if let Modal::Export {
+        mut input,
+        mut scope,
+        overwrite,
+        ..
+    } = modal
+    {
+        match key.code {
+            KeyCode::Esc => {
+                state.modal = None;
+                state.set_status("Export canceled");
+                return Ok(false);
             }
-            KeyCode::Char(c) => {
-                if !key.modifiers.contains(KeyModifiers::CONTROL) {
-                    input.push(c);
+            KeyCode::Tab => {
+                scope = match scope {
+                    ExportScope::All => ExportScope::Filtered,
+                    ExportScope::Filtered if !state.marked.is_empty() => ExportScope::Marked,
+                    _ => ExportScope::All,
                 }
             }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                input.push(c);
+            }
             KeyCode::Enter => {
-                let ip = input.trim().to_string();
-                if ip.parse::<IpAddr>().is_err() {
-                    state.modal = Some(Modal::BanIp {
-                        jail,
-                        input,
-                        error: Some("Invalid IP address".to_string()),
-                    });
-                    return Ok(false);
-                }
-                match run_fail2ban(&["set", &jail, "banip", &ip]) {
-                    Ok(_) => {
-                        state.set_status(format!("Banned {ip} in {jail}"));
+                let path = Path::new(input.trim());
+                let rows = export_rows(state, scope);
+                match export_to_file(path, &rows, ExportFormat::from_path(path), overwrite) {
+                    Ok(count) => {
+                        state.set_status(format!("Exported {count} IPs to {}", path.display()));
                         state.modal = None;
-                        state.refresh();
+                    }
+                    Err(_) if !overwrite && path.exists() => {
+                        let error = format!("{} already exists, enter again to overwrite it", path.display());
+                        state.modal = Some(Modal::Export {
+                            input,
+                            scope,
+                            error: Some(error),
+                            overwrite: true,
+                        });
                     }
                     Err(err) => {
-                        state.modal = Some(Modal::BanIp {
-                            jail,
+                        state.modal = Some(Modal::Export {
                             input,
-                            error: Some(format!("Ban failed: {err}")),
+                            scope,
+                            error: Some(format!("Export failed: {err:#}")),
+                            overwrite: false,
                         });
                     }
                 }
@@ -1365,59 +4471,165 @@ fn handle_modal_key(key: KeyEvent, state: &mut UiState, modal: Modal) -> Result<
             }
             _ => {}
         }
-        state.modal = Some(Modal::BanIp {
-            jail,
+        state.modal = Some(Modal::Export {
             input,
+            scope,
             error: None,
+            overwrite: false,
         });
         return Ok(false);
     }
 
-    match key.code {
-        KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
-            match modal {
-                Modal::UnbanIp { jail, ip } => {
-                    match run_fail2ban(&["set", &jail, "unbanip", &ip]) {
-                        Ok(_) => {
-                            state.set_status(format!("Unbanned {ip} from {jail}"));
-                            state.modal = None;
-                            state.refresh();
-                        }
-                        Err(err) => {
-                            state.set_status(format!("Unban failed for {ip}: {err}"));
-                            state.modal = None;
-                        }
-                    }
+    match state.keymap.resolve(KeyContext::Confirm, &key) {
+        Resolved::Action(Action::Confirm) => answer_modal(state, modal, true),
+        Resolved::Action(Action::Cancel) => answer_modal(state, modal, false),
+        _ => {}
+    }
+
+    Ok(false)
+}
+
+/// Confirms or cancels one of the yes/no dialogs.
+fn answer_modal(state: &mut UiState, modal: Modal, confirmed: bool) {
+    if !confirmed {
+        state.modal = None;
+        state.set_status("Action canceled");
+        return;
+    }
+    match modal {
+        Modal::UnbanIp { jail, ip } => {
+            let undo = state.undo_ban(&jail, &ip);
+            let result = state.backend().unban(&jail, &[&ip]);
+            match result {
+                Ok(_) => {
+                    state.journal.record(format!("unban of {ip} from {jail}"), vec![undo]);
+                    state.set_status(format!("Unbanned {ip} from {jail}"));
+                    state.modal = None;
+                    state.refresh();
                 }
-                Modal::UnbanAll { jail, step } => {
-                    if step == 1 {
-                        state.modal = Some(Modal::UnbanAll { jail, step: 2 });
-                        state.set_status("Second confirmation required");
-                    } else {
-                        match unban_all_in_jail(state, &jail) {
-                            Ok(count) => {
-                                state.set_status(format!("Unbanned {count} IPs from {jail}"));
-                                state.modal = None;
-                                state.refresh();
-                            }
-                            Err(err) => {
-                                state.set_status(format!("Unban all failed for {jail}: {err}"));
-                                state.modal = None;
-                            }
-                        }
-                    }
+                Err(err) => {
+                    state.set_status(format!("Unban failed for {ip}: {err}"));
+                    state.modal = None;
                 }
-                Modal::BanIp { .. } => {}
             }
         }
-        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+        Modal::UnbanIgnore { jail, ip } => {
             state.modal = None;
-            state.set_status("Action canceled");
+            let undo = state.undo_ban(&jail, &ip);
+            let added = state.backend().set(&jail, &["addignoreip", &ip]);
+            if let Err(err) = added {
+                state.set_status(format!("Could not add {ip} to the ignore list of {jail}: {err:#}"));
+                return;
+            }
+            state.load_ignore_list(&jail).ok();
+            let result = state.backend().unban(&jail, &[&ip]);
+            match result {
+                Ok(()) => {
+                    state.journal.record(format!("unban of {ip} from {jail}"), vec![undo]);
+                    state.set_status(format!("Unbanned {ip} from {jail} and added it to the ignore list"));
+                }
+                Err(err) => state.set_status(format!(
+                    "Added {ip} to the ignore list of {jail}, but the unban failed: {err}"
+                )),
+            }
+            state.refresh();
         }
-        _ => {}
+        Modal::UnbanEverywhere { ip, jails } => {
+            let undo: Vec<UndoBan> = jails.iter().map(|jail| state.undo_ban(jail, &ip)).collect();
+            let failed = unban_everywhere(state, &ip, &jails);
+            state.modal = None;
+            state.journal.record(
+                format!("unban of {ip} from every jail"),
+                undo.into_iter()
+                    .filter(|ban| !failed.iter().any(|(jail, _)| *jail == ban.jail))
+                    .collect(),
+            );
+            if failed.is_empty() {
+                state.set_status(format!("Unbanned {ip} from {} jail(s)", jails.len()));
+            } else {
+                let failed: Vec<String> =
+                    failed.iter().map(|(jail, err)| format!("{jail}: {err}")).collect();
+                state.set_status(format!("Unban of {ip} failed in: {}", failed.join("; ")));
+            }
+            state.refresh();
+        }
+        Modal::UnbanAll { jail, step } => {
+            if step == 1 {
+                state.modal = Some(Modal::UnbanAll { jail, step: 2 });
+                state.set_status("Second confirmation required");
+            } else {
+                let undo: Vec<UndoBan> = state
+                    .jails
+                    .iter()
+                    .filter(|j| j.name == jail)
+                    .flat_map(|j| j.ips.iter().map(|entry| state.undo_ban(&jail, &entry.ip)))
+                    .collect();
+                let result = unban_all_in_jail(state.backend().as_mut(), &state.jails, &jail);
+                match result {
+                    Ok(report) => {
+                        state.journal.record(
+                            format!("unban all in {jail}"),
+                            without_failures(undo, &report),
+                        );
+                        let summary = report.summary("Unbanned", &format!("IPs from {jail}"));
+                        finish_bulk(state, summary, &report);
+                    }
+                    Err(err) => {
+                        state.set_status(format!("Unban all failed for {jail}: {err}"));
+                        state.modal = None;
+                    }
+                }
+            }
+        }
+        Modal::UnbanMarked { targets } => {
+            let undo: Vec<UndoBan> =
+                targets.iter().map(|(jail, ip)| state.undo_ban(jail, ip)).collect();
+            let mut by_jail: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+            for (jail, ip) in &targets {
+                by_jail.entry(jail).or_default().push(ip);
+            }
+            let mut report = BulkReport::default();
+            {
+                let mut backend = state.backend();
+                for (jail, ips) in &by_jail {
+                    unban_batched(backend.as_mut(), &mut report, jail, ips);
+                }
+            }
+            for key in targets {
+                if !report.failed.iter().any(|(jail, ip, _)| (jail, ip) == (&key.0, &key.1)) {
+                    state.marked.remove(&key);
+                }
+            }
+            state.journal.record(
+                format!("unban of {} marked IPs", undo.len()),
+                without_failures(undo, &report),
+            );
+            finish_bulk(state, report.summary("Unbanned", "marked IPs"), &report);
+        }
+        Modal::Undo { .. } => {
+            let Some(entry) = state.journal.pop() else {
+                state.modal = None;
+                return;
+            };
+            let report = journal::restore(state.backend().as_mut(), &entry.bans, state.bantime_override);
+            let summary = report.summary(&format!("Undid {}: re-banned", entry.label), "IPs");
+            finish_bulk(state, summary, &report);
+        }
+        Modal::BanIp { .. }
+        | Modal::BanTime { .. }
+        | Modal::BanMarked { .. }
+        | Modal::MoveIp { .. }
+        | Modal::BanList { .. }
+        | Modal::BanSubnet { .. }
+        | Modal::IgnoreList { .. }
+        | Modal::Report { .. }
+        | Modal::Export { .. }
+        | Modal::Lookup { .. }
+        | Modal::Filters { .. }
+        | Modal::Help { .. }
+        | Modal::Audit { .. }
+        | Modal::Palette { .. } => {}
     }
-
-    Ok(false)
 }
 
 fn handle_mouse(mouse: MouseEvent, state: &mut UiState) -> Result<bool> {
@@ -1426,57 +4638,65 @@ fn handle_mouse(mouse: MouseEvent, state: &mut UiState) -> Result<bool> {
     }
 
     if let Some(modal) = state.modal.clone() {
-        if let Some(rect) = state.modal_yes_rect {
-            if point_in_rect(mouse.column, mouse.row, rect) {
-                return handle_modal_key(
-                    KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()),
-                    state,
-                    modal,
-                );
+        let answer = [(state.modal_yes_rect, true), (state.modal_no_rect, false)]
+            .into_iter()
+            .find_map(|(rect, yes)| {
+                rect.filter(|rect| point_in_rect(mouse.column, mouse.row, *rect))
+                    .map(|_| yes)
+            });
+        let Some(yes) = answer else {
+            return Ok(false);
+        };
+        return match modal {
+            Modal::UnbanIp { .. }
+            | Modal::UnbanAll { .. }
+            | Modal::UnbanEverywhere { .. }
+            | Modal::UnbanMarked { .. }
+            | Modal::UnbanIgnore { .. }
+            | Modal::Undo { .. } => {
+                answer_modal(state, modal, yes);
+                Ok(false)
             }
-        }
-        if let Some(rect) = state.modal_no_rect {
-            if point_in_rect(mouse.column, mouse.row, rect) {
-                return handle_modal_key(
-                    KeyEvent::new(KeyCode::Esc, KeyModifiers::empty()),
-                    state,
-                    modal,
-                );
+            Modal::BanList { review: false, .. } => {
+                let code = if yes { KeyCode::Tab } else { KeyCode::Esc };
+                handle_modal_key(KeyEvent::new(code, KeyModifiers::empty()), state, modal)
             }
-        }
+            _ => {
+                let code = if yes { KeyCode::Enter } else { KeyCode::Esc };
+                handle_modal_key(KeyEvent::new(code, KeyModifiers::empty()), state, modal)
+            }
+        };
+    }
+
+    if let Some(rect) = state.jail_rect
+        && let Some(index) =
+            list_index_from_mouse(mouse.column, mouse.row, rect, state.jail_state.offset(), state.jails.len())
+    {
+        state.focus = Focus::Jails;
+        state.jail_state.select(Some(index));
+        state.ip_state.select(Some(0));
         return Ok(false);
     }
 
-    if let Some(rect) = state.jail_rect {
-        if let Some(index) = list_index_from_mouse(mouse.column, mouse.row, rect, state.jails.len()) {
-            state.focus = Focus::Jails;
-            state.jail_state.select(Some(index));
-            state.ip_state.select(Some(0));
-            return Ok(false);
-        }
+    if let Some(&(column, _)) = state
+        .ip_columns
+        .iter()
+        .find(|(_, rect)| point_in_rect(mouse.column, mouse.row, *rect))
+    {
+        state.focus = Focus::Ips;
+        state.sort_by(column);
+        return Ok(false);
     }
 
     if let Some(rect) = state.ip_rect {
-        if let Some(jail_index) = state.jail_state.selected() {
-            let (jail_name, view) = if let Some(jail) = state.jails.get(jail_index) {
-                (jail.name.clone(), current_ip_view(state, jail))
-            } else {
-                return Ok(false);
-            };
-            let maybe_ip = if let Some(index) =
-                list_index_from_mouse(mouse.column, mouse.row, rect, view.len())
-            {
-                state.ip_state.select(Some(index));
-                state.selected_ip(&view).map(|entry| entry.ip.clone())
-            } else {
-                None
-            };
-            if let Some(ip) = maybe_ip {
+        let view_len = state.ip_rows();
+        if let Some(index) =
+            table_index_from_mouse(mouse.column, mouse.row, rect, state.ip_state.offset(), view_len)
+        {
+            state.ip_state.select(Some(index));
+            if let Some((jail, ip)) = state.selected_row() {
                 state.focus = Focus::Ips;
-                state.modal = Some(Modal::UnbanIp {
-                    jail: jail_name,
-                    ip,
-                });
+                state.modal = Some(Modal::UnbanIp { jail, ip });
                 return Ok(false);
             }
         }
@@ -1485,10 +4705,7 @@ fn handle_mouse(mouse: MouseEvent, state: &mut UiState) -> Result<bool> {
     Ok(false)
 }
 
-fn list_index_from_mouse(x: u16, y: u16, area: Rect, len: usize) -> Option<usize> {
-    if len == 0 {
-        return None;
-    }
+fn list_index_from_mouse(x: u16, y: u16, area: Rect, offset: usize, len: usize) -> Option<usize> {
     let inner = Rect {
         x: area.x + 1,
         y: area.y + 1,
@@ -1498,11 +4715,22 @@ fn list_index_from_mouse(x: u16, y: u16, area: Rect, len: usize) -> Option<usize
     if !point_in_rect(x, y, inner) {
         return None;
     }
-    let row = y.saturating_sub(inner.y) as usize;
-    if row >= len {
+    let row = offset + y.saturating_sub(inner.y) as usize;
+    (row < len).then_some(row)
+}
+
+fn table_index_from_mouse(x: u16, y: u16, area: Rect, offset: usize, len: usize) -> Option<usize> {
+    let body = Rect {
+        x: area.x + 1,
+        y: area.y + 2,
+        width: area.width.saturating_sub(2),
+        height: area.height.saturating_sub(3),
+    };
+    if !point_in_rect(x, y, body) {
         return None;
     }
-    Some(row)
+    let row = offset + y.saturating_sub(body.y) as usize;
+    (row < len).then_some(row)
 }
 
 fn point_in_rect(x: u16, y: u16, rect: Rect) -> bool {
@@ -1511,3 +4739,829 @@ fn point_in_rect(x: u16, y: u16, rect: Rect) -> bool {
         && y >= rect.y
         && y < rect.y.saturating_add(rect.height)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::{JailSummary, MemoryBackend};
+    use crossterm::event::MouseButton;
+
+    /// A UI over the demo server with its first snapshot applied. The jails
+    /// come sorted by ban count, so `sshd` (five IPs) is selected.
+    fn demo_state() -> UiState {
+        state_over(MemoryBackend::demo(), Config::default())
+    }
+
+    /// [`demo_state`] over `backend`, with `config`.
+    fn state_over(backend: MemoryBackend, config: Config) -> UiState {
+        let mut state = UiState::new(Box::new(backend), config);
+        refreshed(&mut state);
+        state
+    }
+
+    /// The demo server without per-ban times, like a real fail2ban.
+    fn plain_backend() -> MemoryBackend {
+        MemoryBackend {
+            own_ban_times: false,
+            ..MemoryBackend::demo()
+        }
+    }
+
+    fn refreshed(state: &mut UiState) {
+        state.refresh();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while state.refreshing() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+            state.poll_refresh();
+        }
+        assert!(!state.refreshing(), "refresh did not finish");
+    }
+
+    /// Types `keys`; `\n` is enter, `\t` tab and `\x1b` escape.
+    fn press(state: &mut UiState, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            handle_key(KeyEvent::new(code, KeyModifiers::empty()), state).unwrap();
+        }
+    }
+
+    fn banned(state: &UiState, jail: &str) -> Vec<String> {
+        state.backend().jail_status(jail).unwrap().ips
+    }
+
+    fn click(state: &mut UiState, column: u16, row: u16) {
+        let mouse = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::empty(),
+        };
+        handle_mouse(mouse, state).unwrap();
+    }
+
+    #[test]
+    fn unban_asks_first_and_can_be_undone() {
+        let mut state = demo_state();
+        press(&mut state, "\tu");
+        assert!(matches!(&state.modal, Some(Modal::UnbanIp { ip, .. }) if ip == "192.0.2.9"));
+        assert!(banned(&state, "sshd").contains(&"192.0.2.9".to_string()));
+
+        press(&mut state, "y");
+        assert!(!banned(&state, "sshd").contains(&"192.0.2.9".to_string()));
+        assert_eq!(state.journal.len(), 1);
+
+        refreshed(&mut state);
+        press(&mut state, "z");
+        assert!(matches!(state.modal, Some(Modal::Undo { .. })));
+        press(&mut state, "y");
+        assert!(banned(&state, "sshd").contains(&"192.0.2.9".to_string()));
+    }
+
+    #[test]
+    fn cancelled_unban_keeps_the_ban() {
+        let mut state = demo_state();
+        press(&mut state, "\tun");
+        assert!(state.modal.is_none());
+        assert_eq!(banned(&state, "sshd").len(), 5);
+    }
+
+    #[test]
+    fn ban_dialog_checks_the_ip() {
+        let mut state = demo_state();
+        press(&mut state, "b203.0.113.x\n");
+        assert!(matches!(&state.modal, Some(Modal::BanIp { error: Some(_), .. })));
+
+        press(&mut state, "\x1bb203.0.113.50\n");
+        assert!(state.modal.is_none());
+        assert!(banned(&state, "sshd").contains(&"203.0.113.50".to_string()));
+
+        press(&mut state, "b203.0.113.0/33\n");
+        assert!(matches!(&state.modal, Some(Modal::BanIp { error: Some(_), .. })));
+        press(&mut state, "\x1bb198.51.100.0/24\n");
+        assert!(state.modal.is_none());
+        assert!(banned(&state, "sshd").contains(&"198.51.100.0/24".to_string()));
+    }
+
+    #[test]
+    fn ban_dialog_refuses_times_the_server_cannot_give() {
+        let mut state = state_over(plain_backend(), Config::default());
+        assert!(state.ban_time_problem("sshd", Some(7200)).is_some());
+        assert!(state.ban_time_problem("sshd", Some(3600)).is_none());
+        assert!(state.ban_time_problem("sshd", None).is_none());
+
+        press(&mut state, "b203.0.113.60\t2h\n");
+        let Some(Modal::BanIp { error: Some(error), .. }) = &state.modal else {
+            panic!("the ban went through");
+        };
+        assert!(error.contains("bantime_override"), "{error}");
+        assert!(!banned(&state, "sshd").contains(&"203.0.113.60".to_string()));
+
+        // The jail's own bantime needs no time of its own.
+        press(&mut state, "\x1bb203.0.113.60\t1h\n");
+        assert!(state.modal.is_none());
+        assert_eq!(ban_seconds(state.backend().as_mut(), "sshd", "203.0.113.60"), Some(3600));
+
+        let config = Config {
+            bantime_override: true,
+            ..Config::default()
+        };
+        let mut state = state_over(plain_backend(), config);
+        assert!(state.ban_time_problem("sshd", Some(7200)).is_none());
+        assert!(state.ban_time_problem("sshd", Some(-1)).is_some());
+        press(&mut state, "b203.0.113.61\t2h\n");
+        assert!(state.modal.is_none());
+        let kept = ban_seconds(state.backend().as_mut(), "sshd", "203.0.113.61").unwrap();
+        assert!((7200..7205).contains(&kept), "{kept}");
+    }
+
+    #[test]
+    fn unban_all_needs_two_confirmations() {
+        let mut state = demo_state();
+        press(&mut state, "Ay");
+        assert!(matches!(state.modal, Some(Modal::UnbanAll { step: 2, .. })));
+        assert_eq!(banned(&state, "sshd").len(), 5);
+        press(&mut state, "y");
+        assert!(banned(&state, "sshd").is_empty());
+    }
+
+    #[test]
+    fn a_rejected_unban_batch_only_retries_what_is_still_banned() {
+        let mut backend = MemoryBackend::demo();
+        let mut report = BulkReport::default();
+        // The demo server, like fail2ban, stops at the IP that isn't banned.
+        let ips = ["192.0.2.9", "203.0.113.99", "192.0.2.14"];
+        unban_batched(&mut backend, &mut report, "sshd", &ips);
+        assert_eq!(report.done, 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].1, "203.0.113.99");
+        let left = backend.jail_status("sshd").unwrap().ips;
+        assert!(!left.iter().any(|ip| ip == "192.0.2.9" || ip == "192.0.2.14"));
+    }
+
+    fn ban_seconds(backend: &mut dyn Fail2banBackend, jail: &str, ip: &str) -> Option<i64> {
+        let output = backend.get(jail, &["banip", "--with-time"]).unwrap();
+        let entry = parse_banip_with_time(&output, None).into_iter().find(|e| e.ip == ip)?;
+        parse_ban_seconds(entry.time_raw.as_deref()?)
+    }
+
+    #[test]
+    fn custom_ban_times_outlast_the_jail_bantime() {
+        let mut backend = MemoryBackend::demo();
+        let mut report = BulkReport::default();
+        ban_for(&mut backend, &mut report, "postfix", &["203.0.113.50"], Some(7200), false);
+        ban_for(&mut backend, &mut report, "postfix", &["203.0.113.51"], Some(-1), false);
+        assert_eq!(report.done, 2);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(backend.get("postfix", &["bantime"]).unwrap(), "600");
+
+        // Bans without a time of their own would follow this change.
+        backend.set("postfix", &["bantime", "60"]).unwrap();
+        let kept = ban_seconds(&mut backend, "postfix", "203.0.113.50").unwrap();
+        assert!((7200..7205).contains(&kept), "{kept}");
+        assert_eq!(ban_seconds(&mut backend, "postfix", "203.0.113.51"), Some(-1));
+    }
+
+    #[test]
+    fn the_jail_bantime_is_only_changed_when_allowed() {
+        let mut backend = MemoryBackend::demo();
+        backend.own_ban_times = false;
+        let mut report = BulkReport::default();
+        ban_for(&mut backend, &mut report, "postfix", &["203.0.113.50"], Some(7200), false);
+        assert_eq!(report.done, 1);
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+        assert_eq!(ban_seconds(&mut backend, "postfix", "203.0.113.50"), Some(600));
+
+        let mut report = BulkReport::default();
+        ban_for(&mut backend, &mut report, "postfix", &["203.0.113.51"], Some(7200), true);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let kept = ban_seconds(&mut backend, "postfix", "203.0.113.51").unwrap();
+        assert!((7200..7205).contains(&kept), "{kept}");
+        assert_eq!(backend.get("postfix", &["bantime"]).unwrap(), "600");
+
+        // A permanent ban is never made by setting the jail's bantime to -1.
+        let mut report = BulkReport::default();
+        ban_for(&mut backend, &mut report, "postfix", &["203.0.113.52"], Some(-1), true);
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+        assert_eq!(ban_seconds(&mut backend, "postfix", "203.0.113.52"), Some(600));
+        assert_eq!(backend.get("postfix", &["bantime"]).unwrap(), "600");
+
+        // A jail that already bans for good needs no override.
+        backend.set("recidive", &["bantime", "-1"]).unwrap();
+        let mut report = BulkReport::default();
+        ban_for(&mut backend, &mut report, "recidive", &["203.0.113.53"], Some(-1), false);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert_eq!(ban_seconds(&mut backend, "recidive", "203.0.113.53"), Some(-1));
+    }
+
+    #[test]
+    fn undo_restores_a_whole_unban_all() {
+        let mut state = demo_state();
+        let left: Vec<(String, i64)> = state.jails[0]
+            .ips
+            .iter()
+            .map(|entry| (entry.ip.clone(), remaining_seconds(entry.end_epoch).unwrap() as i64))
+            .collect();
+        assert_eq!(left.len(), 5);
+        press(&mut state, "Ayy");
+        assert!(banned(&state, "sshd").is_empty());
+
+        press(&mut state, "z");
+        assert!(matches!(state.modal, Some(Modal::Undo { count: 5, .. })));
+        assert_eq!(undo_time_note(&state), "The time each ban had left is restored.");
+        press(&mut state, "y");
+        assert_eq!(banned(&state, "sshd").len(), 5);
+        for (ip, left) in left {
+            let kept = ban_seconds(state.backend().as_mut(), "sshd", &ip).unwrap();
+            assert!((left..left + 61).contains(&kept), "{ip}: {kept} for {left}");
+        }
+        assert_eq!(state.journal.len(), 0);
+    }
+
+    #[test]
+    fn undo_says_when_the_times_cannot_be_restored() {
+        let mut state = state_over(plain_backend(), Config::default());
+        press(&mut state, "Ayyz");
+        assert!(undo_time_note(&state).contains("can't be restored"), "{}", undo_time_note(&state));
+        press(&mut state, "y");
+        assert_eq!(banned(&state, "sshd").len(), 5);
+        assert_eq!(ban_seconds(state.backend().as_mut(), "sshd", "192.0.2.9"), Some(3600));
+    }
+
+    #[test]
+    fn new_ban_ends_move_or_replace_the_old_one() {
+        let now = 1_800_000_000;
+        let end = Some(now + 600);
+        assert_eq!(new_ban_end("+1h", end, now), Ok(now + 4200));
+        assert_eq!(new_ban_end("-5m", end, now), Ok(now + 300));
+        assert_eq!(new_ban_end("2h", end, now), Ok(now + 7200));
+        assert_eq!(new_ban_end(" 90 ", None, now), Ok(now + 90));
+        assert_eq!(new_ban_end("permanent", end, now), Ok(PERMANENT_END_EPOCH));
+        assert_eq!(end_bantime(PERMANENT_END_EPOCH, now), -1);
+        assert_eq!(end_bantime(now + 300, now), 300);
+
+        assert!(new_ban_end("-10m", end, now).unwrap_err().contains("past"));
+        assert!(new_ban_end("-1h", end, now).unwrap_err().contains("past"));
+        assert!(new_ban_end("+permanent", end, now).is_err());
+        assert!(new_ban_end("+1h", None, now).is_err());
+        assert!(new_ban_end("+1h", Some(PERMANENT_END_EPOCH), now).is_err());
+        assert!(new_ban_end("", end, now).is_err());
+        assert!(new_ban_end("soon", end, now).is_err());
+    }
+
+    #[test]
+    fn reban_refuses_times_the_server_cannot_give_before_unbanning() {
+        let mut backend = plain_backend();
+        let ip = "192.0.2.9";
+        let err = reban_for(&mut backend, "sshd", ip, 7200, None, false).unwrap_err();
+        assert!(format!("{err}").contains("bantime_override"), "{err}");
+        assert!(reban_for(&mut backend, "sshd", ip, -1, None, true).is_err());
+        assert_eq!(ban_seconds(&mut backend, "sshd", ip), Some(3600));
+
+        // The jail's bantime is always there to be had.
+        assert_eq!(reban_for(&mut backend, "sshd", ip, 3600, None, false).unwrap(), Vec::<String>::new());
+        assert_eq!(ban_seconds(&mut backend, "sshd", ip), Some(3600));
+
+        assert_eq!(reban_for(&mut backend, "sshd", ip, 7200, None, true).unwrap(), Vec::<String>::new());
+        let kept = ban_seconds(&mut backend, "sshd", ip).unwrap();
+        assert!((7200..7205).contains(&kept), "{kept}");
+        assert_eq!(backend.get("sshd", &["bantime"]).unwrap(), "3600");
+    }
+
+    #[test]
+    fn ban_time_dialog_keeps_the_ban_when_the_time_cannot_be_given() {
+        let mut state = state_over(plain_backend(), Config::default());
+        press(&mut state, "\td");
+        assert!(matches!(&state.modal, Some(Modal::BanTime { ip, .. }) if ip == "192.0.2.9"));
+        press(&mut state, "permanent\n");
+        assert!(matches!(&state.modal, Some(Modal::BanTime { error: Some(_), .. })));
+        assert!(banned(&state, "sshd").contains(&"192.0.2.9".to_string()));
+        assert_eq!(ban_seconds(state.backend().as_mut(), "sshd", "192.0.2.9"), Some(3600));
+    }
+
+    /// A server that keeps no per-ban time: banning a banned IP does nothing.
+    struct NoOwnTimes(MemoryBackend);
+
+    impl Fail2banBackend for NoOwnTimes {
+        fn jails(&mut self) -> Result<Vec<String>> {
+            self.0.jails()
+        }
+        fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+            self.0.jail_status(jail)
+        }
+        fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+            self.0.get(jail, args)
+        }
+        fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+            self.0.set(jail, args)
+        }
+        fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+            let held = self.0.jail_status(jail)?.ips;
+            let new: Vec<&str> = ips.iter().copied().filter(|ip| !held.iter().any(|h| h == ip)).collect();
+            self.0.ban(jail, &new)
+        }
+        fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+            self.0.unban(jail, ips)
+        }
+        fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+            self.0.banned_in(ip)
+        }
+    }
+
+    /// The demo server with unbans failing in some jails, and optionally
+    /// without `banned <ip>`, like an old fail2ban.
+    struct Flaky {
+        inner: MemoryBackend,
+        unbans_fail_in: Vec<&'static str>,
+        no_lookup: bool,
+    }
+
+    impl Fail2banBackend for Flaky {
+        fn jails(&mut self) -> Result<Vec<String>> {
+            self.inner.jails()
+        }
+        fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+            self.inner.jail_status(jail)
+        }
+        fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+            self.inner.get(jail, args)
+        }
+        fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+            self.inner.set(jail, args)
+        }
+        fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+            self.inner.ban(jail, ips)
+        }
+        fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+            if self.unbans_fail_in.contains(&jail) {
+                return Err(anyhow!("connection lost"));
+            }
+            self.inner.unban(jail, ips)
+        }
+        fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+            if self.no_lookup {
+                return Err(anyhow!("Invalid command 'banned'"));
+            }
+            self.inner.banned_in(ip)
+        }
+    }
+
+    fn failing_unbans(jails: &[&'static str]) -> Flaky {
+        Flaky {
+            inner: MemoryBackend::demo(),
+            unbans_fail_in: jails.to_vec(),
+            no_lookup: false,
+        }
+    }
+
+    #[test]
+    fn whereis_falls_back_to_the_snapshot() {
+        let state = demo_state();
+        let mut backend = Flaky {
+            no_lookup: true,
+            ..failing_unbans(&[])
+        };
+        // The server knows of a ban the snapshot doesn't have yet.
+        backend.inner.ban("recidive", &["198.51.100.23"]).unwrap();
+        let found = locate_ip(&mut backend, &state.jails, "198.51.100.23");
+        let jails: Vec<&str> = found.iter().map(|m| m.jail.as_str()).collect();
+        assert_eq!(jails, ["sshd", "nginx-http-auth"]);
+        assert!(found.iter().all(|m| m.end_epoch.is_some()));
+
+        backend.no_lookup = false;
+        let found = locate_ip(&mut backend, &state.jails, "198.51.100.23");
+        let jails: Vec<&str> = found.iter().map(|m| m.jail.as_str()).collect();
+        assert_eq!(jails, ["sshd", "nginx-http-auth", "recidive"]);
+        assert_eq!(found[2].end_epoch, None);
+        assert!(locate_ip(&mut backend, &state.jails, "198.18.0.1").is_empty());
+    }
+
+    #[test]
+    fn whereis_lists_the_jails_and_unbans_everywhere() {
+        let mut state = demo_state();
+        press(&mut state, "\tjjjw");
+        assert!(matches!(&state.modal, Some(Modal::Lookup { input, .. }) if input == "198.51.100.23"));
+        press(&mut state, "\n");
+        let Some(Modal::Lookup { matches: Some(matches), .. }) = &state.modal else {
+            panic!("no lookup result");
+        };
+        assert_eq!(matches.len(), 2);
+        press(&mut state, "\n");
+        assert!(matches!(&state.modal, Some(Modal::UnbanEverywhere { jails, .. }) if jails.len() == 2));
+        press(&mut state, "y");
+        assert!(!banned(&state, "sshd").contains(&"198.51.100.23".to_string()));
+        assert!(!banned(&state, "nginx-http-auth").contains(&"198.51.100.23".to_string()));
+    }
+
+    #[test]
+    fn move_ip_takes_the_new_ban_back_when_the_unban_fails() {
+        let mut backend = failing_unbans(&[]);
+        assert!(move_ip(&mut backend, "sshd", "postfix", "192.0.2.9", None, false, false).is_ok());
+        assert_eq!(backend.banned_in("192.0.2.9").unwrap(), ["postfix"]);
+        assert!(move_ip(&mut backend, "postfix", "recidive", "192.0.2.9", None, true, false).is_ok());
+        assert_eq!(backend.banned_in("192.0.2.9").unwrap(), ["recidive", "postfix"]);
+
+        let mut backend = failing_unbans(&["sshd"]);
+        let err = move_ip(&mut backend, "sshd", "postfix", "192.0.2.9", None, false, false).unwrap_err();
+        assert!(format!("{err}").contains("taken back"), "{err}");
+        assert_eq!(backend.banned_in("192.0.2.9").unwrap(), ["sshd"]);
+    }
+
+    #[test]
+    fn move_ip_keeps_a_ban_the_target_already_had() {
+        let mut backend = failing_unbans(&["sshd"]);
+        let err = move_ip(&mut backend, "sshd", "nginx-http-auth", "198.51.100.23", None, false, false).unwrap_err();
+        assert!(!format!("{err}").contains("taken back"), "{err}");
+        assert_eq!(backend.banned_in("198.51.100.23").unwrap(), ["sshd", "nginx-http-auth"]);
+    }
+
+    #[test]
+    fn move_ip_reports_a_failed_rollback() {
+        let mut backend = failing_unbans(&["sshd", "postfix"]);
+        let err = move_ip(&mut backend, "sshd", "postfix", "192.0.2.9", None, false, false).unwrap_err();
+        assert!(format!("{err}").contains("failed too"), "{err}");
+        assert_eq!(backend.banned_in("192.0.2.9").unwrap(), ["sshd", "postfix"]);
+    }
+
+    #[test]
+    fn move_dialog_only_keeps_times_the_server_can_give() {
+        let mut state = demo_state();
+        press(&mut state, "\tm");
+        assert!(matches!(state.modal, Some(Modal::MoveIp { keep_time: true, .. })));
+
+        let mut state = state_over(plain_backend(), Config::default());
+        press(&mut state, "\tm");
+        assert!(matches!(state.modal, Some(Modal::MoveIp { keep_time: false, .. })));
+    }
+
+    #[test]
+    fn bans_that_fall_back_to_the_jail_bantime_are_warned_about() {
+        let mut backend = NoOwnTimes(MemoryBackend::demo());
+        let mut report = BulkReport::default();
+        ban_for(&mut backend, &mut report, "postfix", &["203.0.113.50"], Some(7200), true);
+        assert_eq!(report.done, 1);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("203.0.113.50"), "{:?}", report.warnings);
+        assert_eq!(ban_seconds(&mut backend, "postfix", "203.0.113.50"), Some(600));
+        assert_eq!(backend.get("postfix", &["bantime"]).unwrap(), "600");
+    }
+
+    #[test]
+    fn unban_marked_keeps_partly_failed_batches_undoable() {
+        let mut state = demo_state();
+        press(&mut state, "\t  ");
+        assert_eq!(state.marked.len(), 2);
+        // The first marked IP expires before the unban is sent.
+        state.backend().unban("sshd", &["192.0.2.9"]).unwrap();
+        press(&mut state, "uy");
+        assert!(!banned(&state, "sshd").contains(&"192.0.2.14".to_string()));
+        let entry = state.journal.last().unwrap();
+        assert_eq!(entry.bans.len(), 1);
+        assert_eq!(entry.bans[0].ip, "192.0.2.14");
+        assert!(matches!(state.modal, Some(Modal::Report { .. })));
+    }
+
+    #[test]
+    fn jail_pickers_know_the_ignore_lists() {
+        let mut state = demo_state();
+        state.backend().set("postfix", &["addignoreip", "192.0.2.0/28"]).unwrap();
+        press(&mut state, "\t  b");
+        assert!(matches!(state.modal, Some(Modal::BanMarked { .. })));
+        assert_eq!(state.ignored("postfix", "192.0.2.9"), Some("192.0.2.0/28"));
+        assert_eq!(state.ignored("postfix", "192.0.2.0/24"), Some("192.0.2.0/28"));
+        assert_eq!(state.ignored("sshd", "192.0.2.0/24"), None);
+        assert_eq!(state.ignored("sshd", "127.0.0.0/30"), Some("127.0.0.1/8"));
+    }
+
+    #[test]
+    fn jail_pickers_scroll_to_the_selection() {
+        let names: Vec<String> = (0..12).map(|i| format!("jail{i}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let text = |selected| -> Vec<String> {
+            picker_lines(&names, selected, &Theme::builtin("mono").unwrap())
+                .iter()
+                .map(|line| line.to_string())
+                .collect()
+        };
+        let top = text(2);
+        assert_eq!(top.len(), PICKER_ROWS + 1);
+        assert!(top.contains(&"> jail2".to_string()));
+        assert_eq!(top.last().unwrap(), "4 more below");
+
+        let bottom = text(11);
+        assert_eq!(bottom.first().unwrap(), "4 more above");
+        assert_eq!(bottom.last().unwrap(), "> jail11");
+    }
+
+    #[test]
+    fn ignore_list_scrolls_and_asks_before_removing() {
+        let mut state = demo_state();
+        for i in 0..12 {
+            state.backend().set("sshd", &["addignoreip", &format!("198.18.0.{i}")]).unwrap();
+        }
+        press(&mut state, "i");
+        press(&mut state, &"j".repeat(13));
+        let Some(Modal::IgnoreList { ref entries, selected, .. }) = state.modal else {
+            panic!("ignore list not open");
+        };
+        assert_eq!(selected, entries.len() - 1);
+        let names: Vec<&str> = entries.iter().map(String::as_str).collect();
+        let lines = window_lines(&names, selected, true, &state.theme);
+        assert_eq!(lines.len(), PICKER_ROWS + 1);
+        assert_eq!(lines.last().unwrap().to_string(), "> 198.18.0.11");
+
+        press(&mut state, "dn");
+        assert!(matches!(state.modal, Some(Modal::IgnoreList { removing: false, .. })));
+        assert!(state.load_ignore_list("sshd").unwrap().contains(&"198.18.0.11".to_string()));
+        press(&mut state, "dy");
+        assert!(matches!(state.modal, Some(Modal::IgnoreList { removing: false, .. })));
+        assert!(!state.load_ignore_list("sshd").unwrap().contains(&"198.18.0.11".to_string()));
+    }
+
+    #[test]
+    fn filter_narrows_the_ip_view() {
+        let mut state = demo_state();
+        press(&mut state, "/198.51");
+        assert!(state.search_mode);
+        press(&mut state, "\n");
+        assert!(!state.search_mode);
+        let view = current_ip_view(&state, &state.jails);
+        assert_eq!(view.len(), 1);
+        assert_eq!(view[0].entry.ip, "198.51.100.23");
+
+        press(&mut state, "/ left<");
+        assert!(state.filter_error.is_some());
+        press(&mut state, "\n");
+        assert!(state.search_mode, "enter keeps the prompt open while the filter is invalid");
+    }
+
+    #[test]
+    fn refresh_keeps_the_selection_and_scroll_position() {
+        let mut state = demo_state();
+        press(&mut state, "\tjj");
+        assert_eq!(state.selected_row(), Some(("sshd".to_string(), "192.0.2.100".to_string())));
+        *state.jail_state.offset_mut() = 1;
+        *state.ip_state.offset_mut() = 1;
+
+        // The selected IP goes and nginx-http-auth overtakes sshd in the jail order.
+        state.backend().unban("sshd", &["192.0.2.100"]).unwrap();
+        let new = ["203.0.113.1", "203.0.113.2", "203.0.113.3", "203.0.113.4"];
+        state.backend().ban("nginx-http-auth", &new).unwrap();
+        refreshed(&mut state);
+        assert_eq!(state.jails[0].name, "nginx-http-auth");
+        assert_eq!(state.selected_jail().unwrap().name, "sshd");
+        assert_eq!(state.selected_row(), Some(("sshd".to_string(), "198.51.100.23".to_string())));
+        assert_eq!(state.jail_state.offset(), 1);
+        assert_eq!(state.ip_state.offset(), 1);
+
+        // The nearest IP before it is taken when nothing after it is left.
+        press(&mut state, "j");
+        state.backend().unban("sshd", &["2001:db8::17", "198.51.100.23"]).unwrap();
+        refreshed(&mut state);
+        assert_eq!(state.selected_row(), Some(("sshd".to_string(), "192.0.2.14".to_string())));
+    }
+
+    fn group_sizes(state: &UiState) -> Vec<(String, usize)> {
+        let view = current_ip_view(state, &state.jails);
+        subnet_groups(state, &view)
+            .iter()
+            .map(|group| (group.cidr.to_string(), group.rows.len()))
+            .collect()
+    }
+
+    #[test]
+    fn refresh_marks_new_bans_and_lists_released_ones() {
+        let mut state = demo_state();
+        assert_eq!(state.last_diff, None);
+        assert!(state.fresh.is_empty());
+
+        state.backend().ban("sshd", &["203.0.113.9"]).unwrap();
+        state.backend().unban("sshd", &["192.0.2.9"]).unwrap();
+        refreshed(&mut state);
+        assert_eq!(state.last_diff, Some((1, 1)));
+        assert!(state.is_fresh("sshd", "203.0.113.9"));
+        assert!(!state.is_fresh("sshd", "192.0.2.14"));
+        let released: Vec<(&str, &str)> = state.released.iter().map(|r| (r.jail.as_str(), r.ip.as_str())).collect();
+        assert_eq!(released, [("sshd", "192.0.2.9")]);
+
+        // A new ban stays marked for a few refreshes, a released IP until it is banned again.
+        state.backend().ban("sshd", &["192.0.2.9"]).unwrap();
+        refreshed(&mut state);
+        assert_eq!(state.last_diff, Some((1, 0)));
+        assert!(state.released.is_empty());
+        for _ in 1..FRESH_CYCLES {
+            assert!(state.is_fresh("sshd", "203.0.113.9"));
+            refreshed(&mut state);
+        }
+        assert!(!state.is_fresh("sshd", "203.0.113.9"));
+        assert!(state.is_fresh("sshd", "192.0.2.9"));
+        assert_eq!(state.last_diff, Some((0, 0)));
+    }
+
+    /// Selects the row of `ip` in `jail` in the IP panel.
+    fn select_row(state: &mut UiState, jail: &str, ip: &str) {
+        let index = current_ip_view(state, &state.jails)
+            .iter()
+            .position(|row| row.jail == jail && row.entry.ip == ip)
+            .unwrap();
+        state.focus = Focus::Ips;
+        state.ip_state.select(Some(index));
+    }
+
+    #[test]
+    fn all_jails_view_unbans_in_the_rows_own_jail() {
+        let mut state = demo_state();
+        press(&mut state, "g");
+        let rows: Vec<(&str, &str)> = current_ip_view(&state, &state.jails)
+            .iter()
+            .map(|row| (row.jail, row.entry.ip.as_str()))
+            .collect();
+        assert_eq!(rows.len(), 8);
+        assert!(rows.contains(&("recidive", "203.0.113.200")));
+        assert_eq!(rows.iter().filter(|(_, ip)| *ip == "198.51.100.23").count(), 2);
+
+        select_row(&mut state, "nginx-http-auth", "198.51.100.23");
+        press(&mut state, "u");
+        assert!(matches!(&state.modal, Some(Modal::UnbanIp { jail, .. }) if jail == "nginx-http-auth"));
+        press(&mut state, "y");
+        assert!(!banned(&state, "nginx-http-auth").contains(&"198.51.100.23".to_string()));
+        assert!(banned(&state, "sshd").contains(&"198.51.100.23".to_string()));
+
+        // The filter and the jail column work across jails too.
+        press(&mut state, "/jail:recidive\n");
+        let view = current_ip_view(&state, &state.jails);
+        assert_eq!(view.len(), 1);
+        assert_eq!(view[0].entry.ip, "203.0.113.200");
+    }
+
+    #[test]
+    fn all_jails_view_can_unban_an_ip_everywhere() {
+        let mut state = demo_state();
+        state.backend().ban("recidive", &["198.51.100.23"]).unwrap();
+        refreshed(&mut state);
+        press(&mut state, "g");
+        select_row(&mut state, "sshd", "198.51.100.23");
+        press(&mut state, "U");
+        let Some(Modal::UnbanEverywhere { jails, .. }) = &state.modal else {
+            panic!("no unban everywhere dialog");
+        };
+        assert_eq!(jails.len(), 3);
+        press(&mut state, "y");
+        for jail in ["sshd", "nginx-http-auth", "recidive"] {
+            assert!(!banned(&state, jail).contains(&"198.51.100.23".to_string()), "{jail}");
+        }
+        assert_eq!(banned(&state, "sshd").len(), 4);
+    }
+
+    fn view_ips(state: &UiState) -> Vec<String> {
+        current_ip_view(state, &state.jails)
+            .iter()
+            .map(|row| row.entry.ip.clone())
+            .collect()
+    }
+
+    #[test]
+    fn columns_sort_both_ways_and_keep_the_selection() {
+        let mut state = demo_state();
+        assert_eq!(
+            view_ips(&state),
+            ["192.0.2.9", "192.0.2.14", "192.0.2.100", "198.51.100.23", "2001:db8::17"]
+        );
+        select_row(&mut state, "sshd", "192.0.2.100");
+
+        state.sort_by(Column::Left);
+        assert!(!state.sort_desc);
+        assert_eq!(
+            view_ips(&state),
+            ["198.51.100.23", "192.0.2.100", "2001:db8::17", "192.0.2.9", "192.0.2.14"]
+        );
+        assert_eq!(state.selected_row(), Some(("sshd".to_string(), "192.0.2.100".to_string())));
+
+        state.sort_by(Column::Left);
+        assert!(state.sort_desc);
+        assert_eq!(
+            view_ips(&state),
+            ["192.0.2.14", "192.0.2.9", "2001:db8::17", "192.0.2.100", "198.51.100.23"]
+        );
+        assert_eq!(state.selected_ip_index(), 3);
+
+        // Another column starts ascending again; the oldest ban started first.
+        state.sort_by(Column::Started);
+        assert!(!state.sort_desc);
+        assert_eq!(view_ips(&state)[0], "198.51.100.23");
+        assert_eq!(state.config().sort, "started");
+    }
+
+    #[test]
+    fn sort_key_cycles_through_the_visible_columns() {
+        let mut state = demo_state();
+        assert!(!state.sortable_columns().contains(&Column::Jail));
+        press(&mut state, "\ts");
+        assert_eq!(state.sort_column, Column::Left);
+        press(&mut state, "S");
+        assert!(state.sort_desc);
+
+        press(&mut state, "g");
+        assert!(state.sortable_columns().contains(&Column::Jail));
+        state.sort_by(Column::Jail);
+        let jails: Vec<&str> = current_ip_view(&state, &state.jails).iter().map(|row| row.jail).collect();
+        assert_eq!(jails.first(), Some(&"nginx-http-auth"));
+        assert_eq!(jails.last(), Some(&"sshd"));
+    }
+
+    #[test]
+    fn subnet_view_groups_by_the_configured_prefixes() {
+        let mut state = demo_state();
+        let sizes = |pairs: &[(&str, usize)]| -> Vec<(String, usize)> {
+            pairs.iter().map(|(cidr, n)| (cidr.to_string(), *n)).collect()
+        };
+        assert_eq!(
+            group_sizes(&state),
+            sizes(&[("192.0.2.0/24", 3), ("198.51.100.0/24", 1), ("2001:db8::/64", 1)])
+        );
+        state.group_prefix = (28, 16);
+        assert_eq!(
+            group_sizes(&state),
+            sizes(&[("192.0.2.0/28", 2), ("192.0.2.96/28", 1), ("198.51.100.16/28", 1), ("2001::/16", 1)])
+        );
+    }
+
+    #[test]
+    fn subnet_ban_unbans_only_the_ips_it_covers() {
+        let mut state = demo_state();
+        press(&mut state, "\tcb");
+        assert!(matches!(state.modal, Some(Modal::BanSubnet { unban: true, .. })));
+        press(&mut state, "\n");
+        let mut left = banned(&state, "sshd");
+        left.sort();
+        assert_eq!(left, ["192.0.2.0/24", "198.51.100.23", "2001:db8::17"]);
+        assert_eq!(banned(&state, "nginx-http-auth"), ["203.0.113.7", "198.51.100.23"]);
+        assert_eq!(state.journal.last().unwrap().bans.len(), 3);
+    }
+
+    #[test]
+    fn subnet_ban_can_keep_the_single_ips() {
+        let mut state = demo_state();
+        press(&mut state, "\tcbu");
+        assert!(matches!(state.modal, Some(Modal::BanSubnet { unban: false, .. })));
+        press(&mut state, "\n");
+        assert_eq!(banned(&state, "sshd").len(), 6);
+        assert!(banned(&state, "sshd").contains(&"192.0.2.0/24".to_string()));
+        assert!(state.journal.last().is_none());
+    }
+
+    #[test]
+    fn mouse_follows_the_jail_list_scroll_position() {
+        let mut state = demo_state();
+        state.jail_rect = Some(Rect::new(0, 0, 20, 4));
+        *state.jail_state.offset_mut() = 2;
+        click(&mut state, 2, 1);
+        assert_eq!(state.selected_jail().unwrap().name, "recidive");
+        click(&mut state, 2, 2);
+        assert_eq!(state.selected_jail().unwrap().name, "postfix");
+        assert_eq!(list_index_from_mouse(2, 1, Rect::new(0, 0, 20, 4), 4, 4), None);
+    }
+
+    #[test]
+    fn mouse_selects_a_jail_and_answers_dialogs() {
+        let mut state = demo_state();
+        state.jail_rect = Some(Rect::new(0, 0, 20, 10));
+        click(&mut state, 2, 2);
+        assert_eq!(state.selected_jail().unwrap().name, "nginx-http-auth");
+
+        state.focus = Focus::Ips;
+        press(&mut state, "u");
+        state.modal_yes_rect = Some(Rect::new(0, 20, 10, 1));
+        state.modal_no_rect = Some(Rect::new(10, 20, 10, 1));
+        click(&mut state, 12, 20);
+        assert!(state.modal.is_none());
+        assert_eq!(banned(&state, "nginx-http-auth").len(), 2);
+    }
+    #[test]
+    fn export_asks_before_replacing_a_file() {
+        let path = std::env::temp_dir().join(format!("f2bs-{}-tui-export.json", std::process::id()));
+        std::fs::write(&path, "keep me").unwrap();
+        let mut state = demo_state();
+        state.modal = Some(Modal::Export {
+            input: path.display().to_string(),
+            scope: ExportScope::All,
+            error: None,
+            overwrite: false,
+        });
+        press(&mut state, "\n");
+        assert!(matches!(&state.modal, Some(Modal::Export { overwrite: true, error: Some(error), .. })
+            if error.contains("already exists")));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+
+        press(&mut state, "\t");
+        assert!(matches!(state.modal, Some(Modal::Export { overwrite: false, .. })));
+        press(&mut state, "\n\n");
+        assert!(state.modal.is_none());
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"schema_version\""));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Orders addresses numerically with every IPv4 address before any IPv6
+/// address.
+pub fn compare_addrs(a: &IpAddr, b: &IpAddr) -> Ordering {
+    addr_key(a).cmp(&addr_key(b))
+}
+
+fn addr_key(addr: &IpAddr) -> (u8, u128) {
+    match addr {
+        IpAddr::V4(v4) => (4, u32::from(*v4) as u128),
+        IpAddr::V6(v6) => (6, u128::from(*v6)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Parses `addr/prefix`. The host bits of `addr` are cleared, so equal
+    /// blocks compare and sort equal.
+    pub fn parse(input: &str) -> Option<Self> {
+        let (addr, prefix) = input.split_once('/')?;
+        let addr: IpAddr = addr.trim().parse().ok()?;
+        let prefix: u8 = prefix.trim().parse().ok()?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        (prefix <= max).then(|| Self::enclosing(addr, prefix))
+    }
+
+    /// The block of `prefix` bits that holds `addr`. The prefix is capped at
+    /// the address length.
+    pub fn enclosing(addr: IpAddr, prefix: u8) -> Self {
+        match addr {
+            IpAddr::V4(v4) => {
+                let prefix = prefix.min(32);
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                Self {
+                    addr: IpAddr::V4((u32::from(v4) & mask).into()),
+                    prefix,
+                }
+            }
+            IpAddr::V6(v6) => {
+                let prefix = prefix.min(128);
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                Self {
+                    addr: IpAddr::V6((u128::from(v6) & mask).into()),
+                    prefix,
+                }
+            }
+        }
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the two blocks share an address: one of them holds the other.
+    pub fn overlaps(&self, other: &Cidr) -> bool {
+        self.contains(&other.addr) || other.contains(&self.addr)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// A bare IP term: a substring, a CIDR block or an `a-b` range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpPattern {
+    Text(String),
+    Cidr(Cidr),
+    Range(IpAddr, IpAddr),
+}
+
+impl IpPattern {
+    pub fn parse(token: &str) -> Self {
+        if let Some(cidr) = Cidr::parse(token) {
+            return Self::Cidr(cidr);
+        }
+        if let Some((start, end)) = token.split_once('-')
+            && let (Ok(start), Ok(end)) = (start.parse::<IpAddr>(), end.parse::<IpAddr>())
+            && start.is_ipv4() == end.is_ipv4()
+        {
+            return if compare_addrs(&start, &end) == Ordering::Greater {
+                Self::Range(end, start)
+            } else {
+                Self::Range(start, end)
+            };
+        }
+        Self::Text(token.to_lowercase())
+    }
+
+    pub fn matches(&self, ip: &str, addr: Option<&IpAddr>) -> bool {
+        match (self, addr) {
+            (Self::Text(text), _) => ip.to_lowercase().contains(text.as_str()),
+            (Self::Cidr(cidr), Some(addr)) => cidr.contains(addr),
+            (Self::Range(start, end), Some(addr)) => {
+                start.is_ipv4() == addr.is_ipv4()
+                    && compare_addrs(start, addr) != Ordering::Greater
+                    && compare_addrs(addr, end) != Ordering::Greater
+            }
+            (_, None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    fn cidr(text: &str) -> Cidr {
+        Cidr::parse(text).unwrap()
+    }
+
+    #[test]
+    fn sorts_numerically_with_ipv4_first() {
+        let mut addrs = vec![ip("2001:db8::1"), ip("192.0.2.10"), ip("::1"), ip("192.0.2.9"), ip("10.0.0.1")];
+        addrs.sort_by(compare_addrs);
+        assert_eq!(addrs, [ip("10.0.0.1"), ip("192.0.2.9"), ip("192.0.2.10"), ip("::1"), ip("2001:db8::1")]);
+        // An IPv4-mapped IPv6 address is still IPv6.
+        assert_eq!(compare_addrs(&ip("::ffff:0.0.0.1"), &ip("255.255.255.255")), Ordering::Greater);
+    }
+
+    #[test]
+    fn parses_cidr_blocks() {
+        assert_eq!(cidr("192.0.2.0/24").to_string(), "192.0.2.0/24");
+        assert_eq!(cidr(" 2001:db8::/32 ").to_string(), "2001:db8::/32");
+        assert_eq!(cidr("0.0.0.0/0").to_string(), "0.0.0.0/0");
+        assert!(Cidr::parse("192.0.2.0/33").is_none());
+        assert!(Cidr::parse("2001:db8::/129").is_none());
+        assert!(Cidr::parse("192.0.2.0").is_none());
+        assert!(Cidr::parse("192.0.2.0/").is_none());
+        assert!(Cidr::parse("192.0.2.0/-1").is_none());
+        assert!(Cidr::parse("example.com/24").is_none());
+    }
+
+    #[test]
+    fn parsing_clears_the_host_bits() {
+        assert_eq!(cidr("192.0.2.77/24"), cidr("192.0.2.0/24"));
+        assert_eq!(cidr("192.0.2.77/24").to_string(), "192.0.2.0/24");
+        assert_eq!(cidr("192.0.2.77/24"), Cidr::enclosing(ip("192.0.2.77"), 24));
+        assert_eq!(cidr("2001:db8::1:5/64"), cidr("2001:db8::/64"));
+        assert_eq!(cidr("192.0.2.77/32").to_string(), "192.0.2.77/32");
+        assert_eq!(cidr("203.0.113.9/0"), cidr("0.0.0.0/0"));
+
+        let mut blocks = vec![cidr("192.0.2.200/24"), cidr("10.0.0.1/8"), cidr("192.0.2.7/24")];
+        blocks.sort();
+        blocks.dedup();
+        assert_eq!(blocks, [cidr("10.0.0.0/8"), cidr("192.0.2.0/24")]);
+    }
+
+    #[test]
+    fn contains_follows_the_prefix() {
+        let block = cidr("192.0.2.77/24");
+        assert!(block.contains(&ip("192.0.2.0")));
+        assert!(block.contains(&ip("192.0.2.255")));
+        assert!(!block.contains(&ip("192.0.3.0")));
+        assert!(!block.contains(&ip("::ffff:192.0.2.1")));
+
+        assert!(cidr("0.0.0.0/0").contains(&ip("255.255.255.255")));
+        assert!(!cidr("0.0.0.0/0").contains(&ip("::1")));
+        assert!(cidr("::/0").contains(&ip("2001:db8::1")));
+        assert!(cidr("192.0.2.1/32").contains(&ip("192.0.2.1")));
+        assert!(!cidr("192.0.2.1/32").contains(&ip("192.0.2.2")));
+        assert!(cidr("2001:db8::1/128").contains(&ip("2001:db8::1")));
+        assert!(!cidr("2001:db8::1/128").contains(&ip("2001:db8::2")));
+
+        assert!(cidr("192.0.2.0/24").overlaps(&cidr("192.0.2.128/25")));
+        assert!(cidr("192.0.2.128/25").overlaps(&cidr("192.0.2.0/24")));
+        assert!(!cidr("192.0.2.0/25").overlaps(&cidr("192.0.2.128/25")));
+    }
+
+    #[test]
+    fn enclosing_masks_the_host_bits() {
+        assert_eq!(Cidr::enclosing(ip("192.0.2.77"), 24), cidr("192.0.2.0/24"));
+        assert_eq!(Cidr::enclosing(ip("192.0.2.77"), 0), cidr("0.0.0.0/0"));
+        assert_eq!(Cidr::enclosing(ip("192.0.2.77"), 32), cidr("192.0.2.77/32"));
+        assert_eq!(Cidr::enclosing(ip("192.0.2.77"), 64), cidr("192.0.2.77/32"));
+        assert_eq!(Cidr::enclosing(ip("2001:db8:1:2::5"), 64), cidr("2001:db8:1:2::/64"));
+        assert_eq!(Cidr::enclosing(ip("2001:db8::5"), 0), cidr("::/0"));
+        assert_eq!(Cidr::enclosing(ip("2001:db8::5"), 128), cidr("2001:db8::5/128"));
+        assert_eq!(Cidr::enclosing(ip("2001:db8::5"), 200), cidr("2001:db8::5/128"));
+    }
+
+    #[test]
+    fn parses_ip_patterns() {
+        assert_eq!(IpPattern::parse("192.0.2.0/24"), IpPattern::Cidr(cidr("192.0.2.0/24")));
+        assert_eq!(
+            IpPattern::parse("192.0.2.50-192.0.2.10"),
+            IpPattern::Range(ip("192.0.2.10"), ip("192.0.2.50"))
+        );
+        assert_eq!(IpPattern::parse("192.0.2.1-2001:db8::1"), IpPattern::Text("192.0.2.1-2001:db8::1".into()));
+        assert_eq!(IpPattern::parse("2001:DB8"), IpPattern::Text("2001:db8".into()));
+    }
+
+    #[test]
+    fn matches_by_text_block_and_range() {
+        let addr = ip("192.0.2.30");
+        assert!(IpPattern::parse("0.2.3").matches("192.0.2.30", Some(&addr)));
+        assert!(IpPattern::parse("2001:DB8").matches("2001:db8::1", None));
+        assert!(IpPattern::parse("192.0.2.0/27").matches("192.0.2.30", Some(&addr)));
+        assert!(!IpPattern::parse("192.0.2.0/28").matches("192.0.2.30", Some(&addr)));
+        assert!(!IpPattern::parse("192.0.2.0/24").matches("192.0.2.30", None));
+
+        let range = IpPattern::parse("192.0.2.50-192.0.2.30");
+        assert!(range.matches("192.0.2.30", Some(&addr)));
+        assert!(range.matches("192.0.2.50", Some(&ip("192.0.2.50"))));
+        assert!(!range.matches("192.0.2.51", Some(&ip("192.0.2.51"))));
+        assert!(!range.matches("::ffff:192.0.2.40", Some(&ip("::ffff:192.0.2.40"))));
+        let v6 = IpPattern::parse("2001:db8::1-2001:db8::ff");
+        assert!(v6.matches("2001:db8::10", Some(&ip("2001:db8::10"))));
+        assert!(!v6.matches("192.0.2.30", Some(&addr)));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::keymap::Action;
+
+/// Scores `text` against `pattern`, whose characters must all appear in
+/// order. Consecutive runs and matches at the start of a word score higher.
+fn fuzzy_score(pattern: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for wanted in pattern.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (pos..text.len()).find(|&i| text[i] == wanted)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        match last {
+            Some(prev) if prev + 1 == found => score += 5,
+            Some(prev) => score -= (found - prev - 1).min(5) as i32,
+            None => score -= found.min(5) as i32,
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Actions offered by the `:` palette for `input`, best match first. Both
+/// the description and the config name of an action are searched.
+pub fn matching_actions(input: &str) -> Vec<Action> {
+    let mut scored: Vec<(i32, Action)> = Action::ALL
+        .into_iter()
+        .filter(|action| action.in_palette())
+        .filter_map(|action| {
+            let name = action.name().replace('_', " ");
+            let score = fuzzy_score(input, action.description()).max(fuzzy_score(input, &name))?;
+            Some((score, action))
+        })
+        .collect();
+    scored.sort_by_key(|(score, _)| -score);
+    scored.into_iter().map(|(_, action)| action).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_and_runs_beat_scattered_matches() {
+        let prefix = fuzzy_score("ban", "ban subnet").unwrap();
+        let run = fuzzy_score("ban", "reban").unwrap();
+        let scattered = fuzzy_score("ban", "cobra canyon").unwrap();
+        assert!(prefix > run, "{prefix} vs {run}");
+        assert!(run > scattered, "{run} vs {scattered}");
+        assert_eq!(fuzzy_score("BAN", "ban subnet"), Some(prefix));
+        assert_eq!(fuzzy_score("b a n", "ban subnet"), Some(prefix));
+    }
+
+    #[test]
+    fn every_character_has_to_match_in_order() {
+        assert_eq!(fuzzy_score("nab", "ban"), None);
+        assert_eq!(fuzzy_score("bann", "ban"), None);
+        assert!(matching_actions("qqxz").is_empty());
+    }
+
+    #[test]
+    fn ranks_actions_by_score() {
+        assert_eq!(matching_actions("undo").first(), Some(&Action::Undo));
+        assert_eq!(matching_actions("export").first(), Some(&Action::Export));
+        assert_eq!(matching_actions("unban all").first(), Some(&Action::UnbanAll));
+        assert!(!matching_actions("unban").contains(&Action::Quit));
+    }
+
+    #[test]
+    fn empty_input_lists_every_palette_action() {
+        let all: Vec<Action> = Action::ALL.into_iter().filter(|action| action.in_palette()).collect();
+        assert!(!all.is_empty());
+        assert_eq!(matching_actions(""), all);
+        assert_eq!(matching_actions("  "), all);
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Subset of Python values that can come back from the fail2ban server.
+/// Arbitrary class instances (exceptions, `IPAddr`, ...) are kept as
+/// `Object` with whatever constructor arguments the pickle carried.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Tuple(Vec<Value>),
+    Dict(Vec<(Value, Value)>),
+    Set(Vec<Value>),
+    Global(String),
+    Object { class: String, args: Vec<Value> },
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            Value::Object { args, .. } => args.iter().find_map(|a| a.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(v) => Some(*v),
+            Value::Bool(v) => Some(*v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn items(&self) -> &[Value] {
+        match self {
+            Value::List(items) | Value::Tuple(items) | Value::Set(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// Looks up `key` in either a dict or a list of `(key, value)` pairs, which
+    /// is how fail2ban shapes its `status` replies.
+    pub fn lookup(&self, key: &str) -> Option<&Value> {
+        if let Value::Dict(pairs) = self {
+            return pairs
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v);
+        }
+        self.items().iter().find_map(|item| match item.items() {
+            [k, v] if k.as_str() == Some(key) => Some(v),
+            _ => None,
+        })
+    }
+
+    /// Renders the value roughly the way `fail2ban-client` prints it.
+    pub fn to_text(&self) -> String {
+        match self {
+            Value::None => "None".to_string(),
+            Value::Bool(true) => "True".to_string(),
+            Value::Bool(false) => "False".to_string(),
+            Value::Int(v) => v.to_string(),
+            Value::Float(v) => v.to_string(),
+            Value::Str(s) => s.clone(),
+            Value::Bytes(b) => String::from_utf8_lossy(b).to_string(),
+            Value::List(items) | Value::Tuple(items) | Value::Set(items) => items
+                .iter()
+                .map(|v| v.to_text())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Value::Dict(pairs) => pairs
+                .iter()
+                .map(|(k, v)| format!("{}: {}", k.to_text(), v.to_text()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Value::Global(name) => name.clone(),
+            Value::Object { class, .. } => self
+                .as_str()
+                .map(String::from)
+                .unwrap_or_else(|| class.clone()),
+        }
+    }
+}
+
+pub fn dumps_strings(items: &[&str]) -> Vec<u8> {
+    let mut out = vec![0x80, 2, b']', b'('];
+    for item in items {
+        out.push(b'X');
+        out.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        out.extend_from_slice(item.as_bytes());
+    }
+    out.push(b'e');
+    out.push(b'.');
+    out
+}
+
+enum Slot {
+    Mark,
+    /// A value and the memo keys it was put under. Containers are filled in
+    /// after they are memoized, so the memo copy is only taken once the value
+    /// leaves the stack.
+    Value(Value, Vec<usize>),
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let Some(end) = self.pos.checked_add(n).filter(|&end| end <= self.data.len()) else {
+            bail!("truncated pickle");
+        };
+        let out = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    }
+
+    fn u64(&mut self) -> Result<usize> {
+        let b = self.take(8)?;
+        let mut arr = [0u8; 8];
+        arr.copy_from_slice(b);
+        Ok(u64::from_le_bytes(arr) as usize)
+    }
+
+    fn line(&mut self) -> Result<&'a str> {
+        let rest = &self.data[self.pos..];
+        let end = rest
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| anyhow!("truncated pickle"))?;
+        self.pos += end + 1;
+        std::str::from_utf8(&rest[..end]).map_err(|_| anyhow!("invalid text in pickle"))
+    }
+
+    fn string(&mut self, n: usize) -> Result<String> {
+        Ok(String::from_utf8_lossy(self.take(n)?).to_string())
+    }
+}
+
+fn long_from_le(bytes: &[u8]) -> Result<i64> {
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+    if bytes.len() > 8 {
+        bail!("integer too large");
+    }
+    let fill = if bytes[bytes.len() - 1] & 0x80 != 0 { 0xff } else { 0 };
+    let mut arr = [fill; 8];
+    arr[..bytes.len()].copy_from_slice(bytes);
+    Ok(i64::from_le_bytes(arr))
+}
+
+fn unquote(text: &str) -> String {
+    let text = text.trim();
+    let inner = text
+        .strip_prefix('\'')
+        .and_then(|t| t.strip_suffix('\''))
+        .or_else(|| text.strip_prefix('"').and_then(|t| t.strip_suffix('"')))
+        .unwrap_or(text);
+    inner.to_string()
+}
+
+pub fn loads(data: &[u8]) -> Result<Value> {
+    let mut reader = Reader { data, pos: 0 };
+    let mut stack: Vec<Slot> = Vec::new();
+    let mut memo: HashMap<usize, Value> = HashMap::new();
+
+    fn push(stack: &mut Vec<Slot>, v: Value) {
+        stack.push(Slot::Value(v, Vec::new()));
+    }
+
+    fn settle(memo: &mut HashMap<usize, Value>, v: Value, keys: Vec<usize>) -> Value {
+        for key in keys {
+            memo.insert(key, v.clone());
+        }
+        v
+    }
+
+    fn pop(stack: &mut Vec<Slot>, memo: &mut HashMap<usize, Value>) -> Result<Value> {
+        match stack.pop() {
+            Some(Slot::Value(v, keys)) => Ok(settle(memo, v, keys)),
+            _ => bail!("pickle stack underflow"),
+        }
+    }
+
+    fn pop_mark(stack: &mut Vec<Slot>, memo: &mut HashMap<usize, Value>) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        loop {
+            match stack.pop() {
+                Some(Slot::Value(v, keys)) => items.push(settle(memo, v, keys)),
+                Some(Slot::Mark) => break,
+                None => bail!("pickle mark not found"),
+            }
+        }
+        items.reverse();
+        Ok(items)
+    }
+
+    fn top(stack: &mut [Slot]) -> Result<&mut Value> {
+        match stack.last_mut() {
+            Some(Slot::Value(v, _)) => Ok(v),
+            _ => bail!("pickle stack underflow"),
+        }
+    }
+
+    /// Memoizes the top of the stack under `key`.
+    fn put(stack: &mut [Slot], memo: &mut HashMap<usize, Value>, key: usize) -> Result<()> {
+        match stack.last_mut() {
+            Some(Slot::Value(v, keys)) => {
+                memo.insert(key, v.clone());
+                keys.push(key);
+                Ok(())
+            }
+            _ => bail!("pickle stack underflow"),
+        }
+    }
+
+    fn extend(target: &mut Value, items: Vec<Value>) -> Result<()> {
+        match target {
+            Value::List(list) | Value::Set(list) => list.extend(items),
+            Value::Object { .. } => {}
+            _ => bail!("append to non-list in pickle"),
+        }
+        Ok(())
+    }
+
+    fn set_items(target: &mut Value, items: Vec<Value>) -> Result<()> {
+        let Value::Dict(pairs) = target else {
+            bail!("setitem on non-dict in pickle");
+        };
+        let mut iter = items.into_iter();
+        while let (Some(k), Some(v)) = (iter.next(), iter.next()) {
+            pairs.push((k, v));
+        }
+        Ok(())
+    }
+
+    loop {
+        let op = reader.byte()?;
+        match op {
+            0x80 => {
+                reader.byte()?;
+            }
+            0x95 => {
+                reader.take(8)?;
+            }
+            b'.' => return pop(&mut stack, &mut memo),
+            b'(' => stack.push(Slot::Mark),
+            b'0' => {
+                if let Some(Slot::Value(v, keys)) = stack.pop() {
+                    settle(&mut memo, v, keys);
+                }
+            }
+            b'1' => {
+                pop_mark(&mut stack, &mut memo)?;
+            }
+            b'2' => {
+                let v = top(&mut stack)?.clone();
+                push(&mut stack, v);
+            }
+            b'N' => push(&mut stack, Value::None),
+            0x88 => push(&mut stack, Value::Bool(true)),
+            0x89 => push(&mut stack, Value::Bool(false)),
+            b'I' => {
+                let line = reader.line()?;
+                let v = match line {
+                    "00" => Value::Bool(false),
+                    "01" => Value::Bool(true),
+                    _ => Value::Int(line.parse().map_err(|_| anyhow!("bad INT in pickle"))?),
+                };
+                push(&mut stack, v);
+            }
+            b'L' => {
+                let line = reader.line()?.trim_end_matches('L');
+                let v = line.parse().map_err(|_| anyhow!("bad LONG in pickle"))?;
+                push(&mut stack, Value::Int(v));
+            }
+            b'J' => {
+                let b = reader.take(4)?;
+                let v = i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as i64;
+                push(&mut stack, Value::Int(v));
+            }
+            b'K' => {
+                let v = reader.byte()? as i64;
+                push(&mut stack, Value::Int(v));
+            }
+            b'M' => {
+                let b = reader.take(2)?;
+                let v = u16::from_le_bytes([b[0], b[1]]) as i64;
+                push(&mut stack, Value::Int(v));
+            }
+            0x8a => {
+                let n = reader.byte()? as usize;
+                let v = long_from_le(reader.take(n)?)?;
+                push(&mut stack, Value::Int(v));
+            }
+            0x8b => {
+                let n = reader.u32()?;
+                let v = long_from_le(reader.take(n)?)?;
+                push(&mut stack, Value::Int(v));
+            }
+            b'F' => {
+                let line = reader.line()?;
+                let v = line.parse().map_err(|_| anyhow!("bad FLOAT in pickle"))?;
+                push(&mut stack, Value::Float(v));
+            }
+            b'G' => {
+                let b = reader.take(8)?;
+                let mut arr = [0u8; 8];
+                arr.copy_from_slice(b);
+                push(&mut stack, Value::Float(f64::from_be_bytes(arr)));
+            }
+            b'S' | b'V' => {
+                let v = unquote(reader.line()?);
+                push(&mut stack, Value::Str(v));
+            }
+            b'T' | b'X' => {
+                let n = reader.u32()?;
+                let v = reader.string(n)?;
+                push(&mut stack, Value::Str(v));
+            }
+            b'U' | 0x8c => {
+                let n = reader.byte()? as usize;
+                let v = reader.string(n)?;
+                push(&mut stack, Value::Str(v));
+            }
+            0x8d => {
+                let n = reader.u64()?;
+                let v = reader.string(n)?;
+                push(&mut stack, Value::Str(v));
+            }
+            b'B' => {
+                let n = reader.u32()?;
+                let v = reader.take(n)?.to_vec();
+                push(&mut stack, Value::Bytes(v));
+            }
+            b'C' => {
+                let n = reader.byte()? as usize;
+                let v = reader.take(n)?.to_vec();
+                push(&mut stack, Value::Bytes(v));
+            }
+            0x8e | 0x96 => {
+                let n = reader.u64()?;
+                let v = reader.take(n)?.to_vec();
+                push(&mut stack, Value::Bytes(v));
+            }
+            b']' => push(&mut stack, Value::List(Vec::new())),
+            b'l' => {
+                let items = pop_mark(&mut stack, &mut memo)?;
+                push(&mut stack, Value::List(items));
+            }
+            b'a' => {
+                let v = pop(&mut stack, &mut memo)?;
+                extend(top(&mut stack)?, vec![v])?;
+            }
+            b'e' => {
+                let items = pop_mark(&mut stack, &mut memo)?;
+                extend(top(&mut stack)?, items)?;
+            }
+            b')' => push(&mut stack, Value::Tuple(Vec::new())),
+            b't' => {
+                let items = pop_mark(&mut stack, &mut memo)?;
+                push(&mut stack, Value::Tuple(items));
+            }
+            0x85..=0x87 => {
+                let n = (op - 0x84) as usize;
+                let mut items = Vec::with_capacity(n);
+                for _ in 0..n {
+                    items.push(pop(&mut stack, &mut memo)?);
+                }
+                items.reverse();
+                push(&mut stack, Value::Tuple(items));
+            }
+            b'}' => push(&mut stack, Value::Dict(Vec::new())),
+            b'd' => {
+                let items = pop_mark(&mut stack, &mut memo)?;
+                let mut dict = Value::Dict(Vec::new());
+                set_items(&mut dict, items)?;
+                push(&mut stack, dict);
+            }
+            b's' => {
+                let v = pop(&mut stack, &mut memo)?;
+                let k = pop(&mut stack, &mut memo)?;
+                set_items(top(&mut stack)?, vec![k, v])?;
+            }
+            b'u' => {
+                let items = pop_mark(&mut stack, &mut memo)?;
+                set_items(top(&mut stack)?, items)?;
+            }
+            0x8f => push(&mut stack, Value::Set(Vec::new())),
+            0x90 => {
+                let items = pop_mark(&mut stack, &mut memo)?;
+                extend(top(&mut stack)?, items)?;
+            }
+            0x91 => {
+                let items = pop_mark(&mut stack, &mut memo)?;
+                push(&mut stack, Value::Set(items));
+            }
+            b'c' => {
+                let module = reader.line()?.to_string();
+                let name = reader.line()?;
+                push(&mut stack, Value::Global(format!("{module}.{name}")));
+            }
+            0x93 => {
+                let name = pop(&mut stack, &mut memo)?.to_text();
+                let module = pop(&mut stack, &mut memo)?.to_text();
+                push(&mut stack, Value::Global(format!("{module}.{name}")));
+            }
+            b'R' | 0x81 => {
+                let args = pop(&mut stack, &mut memo)?;
+                let class = pop(&mut stack, &mut memo)?.to_text();
+                push(&mut stack, Value::Object {
+                    class,
+                    args: args.items().to_vec(),
+                });
+            }
+            0x92 => {
+                pop(&mut stack, &mut memo)?;
+                let args = pop(&mut stack, &mut memo)?;
+                let class = pop(&mut stack, &mut memo)?.to_text();
+                push(&mut stack, Value::Object {
+                    class,
+                    args: args.items().to_vec(),
+                });
+            }
+            b'b' => {
+                pop(&mut stack, &mut memo)?;
+            }
+            b'p' => {
+                let idx = reader.line()?.parse().map_err(|_| anyhow!("bad PUT in pickle"))?;
+                put(&mut stack, &mut memo, idx)?;
+            }
+            b'q' => {
+                let idx = reader.byte()? as usize;
+                put(&mut stack, &mut memo, idx)?;
+            }
+            b'r' => {
+                let idx = reader.u32()?;
+                put(&mut stack, &mut memo, idx)?;
+            }
+            0x94 => {
+                let idx = memo.len();
+                put(&mut stack, &mut memo, idx)?;
+            }
+            b'g' | b'h' | b'j' => {
+                let idx = match op {
+                    b'g' => reader.line()?.parse().map_err(|_| anyhow!("bad GET in pickle"))?,
+                    b'h' => reader.byte()? as usize,
+                    _ => reader.u32()?,
+                };
+                let pending = stack.iter().rev().find_map(|slot| match slot {
+                    Slot::Value(v, keys) if keys.contains(&idx) => Some(v),
+                    _ => None,
+                });
+                let v = pending
+                    .or_else(|| memo.get(&idx))
+                    .cloned()
+                    .ok_or_else(|| anyhow!("unknown memo key {idx} in pickle"))?;
+                push(&mut stack, v);
+            }
+            other => bail!("unsupported pickle opcode 0x{other:02x}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strs(items: &[&str]) -> Value {
+        Value::List(items.iter().map(|s| Value::Str(s.to_string())).collect())
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let value = loads(&dumps_strings(&["set", "sshd", "banip", "192.0.2.1"])).unwrap();
+        assert_eq!(value, strs(&["set", "sshd", "banip", "192.0.2.1"]));
+    }
+
+    #[test]
+    fn reads_a_status_reply() {
+        // pickle.dumps((0, [('Currently failed', 0), ('Total failed', -3),
+        // ('Banned IP list', ['192.0.2.1'])]), protocol=2)
+        let data = b"\x80\x02K\x00]q\x00(X\x10\x00\x00\x00Currently failedq\x01K\x00\x86q\x02X\x0c\x00\x00\x00Total failedq\x03J\xfd\xff\xff\xff\x86q\x04X\x0e\x00\x00\x00Banned IP listq\x05]q\x06X\t\x00\x00\x00192.0.2.1q\x07a\x86q\x08e\x86q\t.";
+        let value = loads(data).unwrap();
+        let [code, status] = value.items() else {
+            panic!("not a pair: {value:?}");
+        };
+        assert_eq!(code.as_int(), Some(0));
+        assert_eq!(status.lookup("Total failed"), Some(&Value::Int(-3)));
+        assert_eq!(status.lookup("Banned IP list"), Some(&strs(&["192.0.2.1"])));
+    }
+
+    #[test]
+    fn memo_gets_see_items_added_after_the_put() {
+        let banned = strs(&["192.0.2.1", "192.0.2.2"]);
+        // l = ['192.0.2.1', '192.0.2.2']; pickle.dumps([l, l], protocol=2)
+        let v2 = b"\x80\x02]q\x00(]q\x01(X\t\x00\x00\x00192.0.2.1q\x02X\t\x00\x00\x00192.0.2.2q\x03eh\x01e.";
+        assert_eq!(loads(v2).unwrap(), Value::List(vec![banned.clone(), banned.clone()]));
+        // The same with protocol 4: a frame, short strings and MEMOIZE.
+        let v4 = b"\x80\x04\x95#\x00\x00\x00\x00\x00\x00\x00]\x94(]\x94(\x8c\t192.0.2.1\x94\x8c\t192.0.2.2\x94eh\x01e.";
+        assert_eq!(loads(v4).unwrap(), Value::List(vec![banned.clone(), banned]));
+
+        // d = {'a': 1}; pickle.dumps((d, [d]), protocol=2)
+        let dict = b"\x80\x02}q\x00X\x01\x00\x00\x00aq\x01K\x01s]q\x02h\x00a\x86q\x03.";
+        let d = Value::Dict(vec![(Value::Str("a".to_string()), Value::Int(1))]);
+        assert_eq!(loads(dict).unwrap(), Value::Tuple(vec![d.clone(), Value::List(vec![d])]));
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let data = dumps_strings(&["status"]);
+        assert!(loads(&data[..data.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn rejects_lengths_past_the_end() {
+        // BINUNICODE8, BINBYTES8 and FRAME with a length near usize::MAX.
+        for op in [0x8d, 0x8e, 0x96] {
+            let mut data = vec![0x80, 0x04, op];
+            data.extend_from_slice(&u64::MAX.to_le_bytes());
+            data.extend_from_slice(b"abc.");
+            assert!(loads(&data).is_err(), "opcode {op:#x}");
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::net::IpAddr;
+
+use crate::net::{Cidr, IpPattern};
+use crate::parse_duration_string;
+
+/// What a query is evaluated against: one banned IP in one jail.
+pub struct Candidate<'a> {
+    pub ip: &'a str,
+    pub addr: Option<&'a IpAddr>,
+    pub jail: &'a str,
+    pub remaining: Option<u64>,
+    pub permanent: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    Ip(IpPattern),
+    Cidr(Cidr),
+    Jail(String),
+    Left(Op, u64),
+    Permanent,
+}
+
+impl Predicate {
+    fn matches(&self, c: &Candidate) -> bool {
+        match self {
+            Predicate::Ip(pattern) => pattern.matches(c.ip, c.addr),
+            Predicate::Cidr(cidr) => c.addr.is_some_and(|addr| cidr.contains(addr)),
+            Predicate::Jail(name) => match name.strip_suffix('*') {
+                Some(prefix) => c.jail.to_lowercase().starts_with(prefix),
+                None => c.jail.eq_ignore_ascii_case(name),
+            },
+            Predicate::Permanent => c.permanent,
+            Predicate::Left(op, _) if c.permanent => matches!(op, Op::Gt | Op::Ge),
+            Predicate::Left(op, secs) => c.remaining.is_some_and(|left| match op {
+                Op::Lt => left < *secs,
+                Op::Le => left <= *secs,
+                Op::Gt => left > *secs,
+                Op::Ge => left >= *secs,
+                Op::Eq => left == *secs,
+            }),
+        }
+    }
+}
+
+/// A parsed `/` filter. Terms next to each other must all match, `or` (or a
+/// comma) separates alternatives, `not`/`!` negates and parentheses group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    All,
+    Pred(Predicate),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let tokens = tokenize(input);
+        if tokens.is_empty() {
+            return Ok(Query::All);
+        }
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(Token::Close) => Err("unmatched ')'".to_string()),
+            Some(token) => Err(format!("unexpected '{}'", token.text())),
+        }
+    }
+
+    pub fn matches(&self, c: &Candidate) -> bool {
+        match self {
+            Query::All => true,
+            Query::Pred(pred) => pred.matches(c),
+            Query::Not(inner) => !inner.matches(c),
+            Query::And(a, b) => a.matches(c) && b.matches(c),
+            Query::Or(a, b) => a.matches(c) || b.matches(c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Or,
+    And,
+    Not,
+    Word(String),
+}
+
+impl Token {
+    fn text(&self) -> &str {
+        match self {
+            Token::Open => "(",
+            Token::Close => ")",
+            Token::Or => "or",
+            Token::And => "and",
+            Token::Not => "not",
+            Token::Word(word) => word,
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let flush = |word: &mut String, tokens: &mut Vec<Token>| {
+        if word.is_empty() {
+            return;
+        }
+        tokens.push(match word.to_lowercase().as_str() {
+            "or" | "||" => Token::Or,
+            "and" | "&&" => Token::And,
+            "not" => Token::Not,
+            _ => Token::Word(word.clone()),
+        });
+        word.clear();
+    };
+    for ch in input.chars() {
+        match ch {
+            '(' | ')' | ',' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(match ch {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Or,
+                });
+            }
+            '!' if word.is_empty() => tokens.push(Token::Not),
+            c if c.is_whitespace() => flush(&mut word, &mut tokens),
+            c => word.push(c),
+        }
+    }
+    flush(&mut word, &mut tokens);
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut query = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Word(_) | Token::Not | Token::Open) => {}
+                _ => return Ok(query),
+            }
+            query = Query::And(Box::new(query), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Query, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Query::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(Token::Word(word)) => parse_predicate(&word).map(Query::Pred),
+            Some(token) => Err(format!("expected a term before '{}'", token.text())),
+            None => Err("expected a term at the end".to_string()),
+        }
+    }
+}
+
+fn parse_predicate(word: &str) -> Result<Predicate, String> {
+    let lower = word.to_lowercase();
+    if lower == "permanent" {
+        return Ok(Predicate::Permanent);
+    }
+    if let Some(rest) = lower.strip_prefix("left") {
+        let (op, value) = [
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+            ("=", Op::Eq),
+        ]
+        .into_iter()
+        .find_map(|(text, op)| rest.strip_prefix(text).map(|value| (op, value)))
+        .ok_or_else(|| format!("'{word}' needs <, <=, >, >= or = and a duration"))?;
+        let secs = if value.chars().all(|c| c.is_ascii_digit()) {
+            value.parse::<u64>().ok()
+        } else {
+            parse_duration_string(value)
+        };
+        return secs
+            .map(|secs| Predicate::Left(op, secs))
+            .ok_or_else(|| format!("invalid duration '{value}', try 30m, 1h or 7d"));
+    }
+    if let Some((field, value)) = word.split_once(':') {
+        let field = field.to_lowercase();
+        match field.as_str() {
+            "jail" if !value.is_empty() => return Ok(Predicate::Jail(value.to_lowercase())),
+            "ip" if !value.is_empty() => return Ok(Predicate::Ip(IpPattern::parse(value))),
+            "cidr" => {
+                return Cidr::parse(value)
+                    .map(Predicate::Cidr)
+                    .ok_or_else(|| format!("invalid CIDR '{value}'"));
+            }
+            "country" | "asn" | "host" => {
+                return Err(format!("{field}: needs IP enrichment, which is not available"));
+            }
+            "jail" | "ip" => return Err(format!("{field}: needs a value")),
+            _ if field.chars().all(|c| c.is_ascii_alphabetic())
+                && !field.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                return Err(format!("unknown field '{field}:'"));
+            }
+            _ => {}
+        }
+    }
+    if let Some((addr, _)) = word.split_once('/')
+        && addr.parse::<IpAddr>().is_ok()
+    {
+        return Cidr::parse(word)
+            .map(Predicate::Cidr)
+            .ok_or_else(|| format!("invalid CIDR '{word}'"));
+    }
+    Ok(Predicate::Ip(IpPattern::parse(word)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Query {
+        Query::parse(input).unwrap_or_else(|err| panic!("{input}: {err}"))
+    }
+
+    fn error(input: &str) -> String {
+        Query::parse(input).expect_err(input)
+    }
+
+    fn word(text: &str) -> Query {
+        Query::Pred(Predicate::Ip(IpPattern::parse(text)))
+    }
+
+    fn and(a: Query, b: Query) -> Query {
+        Query::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: Query, b: Query) -> Query {
+        Query::Or(Box::new(a), Box::new(b))
+    }
+
+    fn not(a: Query) -> Query {
+        Query::Not(Box::new(a))
+    }
+
+    /// Whether `query` matches `ip` in `jail` with `remaining` seconds left,
+    /// `None` standing for a permanent ban.
+    fn hit(query: &str, ip: &str, jail: &str, remaining: Option<u64>) -> bool {
+        let addr = ip.parse().ok();
+        parse(query).matches(&Candidate {
+            ip,
+            addr: addr.as_ref(),
+            jail,
+            remaining,
+            permanent: remaining.is_none(),
+        })
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(parse(""), Query::All);
+        assert_eq!(parse("a b or c"), or(and(word("a"), word("b")), word("c")));
+        assert_eq!(parse("a, b and c"), or(word("a"), and(word("b"), word("c"))));
+        assert_eq!(parse("a || b && c"), parse("a or b and c"));
+        assert_eq!(parse("a (b or c)"), and(word("a"), or(word("b"), word("c"))));
+        assert_eq!(parse("a or b or c"), or(or(word("a"), word("b")), word("c")));
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term() {
+        assert_eq!(parse("!a b"), and(not(word("a")), word("b")));
+        assert_eq!(parse("NOT a"), not(word("a")));
+        assert_eq!(parse("!(a, b)"), not(or(word("a"), word("b"))));
+        assert_eq!(parse("!!a"), not(not(word("a"))));
+        // Only a leading `!` negates; inside a word it is part of the text.
+        assert_eq!(parse("a!b"), word("a!b"));
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert_eq!(error("(a"), "missing ')'");
+        assert_eq!(error("a)"), "unmatched ')'");
+        assert_eq!(error("()"), "expected a term before ')'");
+        assert_eq!(error("a or"), "expected a term at the end");
+        assert_eq!(error("or a"), "expected a term before 'or'");
+        assert_eq!(error("a and or b"), "expected a term before 'or'");
+        assert_eq!(error("!"), "expected a term at the end");
+    }
+
+    #[test]
+    fn parses_durations_left() {
+        assert_eq!(parse("left<=1h30m"), Query::Pred(Predicate::Left(Op::Le, 5400)));
+        assert_eq!(parse("LEFT>=7d"), Query::Pred(Predicate::Left(Op::Ge, 7 * 86_400)));
+        assert_eq!(parse("left=90"), Query::Pred(Predicate::Left(Op::Eq, 90)));
+        assert_eq!(parse("left<10m"), Query::Pred(Predicate::Left(Op::Lt, 600)));
+        assert_eq!(parse("left>0"), Query::Pred(Predicate::Left(Op::Gt, 0)));
+        assert!(error("left<").contains("invalid duration"));
+        assert!(error("left<soon").contains("invalid duration"));
+        assert!(error("left10m").contains("needs <, <=, >, >= or ="));
+    }
+
+    #[test]
+    fn parses_fields() {
+        assert_eq!(parse("jail:SSHD"), Query::Pred(Predicate::Jail("sshd".to_string())));
+        assert_eq!(parse("ip:203.0.113"), word("203.0.113"));
+        assert!(matches!(parse("cidr:10.0.0.0/8"), Query::Pred(Predicate::Cidr(_))));
+        assert!(matches!(parse("10.0.0.0/8"), Query::Pred(Predicate::Cidr(_))));
+        assert_eq!(error("cidr:10.0.0.0/33"), "invalid CIDR '10.0.0.0/33'");
+        assert_eq!(error("10.0.0.0/33"), "invalid CIDR '10.0.0.0/33'");
+        assert_eq!(error("jail:"), "jail: needs a value");
+        assert!(error("country:de").contains("enrichment"));
+    }
+
+    #[test]
+    fn tells_ipv6_from_unknown_fields() {
+        // Hex digits before the colon make it part of an address.
+        assert_eq!(parse("fe80::1"), word("fe80::1"));
+        assert_eq!(parse("dead:beef"), word("dead:beef"));
+        assert_eq!(parse("2001:db8"), word("2001:db8"));
+        assert_eq!(parse("::1"), word("::1"));
+        assert_eq!(error("colour:red"), "unknown field 'colour:'");
+        assert_eq!(error("Host:example"), "host: needs IP enrichment, which is not available");
+    }
+
+    #[test]
+    fn matches_candidates() {
+        assert!(hit("jail:ssh*", "192.0.2.1", "sshd", Some(60)));
+        assert!(!hit("jail:ssh", "192.0.2.1", "sshd", Some(60)));
+        assert!(hit("jail:SSHD 192.0.2.0/24", "192.0.2.1", "sshd", Some(60)));
+        assert!(!hit("!cidr:192.0.2.0/24", "192.0.2.1", "sshd", Some(60)));
+        assert!(hit("192.0.2.0-192.0.2.9", "192.0.2.1", "sshd", Some(60)));
+        assert!(hit("jail:nginx* or left<2m", "192.0.2.1", "sshd", Some(60)));
+        assert!(!hit("jail:nginx* or left<1m", "192.0.2.1", "sshd", Some(60)));
+        assert!(hit("left<=60", "192.0.2.1", "sshd", Some(60)));
+        assert!(hit("left=60", "192.0.2.1", "sshd", Some(60)));
+        assert!(!hit("permanent", "192.0.2.1", "sshd", Some(60)));
+    }
+
+    #[test]
+    fn permanent_bans_outlast_any_time_left() {
+        assert!(hit("permanent", "192.0.2.1", "recidive", None));
+        assert!(hit("left>1000d", "192.0.2.1", "recidive", None));
+        assert!(hit("left>=1h", "192.0.2.1", "recidive", None));
+        assert!(!hit("left<1000d", "192.0.2.1", "recidive", None));
+        assert!(!hit("left<=1h", "192.0.2.1", "recidive", None));
+        assert!(!hit("left=0", "192.0.2.1", "recidive", None));
+        assert!(hit("left<10m or permanent", "192.0.2.1", "recidive", None));
+    }
+}
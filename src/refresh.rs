@@ -0,0 +1,238 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+use anyhow::{bail, Result};
+
+use crate::backend::{Fail2banBackend, JailSummary};
+use crate::{fetch_status, JailStatus};
+
+pub type SharedBackend = Arc<Mutex<Box<dyn Fail2banBackend>>>;
+
+pub fn lock_backend(backend: &SharedBackend) -> MutexGuard<'_, Box<dyn Fail2banBackend>> {
+    backend.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The shared backend as the worker sees it: the lock is taken for one call
+/// at a time, so UI actions get their turn between the calls of a refresh,
+/// and every call first checks that the refresh is still wanted.
+struct PerCall<'a> {
+    backend: &'a SharedBackend,
+    canceled: &'a (dyn Fn() -> bool + Sync),
+}
+
+impl PerCall<'_> {
+    fn lock(&self) -> Result<MutexGuard<'_, Box<dyn Fail2banBackend>>> {
+        if (self.canceled)() {
+            bail!("refresh canceled");
+        }
+        Ok(lock_backend(self.backend))
+    }
+}
+
+impl Fail2banBackend for PerCall<'_> {
+    fn jails(&mut self) -> Result<Vec<String>> {
+        self.lock()?.jails()
+    }
+
+    fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+        self.lock()?.jail_status(jail)
+    }
+
+    fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        self.lock()?.get(jail, args)
+    }
+
+    fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        self.lock()?.set(jail, args)
+    }
+
+    fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        self.lock()?.ban(jail, ips)
+    }
+
+    fn own_ban_times(&mut self) -> bool {
+        self.lock().is_ok_and(|mut backend| backend.own_ban_times())
+    }
+
+    fn ban_with_time(&mut self, jail: &str, ips: &[&str], bantime: i64) -> Result<()> {
+        self.lock()?.ban_with_time(jail, ips, bantime)
+    }
+
+    fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        self.lock()?.unban(jail, ips)
+    }
+
+    fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+        self.lock()?.banned_in(ip)
+    }
+}
+
+pub struct RefreshResult {
+    pub generation: u64,
+    pub result: Result<Vec<JailStatus>>,
+}
+
+/// Runs `fetch_status` on a worker thread. Every request gets a new
+/// generation number; a fetch notices when a newer request has arrived and
+/// gives up early, and its result is dropped by `poll`. The worker shares the
+/// UI's backend but only holds it for one call at a time.
+pub struct Refresher {
+    requests: Sender<u64>,
+    results: Receiver<RefreshResult>,
+    latest: Arc<AtomicU64>,
+    pending: bool,
+}
+
+impl Refresher {
+    pub fn spawn(backend: SharedBackend) -> Self {
+        let (request_tx, request_rx) = channel::<u64>();
+        let (result_tx, result_rx) = channel();
+        let latest = Arc::new(AtomicU64::new(0));
+        let worker_latest = Arc::clone(&latest);
+
+        thread::spawn(move || {
+            while let Ok(mut generation) = request_rx.recv() {
+                while let Ok(newer) = request_rx.try_recv() {
+                    generation = newer;
+                }
+                if generation != worker_latest.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let canceled = || worker_latest.load(Ordering::SeqCst) != generation;
+                let result = fetch_status(&mut PerCall {
+                    backend: &backend,
+                    canceled: &canceled,
+                });
+                if result_tx.send(RefreshResult { generation, result }).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests: request_tx,
+            results: result_rx,
+            latest,
+            pending: false,
+        }
+    }
+
+    pub fn request(&mut self) {
+        let generation = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        self.pending = self.requests.send(generation).is_ok();
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    pub fn poll(&mut self) -> Option<Result<Vec<JailStatus>>> {
+        loop {
+            match self.results.try_recv() {
+                Ok(done) if done.generation == self.latest.load(Ordering::SeqCst) => {
+                    self.pending = false;
+                    return Some(done.result);
+                }
+                Ok(_) => continue,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    self.pending = false;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    /// The demo server, taking `DELAY` to answer each call.
+    struct Slow(MemoryBackend);
+
+    const DELAY: Duration = Duration::from_millis(10);
+
+    impl Fail2banBackend for Slow {
+        fn jails(&mut self) -> Result<Vec<String>> {
+            thread::sleep(DELAY);
+            self.0.jails()
+        }
+
+        fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+            thread::sleep(DELAY);
+            self.0.jail_status(jail)
+        }
+
+        fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+            thread::sleep(DELAY);
+            self.0.get(jail, args)
+        }
+
+        fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+            self.0.set(jail, args)
+        }
+
+        fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+            self.0.ban(jail, ips)
+        }
+
+        fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+            self.0.unban(jail, ips)
+        }
+
+        fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+            self.0.banned_in(ip)
+        }
+    }
+
+    #[test]
+    fn the_ui_gets_the_backend_during_a_refresh() {
+        let backend: SharedBackend = Arc::new(Mutex::new(Box::new(Slow(MemoryBackend::demo()))));
+        let mut refresher = Refresher::spawn(Arc::clone(&backend));
+        refresher.request();
+        thread::sleep(DELAY * 3);
+
+        // A whole refresh of the four demo jails takes 21 calls.
+        let asked = Instant::now();
+        lock_backend(&backend).unban("sshd", &["192.0.2.9"]).unwrap();
+        assert!(asked.elapsed() < DELAY * 8, "waited {:?} for the backend", asked.elapsed());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let jails = loop {
+            if let Some(result) = refresher.poll() {
+                break result.unwrap();
+            }
+            assert!(Instant::now() < deadline, "refresh did not finish");
+            thread::sleep(DELAY);
+        };
+        assert_eq!(jails.len(), 4);
+    }
+
+    #[test]
+    fn a_newer_request_replaces_the_running_one() {
+        let backend: SharedBackend = Arc::new(Mutex::new(Box::new(Slow(MemoryBackend::demo()))));
+        let mut refresher = Refresher::spawn(Arc::clone(&backend));
+        refresher.request();
+        thread::sleep(DELAY * 3);
+        lock_backend(&backend).unban("sshd", &["192.0.2.9"]).unwrap();
+        refresher.request();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let jails = loop {
+            if let Some(result) = refresher.poll() {
+                break result.unwrap();
+            }
+            assert!(Instant::now() < deadline, "refresh did not finish");
+            thread::sleep(DELAY);
+        };
+        let sshd = jails.iter().find(|j| j.name == "sshd").unwrap();
+        assert!(sshd.ips.iter().all(|entry| entry.ip != "192.0.2.9"));
+        assert!(!refresher.is_pending());
+    }
+}
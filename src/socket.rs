@@ -0,0 +1,392 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::backend::{Fail2banBackend, JailSummary};
+use crate::pickle::{dumps_strings, loads, Value};
+
+pub const DEFAULT_SOCKET: &str = "/var/run/fail2ban/fail2ban.sock";
+
+const END_STRING: &[u8] = b"<F2B_END_COMMAND>";
+const CLOSE_STRING: &[u8] = b"<F2B_CLOSE_COMMAND>";
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Talks to the fail2ban server over its Unix socket using the same
+/// pickle-framed protocol as `fail2ban-client`, keeping one connection open
+/// for the lifetime of the backend.
+#[derive(Debug)]
+pub struct SocketBackend {
+    path: PathBuf,
+    stream: Option<UnixStream>,
+}
+
+impl SocketBackend {
+    pub fn connect(path: impl Into<PathBuf>) -> Result<Self> {
+        let mut backend = Self {
+            path: path.into(),
+            stream: None,
+        };
+        backend.command(&["ping"])?;
+        Ok(backend)
+    }
+
+    fn open(&self) -> Result<UnixStream> {
+        let stream = UnixStream::connect(&self.path)
+            .with_context(|| format!("failed to connect to {}", self.path.display()))?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        Ok(stream)
+    }
+
+    fn read_reply(stream: &mut UnixStream) -> Result<Vec<u8>> {
+        let mut reply = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !reply.ends_with(END_STRING) {
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                bail!("fail2ban server closed the connection");
+            }
+            reply.extend_from_slice(&buf[..n]);
+        }
+        reply.truncate(reply.len() - END_STRING.len());
+        Ok(reply)
+    }
+
+    /// Sends `args` and waits for the reply. A kept connection the server has
+    /// since closed fails on write, and only then is the command sent again on
+    /// a new one. A command that was sent is never repeated: the server may
+    /// have carried out a `banip` whose reply got lost.
+    fn command(&mut self, args: &[&str]) -> Result<Value> {
+        let mut request = dumps_strings(args);
+        request.extend_from_slice(END_STRING);
+        let kept = self.stream.take().filter(|mut stream| stream.write_all(&request).is_ok());
+        let mut stream = match kept {
+            Some(stream) => stream,
+            None => {
+                let mut stream = self.open()?;
+                stream.write_all(&request)?;
+                stream
+            }
+        };
+        let reply = Self::read_reply(&mut stream)?;
+        self.stream = Some(stream);
+        let value = loads(&reply).context("invalid reply from fail2ban server")?;
+        match value.items() {
+            [code, result] if code.as_int() == Some(0) => Ok(result.clone()),
+            [_, err] => Err(anyhow!(err.to_text())),
+            _ => Err(anyhow!("unexpected reply from fail2ban server")),
+        }
+    }
+}
+
+impl Drop for SocketBackend {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.as_mut() {
+            let mut msg = CLOSE_STRING.to_vec();
+            msg.extend_from_slice(END_STRING);
+            stream.write_all(&msg).ok();
+        }
+    }
+}
+
+fn count(value: Option<&Value>) -> Option<u32> {
+    value
+        .and_then(|v| v.as_int())
+        .and_then(|v| u32::try_from(v).ok())
+}
+
+impl Fail2banBackend for SocketBackend {
+    fn jails(&mut self) -> Result<Vec<String>> {
+        let status = self.command(&["status"])?;
+        let list = status
+            .lookup("Jail list")
+            .map(|v| v.to_text())
+            .unwrap_or_default();
+        Ok(list
+            .split(',')
+            .map(|j| j.trim())
+            .filter(|j| !j.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    fn jail_status(&mut self, jail: &str) -> Result<JailSummary> {
+        let status = self.command(&["status", jail])?;
+        let actions = status.lookup("Actions");
+        Ok(JailSummary {
+            currently_banned: count(actions.and_then(|a| a.lookup("Currently banned"))),
+            total_banned: count(actions.and_then(|a| a.lookup("Total banned"))),
+            ips: actions
+                .and_then(|a| a.lookup("Banned IP list"))
+                .map(|list| list.items().iter().map(|ip| ip.to_text()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    fn get(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        let mut full = vec!["get", jail];
+        full.extend_from_slice(args);
+        Ok(self.command(&full)?.to_text())
+    }
+
+    fn set(&mut self, jail: &str, args: &[&str]) -> Result<String> {
+        let mut full = vec!["set", jail];
+        full.extend_from_slice(args);
+        Ok(self.command(&full)?.to_text())
+    }
+
+    fn ban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let mut full = vec!["set", jail, "banip"];
+        full.extend_from_slice(ips);
+        self.command(&full).map(|_| ())
+    }
+
+    fn unban(&mut self, jail: &str, ips: &[&str]) -> Result<()> {
+        let mut full = vec!["set", jail, "unbanip"];
+        full.extend_from_slice(ips);
+        self.command(&full).map(|_| ())
+    }
+
+    fn banned_in(&mut self, ip: &str) -> Result<Vec<String>> {
+        let reply = self.command(&["banned", ip])?;
+        let mut jails = Vec::new();
+        collect_strings(&reply, &mut jails);
+        Ok(jails)
+    }
+}
+
+fn collect_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::Str(s) => out.push(s.clone()),
+        other => other.items().iter().for_each(|item| collect_strings(item, out)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+
+    /// What the fake server does with a command.
+    enum Reply {
+        Send(Value),
+        /// Hang up without answering, as if the reply got lost.
+        Hangup,
+        /// Answer, then close the connection.
+        SendAndClose(Value),
+    }
+
+    fn ok(result: Value) -> Reply {
+        Reply::Send(Value::Tuple(vec![Value::Int(0), result]))
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Str(s.to_string())
+    }
+
+    fn pair(key: &str, value: Value) -> Value {
+        Value::Tuple(vec![text(key), value])
+    }
+
+    /// Pickles `value` the way Python's protocol 2 does, for the subset of
+    /// values the fake server sends.
+    fn dumps(value: &Value, out: &mut Vec<u8>) {
+        match value {
+            Value::None => out.push(b'N'),
+            Value::Int(v) => {
+                out.push(b'J');
+                out.extend_from_slice(&(*v as i32).to_le_bytes());
+            }
+            Value::Str(s) => {
+                out.push(b'X');
+                out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                out.extend_from_slice(s.as_bytes());
+            }
+            Value::List(items) => {
+                out.extend_from_slice(b"](");
+                items.iter().for_each(|item| dumps(item, out));
+                out.push(b'e');
+            }
+            Value::Tuple(items) => {
+                out.push(b'(');
+                items.iter().for_each(|item| dumps(item, out));
+                out.push(b't');
+            }
+            other => panic!("the fake server can't send {other:?}"),
+        }
+    }
+
+    /// A fail2ban server on a temporary socket. Every command it gets is
+    /// recorded, and each reply is written in two pieces to exercise the
+    /// framing.
+    struct FakeServer {
+        path: PathBuf,
+        commands: Arc<Mutex<Vec<String>>>,
+        connections: Arc<AtomicUsize>,
+        closed: Arc<AtomicUsize>,
+    }
+
+    impl FakeServer {
+        fn start(name: &str, handler: fn(&str) -> Reply) -> Self {
+            let path = std::env::temp_dir().join(format!("f2bs-{}-{name}.sock", std::process::id()));
+            std::fs::remove_file(&path).ok();
+            let listener = UnixListener::bind(&path).unwrap();
+            let commands = Arc::new(Mutex::new(Vec::new()));
+            let connections = Arc::new(AtomicUsize::new(0));
+            let closed = Arc::new(AtomicUsize::new(0));
+            let server = Self {
+                path,
+                commands: Arc::clone(&commands),
+                connections: Arc::clone(&connections),
+                closed: Arc::clone(&closed),
+            };
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else {
+                        break;
+                    };
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    serve(&mut stream, handler, &commands);
+                    drop(stream);
+                    closed.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+            server
+        }
+
+        fn commands(&self) -> Vec<String> {
+            self.commands.lock().unwrap().clone()
+        }
+
+        fn wait_closed(&self, count: usize) {
+            for _ in 0..500 {
+                if self.closed.load(Ordering::SeqCst) >= count {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(2));
+            }
+            panic!("the fake server kept the connection open");
+        }
+    }
+
+    impl Drop for FakeServer {
+        fn drop(&mut self) {
+            std::fs::remove_file(&self.path).ok();
+        }
+    }
+
+    fn serve(stream: &mut UnixStream, handler: fn(&str) -> Reply, commands: &Mutex<Vec<String>>) {
+        let mut pending = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let Some(end) = pending.windows(END_STRING.len()).position(|w| w == END_STRING) else {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => pending.extend_from_slice(&buf[..n]),
+                }
+                continue;
+            };
+            let request: Vec<u8> = pending.drain(..end + END_STRING.len()).take(end).collect();
+            if request == CLOSE_STRING {
+                return;
+            }
+            let args: Vec<String> = loads(&request).unwrap().items().iter().map(Value::to_text).collect();
+            let command = args.join(" ");
+            commands.lock().unwrap().push(command.clone());
+            let (value, close) = match handler(&command) {
+                Reply::Send(value) => (value, false),
+                Reply::SendAndClose(value) => (value, true),
+                Reply::Hangup => return,
+            };
+            let mut reply = vec![0x80, 2];
+            dumps(&value, &mut reply);
+            reply.push(b'.');
+            reply.extend_from_slice(END_STRING);
+            let (first, rest) = reply.split_at(reply.len() / 2);
+            stream.write_all(first).unwrap();
+            thread::sleep(Duration::from_millis(5));
+            stream.write_all(rest).unwrap();
+            if close {
+                return;
+            }
+        }
+    }
+
+    fn fail2ban(command: &str) -> Reply {
+        match command {
+            "ping" => ok(text("pong")),
+            "status" => ok(Value::List(vec![
+                pair("Number of jail", Value::Int(2)),
+                pair("Jail list", text("sshd, recidive")),
+            ])),
+            "status sshd" => ok(Value::List(vec![
+                pair("Filter", Value::List(vec![pair("Currently failed", Value::Int(1))])),
+                pair(
+                    "Actions",
+                    Value::List(vec![
+                        pair("Currently banned", Value::Int(2)),
+                        pair("Total banned", Value::Int(7)),
+                        pair("Banned IP list", Value::List(vec![text("192.0.2.1"), text("192.0.2.2")])),
+                    ]),
+                ),
+            ])),
+            "get sshd bantime" => ok(Value::Int(600)),
+            "banned 192.0.2.1" => ok(Value::List(vec![text("sshd")])),
+            "set sshd banip 192.0.2.9" => Reply::Hangup,
+            "set sshd unbanip 192.0.2.1" => Reply::SendAndClose(Value::Tuple(vec![Value::Int(0), Value::Int(1)])),
+            other => Reply::Send(Value::Tuple(vec![
+                Value::Int(1),
+                text(&format!("Invalid command '{other}'")),
+            ])),
+        }
+    }
+
+    #[test]
+    fn answers_over_one_connection() {
+        let server = FakeServer::start("one", fail2ban);
+        let mut backend = SocketBackend::connect(&server.path).unwrap();
+        assert_eq!(backend.jails().unwrap(), ["sshd", "recidive"]);
+        let status = backend.jail_status("sshd").unwrap();
+        assert_eq!(status.currently_banned, Some(2));
+        assert_eq!(status.total_banned, Some(7));
+        assert_eq!(status.ips, ["192.0.2.1", "192.0.2.2"]);
+        assert_eq!(backend.get("sshd", &["bantime"]).unwrap(), "600");
+        assert_eq!(backend.banned_in("192.0.2.1").unwrap(), ["sshd"]);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn server_errors_come_back_as_errors() {
+        let server = FakeServer::start("errors", fail2ban);
+        let mut backend = SocketBackend::connect(&server.path).unwrap();
+        let err = backend.get("nope", &["bantime"]).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid command 'get nope bantime'");
+    }
+
+    #[test]
+    fn reconnects_when_the_server_closed_the_connection() {
+        let server = FakeServer::start("reconnect", fail2ban);
+        let mut backend = SocketBackend::connect(&server.path).unwrap();
+        backend.unban("sshd", &["192.0.2.1"]).unwrap();
+        server.wait_closed(1);
+        assert_eq!(backend.jails().unwrap(), ["sshd", "recidive"]);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn a_sent_command_is_not_sent_again() {
+        let server = FakeServer::start("lost", fail2ban);
+        let mut backend = SocketBackend::connect(&server.path).unwrap();
+        assert!(backend.ban("sshd", &["192.0.2.9"]).is_err());
+        let bans = server.commands().iter().filter(|c| c.contains("banip")).count();
+        assert_eq!(bans, 1);
+        assert_eq!(backend.get("sshd", &["bantime"]).unwrap(), "600");
+    }
+}
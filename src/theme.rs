@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+
+use ratatui::style::{Color, Modifier, Style};
+
+/// Named style roles used by the renderer. Every colour in the UI comes from
+/// one of these, so a theme only has to fill in this struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub accent: Style,
+    pub info: Style,
+    pub text: Style,
+    pub muted: Style,
+    pub border: Style,
+    pub border_active: Style,
+    pub jail_selected: Style,
+    pub ip_selected: Style,
+    pub marked: Style,
+    pub fresh: Style,
+    pub error: Style,
+    pub confirm_button: Style,
+    pub cancel_button: Style,
+}
+
+pub const BUILTIN: [&str; 4] = ["dark", "light", "ansi", "mono"];
+
+const ROLES: [&str; 13] = [
+    "accent",
+    "info",
+    "text",
+    "muted",
+    "border",
+    "border_active",
+    "jail_selected",
+    "ip_selected",
+    "marked",
+    "fresh",
+    "error",
+    "confirm_button",
+    "cancel_button",
+];
+
+fn fg(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+fn bold() -> Style {
+    Style::default().add_modifier(Modifier::BOLD)
+}
+
+fn on(bg: Color, fg: Color) -> Style {
+    Style::default().bg(bg).fg(fg).add_modifier(Modifier::BOLD)
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let orange = Color::Rgb(255, 184, 108);
+        let cyan = Color::Rgb(88, 196, 220);
+        let theme = match name {
+            "dark" => Self {
+                accent: fg(orange).add_modifier(Modifier::BOLD),
+                info: fg(Color::Rgb(120, 200, 210)),
+                text: fg(Color::Rgb(190, 190, 190)),
+                muted: fg(Color::Rgb(160, 160, 160)),
+                border: fg(Color::Rgb(80, 80, 80)),
+                border_active: fg(orange),
+                jail_selected: on(orange, Color::Rgb(20, 20, 20)),
+                ip_selected: on(cyan, Color::Rgb(10, 10, 10)),
+                marked: fg(Color::Rgb(240, 210, 110)).add_modifier(Modifier::BOLD),
+                fresh: fg(Color::Rgb(140, 220, 140)),
+                error: fg(Color::Rgb(240, 120, 120)),
+                confirm_button: on(orange, Color::Rgb(20, 20, 20)),
+                cancel_button: on(cyan, Color::Rgb(10, 10, 10)),
+            },
+            "light" => Self {
+                accent: fg(Color::Rgb(175, 85, 0)).add_modifier(Modifier::BOLD),
+                info: fg(Color::Rgb(0, 110, 140)),
+                text: fg(Color::Rgb(60, 60, 60)),
+                muted: fg(Color::Rgb(100, 100, 100)),
+                border: fg(Color::Rgb(170, 170, 170)),
+                border_active: fg(Color::Rgb(175, 85, 0)),
+                jail_selected: on(Color::Rgb(255, 200, 140), Color::Rgb(20, 20, 20)),
+                ip_selected: on(Color::Rgb(150, 215, 235), Color::Rgb(10, 10, 10)),
+                marked: fg(Color::Rgb(140, 70, 160)).add_modifier(Modifier::BOLD),
+                fresh: fg(Color::Rgb(0, 130, 40)),
+                error: fg(Color::Rgb(190, 20, 20)),
+                confirm_button: on(Color::Rgb(255, 200, 140), Color::Rgb(20, 20, 20)),
+                cancel_button: on(Color::Rgb(150, 215, 235), Color::Rgb(10, 10, 10)),
+            },
+            "ansi" => Self {
+                accent: fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                info: fg(Color::Cyan),
+                text: Style::default(),
+                muted: fg(Color::Gray),
+                border: fg(Color::DarkGray),
+                border_active: fg(Color::Yellow),
+                jail_selected: on(Color::Yellow, Color::Black),
+                ip_selected: on(Color::Cyan, Color::Black),
+                marked: fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                fresh: fg(Color::Green),
+                error: fg(Color::Red),
+                confirm_button: on(Color::Yellow, Color::Black),
+                cancel_button: on(Color::Cyan, Color::Black),
+            },
+            "mono" => {
+                let reversed = Style::default().add_modifier(Modifier::REVERSED);
+                Self {
+                    accent: bold(),
+                    info: Style::default(),
+                    text: Style::default(),
+                    muted: Style::default().add_modifier(Modifier::DIM),
+                    border: Style::default(),
+                    border_active: bold(),
+                    jail_selected: reversed.add_modifier(Modifier::BOLD),
+                    ip_selected: reversed,
+                    marked: bold(),
+                    fresh: Style::default().add_modifier(Modifier::UNDERLINED),
+                    error: bold(),
+                    confirm_button: reversed.add_modifier(Modifier::BOLD),
+                    cancel_button: reversed,
+                }
+            }
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    fn role_mut(&mut self, role: &str) -> Option<&mut Style> {
+        Some(match role {
+            "accent" => &mut self.accent,
+            "info" => &mut self.info,
+            "text" => &mut self.text,
+            "muted" => &mut self.muted,
+            "border" => &mut self.border,
+            "border_active" => &mut self.border_active,
+            "jail_selected" => &mut self.jail_selected,
+            "ip_selected" => &mut self.ip_selected,
+            "marked" => &mut self.marked,
+            "fresh" => &mut self.fresh,
+            "error" => &mut self.error,
+            "confirm_button" => &mut self.confirm_button,
+            "cancel_button" => &mut self.cancel_button,
+            _ => return None,
+        })
+    }
+
+    /// Picks the theme named in the config, which can be a built-in one or a
+    /// `[themes.<name>]` table that overrides roles of its `base` theme.
+    /// `NO_COLOR` always wins and selects `mono`. Problems are returned as
+    /// messages and fall back to the default dark theme.
+    pub fn load(
+        name: Option<&str>,
+        colors: Option<&str>,
+        custom: &BTreeMap<String, BTreeMap<String, String>>,
+    ) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return (Self::builtin("mono").unwrap_or_else(Self::dark), warnings);
+        }
+        let name = name.unwrap_or("dark");
+        let mut theme = match (Self::builtin(name), custom.get(name)) {
+            (_, Some(roles)) => {
+                let base = roles.get("base").map(String::as_str).unwrap_or("dark");
+                let mut theme = Self::builtin(base).unwrap_or_else(|| {
+                    warnings.push(format!("theme '{name}': unknown base theme '{base}'"));
+                    Self::dark()
+                });
+                for (role, spec) in roles.iter().filter(|(role, _)| *role != "base") {
+                    match (theme.role_mut(role), parse_style(spec)) {
+                        (None, _) => warnings.push(format!(
+                            "theme '{name}': unknown role '{role}' (roles: {})",
+                            ROLES.join(", ")
+                        )),
+                        (_, Err(err)) => warnings.push(format!("theme '{name}', {role}: {err}")),
+                        (Some(slot), Ok(style)) => *slot = style,
+                    }
+                }
+                theme
+            }
+            (Some(theme), None) => theme,
+            (None, None) => {
+                warnings.push(format!(
+                    "unknown theme '{name}' (built-in: {})",
+                    BUILTIN.join(", ")
+                ));
+                Self::dark()
+            }
+        };
+
+        let depth = match colors.unwrap_or("auto") {
+            "auto" => ColorDepth::detect(),
+            "truecolor" | "24bit" => ColorDepth::TrueColor,
+            "256" => ColorDepth::Indexed,
+            "16" => ColorDepth::Basic,
+            "none" => return (Self::builtin("mono").unwrap_or_else(Self::dark), warnings),
+            other => {
+                warnings.push(format!("unknown colors setting '{other}' (auto, truecolor, 256, 16, none)"));
+                ColorDepth::detect()
+            }
+        };
+        theme.downgrade(depth);
+        (theme, warnings)
+    }
+
+    fn dark() -> Self {
+        Self::builtin("dark").expect("dark theme is built in")
+    }
+
+    fn downgrade(&mut self, depth: ColorDepth) {
+        for role in ROLES {
+            if let Some(style) = self.role_mut(role) {
+                style.fg = style.fg.map(|c| depth.convert(c));
+                style.bg = style.bg.map(|c| depth.convert(c));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorDepth {
+    TrueColor,
+    Indexed,
+    Basic,
+}
+
+impl ColorDepth {
+    /// Guesses from `COLORTERM` and `TERM` the way most terminal programs do.
+    fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        Self::guess(&colorterm, &term)
+    }
+
+    /// `*-direct` terminfo entries such as `xterm-direct` take 24-bit colour.
+    fn guess(colorterm: &str, term: &str) -> Self {
+        let colorterm = colorterm.to_lowercase();
+        let term = term.to_lowercase();
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") || term.ends_with("-direct") {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Indexed
+        } else {
+            Self::Basic
+        }
+    }
+
+    fn convert(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::TrueColor, _) => color,
+            (Self::Indexed, Color::Rgb(r, g, b)) => Color::Indexed(rgb_to_256(r, g, b)),
+            (Self::Basic, Color::Rgb(r, g, b)) => rgb_to_basic(r, g, b),
+            (Self::Basic, Color::Indexed(i)) if i >= 16 => {
+                let (r, g, b) = indexed_to_rgb(i);
+                rgb_to_basic(r, g, b)
+            }
+            _ => color,
+        }
+    }
+}
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    if index >= 232 {
+        let level = 8 + (index - 232) * 10;
+        return (level, level, level);
+    }
+    let i = index.saturating_sub(16);
+    (
+        CUBE_LEVELS[(i / 36) as usize],
+        CUBE_LEVELS[(i / 6 % 6) as usize],
+        CUBE_LEVELS[(i % 6) as usize],
+    )
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Nearest entry of the xterm 6x6x6 cube or grey ramp.
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    (16..=255u8)
+        .min_by_key(|i| distance(indexed_to_rgb(*i), (r, g, b)))
+        .unwrap_or(16)
+}
+
+fn rgb_to_basic(r: u8, g: u8, b: u8) -> Color {
+    const BASIC: [(Color, (u8, u8, u8)); 16] = [
+        (Color::Black, (0, 0, 0)),
+        (Color::Red, (205, 0, 0)),
+        (Color::Green, (0, 205, 0)),
+        (Color::Yellow, (205, 205, 0)),
+        (Color::Blue, (0, 0, 238)),
+        (Color::Magenta, (205, 0, 205)),
+        (Color::Cyan, (0, 205, 205)),
+        (Color::Gray, (229, 229, 229)),
+        (Color::DarkGray, (127, 127, 127)),
+        (Color::LightRed, (255, 0, 0)),
+        (Color::LightGreen, (0, 255, 0)),
+        (Color::LightYellow, (255, 255, 0)),
+        (Color::LightBlue, (92, 92, 255)),
+        (Color::LightMagenta, (255, 0, 255)),
+        (Color::LightCyan, (0, 255, 255)),
+        (Color::White, (255, 255, 255)),
+    ];
+    BASIC
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// Parses a style such as `#ffb86c bold`, `black on cyan` or `reversed`.
+fn parse_style(spec: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        let word = word.to_lowercase();
+        style = match word.as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underline" | "underlined" => style.add_modifier(Modifier::UNDERLINED),
+            "reversed" | "reverse" => style.add_modifier(Modifier::REVERSED),
+            "on" => {
+                let bg = words.next().ok_or("'on' needs a background colour")?;
+                style.bg(parse_color(bg)?)
+            }
+            _ => style.fg(parse_color(&word)?),
+        };
+    }
+    Ok(style)
+}
+
+fn parse_color(text: &str) -> Result<Color, String> {
+    if let Some(hex) = text.strip_prefix('#')
+        && hex.len() == 6
+        && let Ok(value) = u32::from_str_radix(hex, 16)
+    {
+        return Ok(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    if let Ok(index) = text.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+    text.parse::<Color>()
+        .map_err(|_| format!("unknown colour '{text}' (use a name, #rrggbb or 0-255)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_the_colour_depth() {
+        assert_eq!(ColorDepth::guess("truecolor", "xterm"), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::guess("24bit", ""), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::guess("", "xterm-direct"), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::guess("", "tmux-direct"), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::guess("", "xterm-256color"), ColorDepth::Indexed);
+        assert_eq!(ColorDepth::guess("", "screen-256color"), ColorDepth::Indexed);
+        assert_eq!(ColorDepth::guess("", "xterm"), ColorDepth::Basic);
+        assert_eq!(ColorDepth::guess("", ""), ColorDepth::Basic);
+    }
+
+    #[test]
+    fn parses_colours() {
+        assert_eq!(parse_color("#ffb86c"), Ok(Color::Rgb(255, 184, 108)));
+        assert_eq!(parse_color("#FFB86C"), Ok(Color::Rgb(255, 184, 108)));
+        assert_eq!(parse_color("208"), Ok(Color::Indexed(208)));
+        assert_eq!(parse_color("cyan"), Ok(Color::Cyan));
+        assert_eq!(parse_color("lightred"), Ok(Color::LightRed));
+        assert!(parse_color("#ffb86").is_err());
+        assert!(parse_color("#gggggg").is_err());
+        assert!(parse_color("256").is_err());
+        assert!(parse_color("orangeish").is_err());
+    }
+
+    #[test]
+    fn parses_styles() {
+        assert_eq!(parse_style(""), Ok(Style::default()));
+        assert_eq!(
+            parse_style("#ffb86c bold"),
+            Ok(Style::default().fg(Color::Rgb(255, 184, 108)).add_modifier(Modifier::BOLD))
+        );
+        assert_eq!(parse_style("Black on CYAN"), Ok(Style::default().fg(Color::Black).bg(Color::Cyan)));
+        assert_eq!(
+            parse_style("reverse underlined"),
+            Ok(Style::default().add_modifier(Modifier::REVERSED | Modifier::UNDERLINED))
+        );
+        assert_eq!(parse_style("red on"), Err("'on' needs a background colour".to_string()));
+        assert!(parse_style("bold sparkly").is_err());
+    }
+
+    #[test]
+    fn maps_rgb_to_the_nearest_palette_entry() {
+        assert_eq!(rgb_to_256(0, 0, 0), 16);
+        assert_eq!(rgb_to_256(255, 255, 255), 231);
+        assert_eq!(rgb_to_256(255, 0, 0), 196);
+        assert_eq!(rgb_to_256(95, 135, 175), 67);
+        assert_eq!(rgb_to_256(128, 128, 128), 244);
+        assert_eq!(indexed_to_rgb(rgb_to_256(255, 184, 108)), (255, 175, 95));
+
+        assert_eq!(rgb_to_basic(0, 0, 0), Color::Black);
+        assert_eq!(rgb_to_basic(255, 255, 255), Color::White);
+        assert_eq!(rgb_to_basic(200, 10, 10), Color::Red);
+        assert_eq!(rgb_to_basic(88, 196, 220), Color::Cyan);
+        assert_eq!(rgb_to_basic(130, 130, 130), Color::DarkGray);
+
+        assert_eq!(ColorDepth::Indexed.convert(Color::Rgb(255, 0, 0)), Color::Indexed(196));
+        assert_eq!(ColorDepth::Basic.convert(Color::Indexed(196)), Color::LightRed);
+        assert_eq!(ColorDepth::Basic.convert(Color::Indexed(3)), Color::Indexed(3));
+        assert_eq!(ColorDepth::TrueColor.convert(Color::Rgb(1, 2, 3)), Color::Rgb(1, 2, 3));
+    }
+}